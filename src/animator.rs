@@ -0,0 +1,624 @@
+use super::buffer::FrameBuffer;
+use super::color::Color;
+use super::dma::{MatrixDma, Transfer};
+use super::rgb_matrix::RgbMatrix;
+
+/// An error from `Animator::step()`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AnimatorError<E> {
+    /// Swapping frame buffers failed, and the DMA transfer is no longer running.
+    Dma(E),
+
+    /// The DMA transfer stopped after an earlier error, so nothing is being shown.
+    Halted,
+}
+
+/// Runs the render, flush, and swap loop for simple animations.
+///
+/// Each step of the animation is a closure that draws on the matrix. After the DMA has sent the
+/// configured number of frames, the next closure is run and the frame buffer it was drawn into is
+/// swapped with the one being shown. The matrix must have a pending frame buffer (from
+/// `RgbMatrix::set_pending`), and the transfer must be showing a different frame buffer.
+///
+/// Swapping frame buffers stops and restarts the DMA transfer. Any frame hook set on the transfer
+/// is moved to the restarted transfer, and is only dropped if swapping fails.
+///
+/// With `with_startup_ramp_frames`, the brightness is ramped up from 0 over the first frames
+/// instead of being shown at full brightness straight away.
+pub struct Animator<
+    'm,
+    'a,
+    ColorType,
+    M,
+    const WIDTH: usize,
+    const HEIGHT: usize,
+    const CHAIN_LENGTH: usize,
+    const COLOR_DEPTH: usize,
+    const PER_FRAME_DENOMINATOR: u8,
+    const WORDS_PER_PLANE: usize,
+    const SCANLINES_PER_FRAME: usize,
+    const BITMAP_ELEMENTS: usize,
+> where
+    M: MatrixDma<
+        WIDTH,
+        HEIGHT,
+        CHAIN_LENGTH,
+        COLOR_DEPTH,
+        PER_FRAME_DENOMINATOR,
+        WORDS_PER_PLANE,
+        SCANLINES_PER_FRAME,
+    >,
+{
+    matrix: &'m mut RgbMatrix<
+        'a,
+        ColorType,
+        WIDTH,
+        HEIGHT,
+        CHAIN_LENGTH,
+        COLOR_DEPTH,
+        PER_FRAME_DENOMINATOR,
+        WORDS_PER_PLANE,
+        SCANLINES_PER_FRAME,
+        BITMAP_ELEMENTS,
+    >,
+
+    // The running transfer, or what is left of it if swapping frame buffers failed. This is only
+    // `None` while the frame buffers are being swapped.
+    #[allow(clippy::type_complexity)]
+    output: Option<
+        Result<
+            Transfer<
+                'a,
+                M,
+                WIDTH,
+                HEIGHT,
+                CHAIN_LENGTH,
+                COLOR_DEPTH,
+                PER_FRAME_DENOMINATOR,
+                WORDS_PER_PLANE,
+                SCANLINES_PER_FRAME,
+            >,
+            (
+                M,
+                &'a mut FrameBuffer<
+                    WIDTH,
+                    HEIGHT,
+                    CHAIN_LENGTH,
+                    COLOR_DEPTH,
+                    PER_FRAME_DENOMINATOR,
+                    WORDS_PER_PLANE,
+                    SCANLINES_PER_FRAME,
+                >,
+            ),
+        >,
+    >,
+
+    frames_per_step: usize,
+
+    frames_since_step: usize,
+
+    // How many frames the brightness is ramped up over, and how many of them have been shown.
+    startup_ramp_frames: u16,
+
+    ramp_frame: u16,
+}
+
+impl<
+        'm,
+        'a,
+        ColorType,
+        M,
+        const WIDTH: usize,
+        const HEIGHT: usize,
+        const CHAIN_LENGTH: usize,
+        const COLOR_DEPTH: usize,
+        const PER_FRAME_DENOMINATOR: u8,
+        const WORDS_PER_PLANE: usize,
+        const SCANLINES_PER_FRAME: usize,
+        const BITMAP_ELEMENTS: usize,
+    >
+    Animator<
+        'm,
+        'a,
+        ColorType,
+        M,
+        WIDTH,
+        HEIGHT,
+        CHAIN_LENGTH,
+        COLOR_DEPTH,
+        PER_FRAME_DENOMINATOR,
+        WORDS_PER_PLANE,
+        SCANLINES_PER_FRAME,
+        BITMAP_ELEMENTS,
+    >
+where
+    ColorType: Default + PartialEq + Copy + Color<COLOR_DEPTH>,
+    M: MatrixDma<
+        WIDTH,
+        HEIGHT,
+        CHAIN_LENGTH,
+        COLOR_DEPTH,
+        PER_FRAME_DENOMINATOR,
+        WORDS_PER_PLANE,
+        SCANLINES_PER_FRAME,
+    >,
+{
+    /// Create an animator that advances one step every `frames_per_step` frames sent by the DMA.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the matrix does not have a pending frame buffer, or if `frames_per_step` is 0.
+    ///
+    /// # Safety
+    ///
+    /// Each step restarts the transfer with a frame buffer of the matrix, so the same requirements
+    /// as `MatrixDma::start_reference()` apply to every frame buffer the matrix is given. In
+    /// particular, neither the animator nor the transfer from `into_parts()` can be
+    /// `core::mem::forget()`-ten while the transfer is running, unless the frame buffers are
+    /// borrowed for `'static`.
+    pub unsafe fn new(
+        matrix: &'m mut RgbMatrix<
+            'a,
+            ColorType,
+            WIDTH,
+            HEIGHT,
+            CHAIN_LENGTH,
+            COLOR_DEPTH,
+            PER_FRAME_DENOMINATOR,
+            WORDS_PER_PLANE,
+            SCANLINES_PER_FRAME,
+            BITMAP_ELEMENTS,
+        >,
+        transfer: Transfer<
+            'a,
+            M,
+            WIDTH,
+            HEIGHT,
+            CHAIN_LENGTH,
+            COLOR_DEPTH,
+            PER_FRAME_DENOMINATOR,
+            WORDS_PER_PLANE,
+            SCANLINES_PER_FRAME,
+        >,
+        frames_per_step: usize,
+    ) -> Self {
+        assert!(
+            matrix.has_pending_frame_buffer(),
+            "the matrix needs a pending frame buffer to draw into"
+        );
+        assert!(frames_per_step > 0, "frames_per_step cannot be 0");
+        Self {
+            matrix,
+            output: Some(Ok(transfer)),
+            frames_per_step,
+            frames_since_step: 0,
+            startup_ramp_frames: 0,
+            ramp_frame: 0,
+        }
+    }
+
+    /// Ramp the brightness up from 0 over the first `startup_ramp_frames` frames sent by the DMA.
+    ///
+    /// Driving a cold panel to full brightness instantly can show a visible flash. The ramp starts
+    /// from the frame being shown, so this should be called straight after `new()`.
+    pub fn with_startup_ramp_frames(mut self, startup_ramp_frames: u16) -> Self {
+        self.startup_ramp_frames = startup_ramp_frames;
+        if let Some(Ok(transfer)) = self.output.as_mut() {
+            if self.ramp_frame < startup_ramp_frames {
+                let brightness = ramp_brightness(
+                    self.matrix.brightness(),
+                    self.ramp_frame,
+                    startup_ramp_frames,
+                );
+                self.matrix
+                    .write_temporary_brightness(transfer.frame_buffer_mut(), brightness);
+            }
+        }
+        self
+    }
+
+    /// Wait for the DMA to send `frames_per_step` frames, then draw the next step and show it.
+    pub fn step<F>(&mut self, frame: F) -> Result<(), AnimatorError<M::Error>>
+    where
+        F: FnOnce(
+            &mut RgbMatrix<
+                'a,
+                ColorType,
+                WIDTH,
+                HEIGHT,
+                CHAIN_LENGTH,
+                COLOR_DEPTH,
+                PER_FRAME_DENOMINATOR,
+                WORDS_PER_PLANE,
+                SCANLINES_PER_FRAME,
+                BITMAP_ELEMENTS,
+            >,
+        ),
+    {
+        let Some(Ok(transfer)) = self.output.as_mut() else {
+            return Err(AnimatorError::Halted);
+        };
+        while self.frames_since_step < self.frames_per_step {
+            if transfer.poll() {
+                self.frames_since_step += 1;
+                if self.ramp_frame < self.startup_ramp_frames {
+                    self.ramp_frame += 1;
+                    // Not `self.ramp_brightness()`, as the transfer is still borrowed.
+                    let brightness = ramp_brightness(
+                        self.matrix.brightness(),
+                        self.ramp_frame,
+                        self.startup_ramp_frames,
+                    );
+                    self.matrix
+                        .write_temporary_brightness(transfer.frame_buffer_mut(), brightness);
+                }
+            }
+        }
+        self.frames_since_step = 0;
+        frame(self.matrix);
+        self.swap()
+    }
+
+    /// Run each step in turn, stopping at the first error.
+    pub fn run<I, F>(&mut self, frames: I) -> Result<(), AnimatorError<M::Error>>
+    where
+        I: IntoIterator<Item = F>,
+        F: FnOnce(
+            &mut RgbMatrix<
+                'a,
+                ColorType,
+                WIDTH,
+                HEIGHT,
+                CHAIN_LENGTH,
+                COLOR_DEPTH,
+                PER_FRAME_DENOMINATOR,
+                WORDS_PER_PLANE,
+                SCANLINES_PER_FRAME,
+                BITMAP_ELEMENTS,
+            >,
+        ),
+    {
+        for frame in frames {
+            self.step(frame)?;
+        }
+        Ok(())
+    }
+
+    /// Show the pending frame buffer of the matrix, and give the matrix the frame buffer that was
+    /// being shown to draw into next.
+    ///
+    /// A frame left open by the step (with `RgbMatrix::begin_frame`) is ended first, before the
+    /// transfer is stopped, so that its pixels are shown.
+    fn swap(&mut self) -> Result<(), AnimatorError<M::Error>> {
+        let mut transfer = match self.output.take() {
+            Some(Ok(transfer)) => transfer,
+            halted => {
+                self.output = halted;
+                return Err(AnimatorError::Halted);
+            }
+        };
+        // Inside a frame `set_pending()` would leave the swap to `end_frame()`, so end it now.
+        self.matrix.end_frame();
+        let frame_hook = transfer.take_frame_hook();
+        let (matrix_dma, shown) = match transfer.stop() {
+            Ok(parts) => parts,
+            Err((error, matrix_dma, frame_buffer)) => {
+                self.output = Some(Err((matrix_dma, frame_buffer)));
+                return Err(AnimatorError::Dma(error));
+            }
+        };
+        // There is always a pending frame buffer, as it is checked in `new()`, and with no frame
+        // open `set_pending()` replaces it straight away.
+        let drawn = self.matrix.set_pending(shown).unwrap();
+        if self.ramp_frame < self.startup_ramp_frames {
+            let brightness = self.ramp_brightness();
+            self.matrix.write_temporary_brightness(drawn, brightness);
+        }
+        // Safety: the caller of `new()` has upheld the requirements of `start_reference()` for the
+        // frame buffers of the matrix.
+        match unsafe { matrix_dma.start_reference(drawn) } {
+            Ok(mut transfer) => {
+                if let Some(frame_hook) = frame_hook {
+                    transfer.set_frame_hook(frame_hook);
+                }
+                self.output = Some(Ok(transfer));
+                Ok(())
+            }
+            Err((error, matrix_dma, frame_buffer)) => {
+                self.output = Some(Err((matrix_dma, frame_buffer)));
+                Err(AnimatorError::Dma(error))
+            }
+        }
+    }
+
+    /// The brightness for the current frame of the startup ramp.
+    fn ramp_brightness(&self) -> u8 {
+        ramp_brightness(
+            self.matrix.brightness(),
+            self.ramp_frame,
+            self.startup_ramp_frames,
+        )
+    }
+
+    /// Take back the matrix and the transfer.
+    ///
+    /// If swapping frame buffers failed, the DMA and the frame buffer it was using are returned
+    /// instead of the transfer.
+    #[allow(clippy::type_complexity)]
+    pub fn into_parts(
+        self,
+    ) -> (
+        &'m mut RgbMatrix<
+            'a,
+            ColorType,
+            WIDTH,
+            HEIGHT,
+            CHAIN_LENGTH,
+            COLOR_DEPTH,
+            PER_FRAME_DENOMINATOR,
+            WORDS_PER_PLANE,
+            SCANLINES_PER_FRAME,
+            BITMAP_ELEMENTS,
+        >,
+        Result<
+            Transfer<
+                'a,
+                M,
+                WIDTH,
+                HEIGHT,
+                CHAIN_LENGTH,
+                COLOR_DEPTH,
+                PER_FRAME_DENOMINATOR,
+                WORDS_PER_PLANE,
+                SCANLINES_PER_FRAME,
+            >,
+            (
+                M,
+                &'a mut FrameBuffer<
+                    WIDTH,
+                    HEIGHT,
+                    CHAIN_LENGTH,
+                    COLOR_DEPTH,
+                    PER_FRAME_DENOMINATOR,
+                    WORDS_PER_PLANE,
+                    SCANLINES_PER_FRAME,
+                >,
+            ),
+        >,
+    ) {
+        // The output is only taken while swapping, which always puts it back.
+        (self.matrix, self.output.unwrap())
+    }
+}
+
+/// The brightness to show `frame` frames into a startup ramp of `frames` frames, working up to
+/// `target`.
+///
+/// The brightness never decreases from one frame to the next, and is `target` once `frame`
+/// reaches `frames`.
+fn ramp_brightness(target: u8, frame: u16, frames: u16) -> u8 {
+    let frames = frames as u32;
+    let frame = (frame as u32).min(frames);
+    (target as u32 * (frame + 1) / (frames + 1)) as u8
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use embedded_graphics_core::pixelcolor::{Rgb888, RgbColor};
+
+    use crate::config::MatrixConfig;
+    use crate::declare_frame_buffer;
+    use crate::matrix_word::MatrixWord;
+
+    // Test cases are using std
+    extern crate std;
+    use std::cell::RefCell;
+    use std::vec::Vec;
+
+    type TestFrameBuffer = FrameBuffer<64, 32, 1, 8, 16, 64, 16>;
+
+    type TestMatrix<'a> = RgbMatrix<'a, Rgb888, 64, 32, 1, 8, 16, 64, 16, 64>;
+
+    /// A DMA "peripheral" that finishes a frame every time it is polled.
+    #[derive(Debug, Default)]
+    struct FrameCountingDma {
+        polls: usize,
+        starts: usize,
+    }
+
+    impl MatrixDma<64, 32, 1, 8, 16, 64, 16> for FrameCountingDma {
+        type Error = ();
+
+        unsafe fn start_reference<'a>(
+            self,
+            frame_buffer: &'a mut TestFrameBuffer,
+        ) -> Result<
+            Transfer<'a, Self, 64, 32, 1, 8, 16, 64, 16>,
+            (Self::Error, Self, &'a mut TestFrameBuffer),
+        > {
+            let mut matrix_dma = self;
+            matrix_dma.starts += 1;
+            Ok(Transfer::new(matrix_dma, frame_buffer))
+        }
+
+        fn poll_frame_done(&mut self) -> bool {
+            self.polls += 1;
+            true
+        }
+
+        fn pause(&mut self) {}
+
+        fn resume(&mut self) {}
+
+        fn stop<'a>(
+            transfer: Transfer<'a, Self, 64, 32, 1, 8, 16, 64, 16>,
+        ) -> Result<(Self, &'a mut TestFrameBuffer), (Self::Error, Self, &'a mut TestFrameBuffer)>
+        {
+            Ok(transfer.into_parts())
+        }
+    }
+
+    fn red_at(frame_buffer: &TestFrameBuffer, x: usize, y: usize) -> bool {
+        let location = frame_buffer.locate(x, y);
+        frame_buffer.plane(location.scanline, 7).unwrap()[location.word_index].red(location.lane)
+    }
+
+    /// The number of lit columns in the first scanline and plane of a frame buffer.
+    fn lit_columns(frame_buffer: &TestFrameBuffer) -> usize {
+        frame_buffer
+            .plane(0, 0)
+            .unwrap()
+            .iter()
+            .filter(|word| !word.output_enable())
+            .count()
+    }
+
+    #[test]
+    fn ramp_brightness() {
+        let ramp: [u8; 11] =
+            core::array::from_fn(|frame| super::ramp_brightness(200, frame as u16, 8));
+        assert!(ramp[0] > 0);
+        assert!(
+            ramp.windows(2).take(8).all(|pair| pair[0] < pair[1]),
+            "{ramp:?}"
+        );
+        assert!(ramp[8..].iter().all(|brightness| *brightness == 200));
+        // Dim targets can't go up every frame, but never go down.
+        let ramp: [u8; 11] =
+            core::array::from_fn(|frame| super::ramp_brightness(4, frame as u16, 8));
+        assert!(ramp.windows(2).all(|pair| pair[0] <= pair[1]), "{ramp:?}");
+        assert_eq!(ramp[8], 4);
+        // Without a ramp the target is shown straight away.
+        assert_eq!(super::ramp_brightness(200, 0, 0), 200);
+    }
+
+    #[test]
+    fn startup_ramp() {
+        let mut shown = declare_frame_buffer!(64, 32, 8, 1, 16);
+        let mut drawn = declare_frame_buffer!(64, 32, 8, 1, 16);
+        let mut matrix = TestMatrix::new(MatrixConfig::default());
+        matrix.set_brightness(255);
+        matrix.configure_frame_buffer(&mut shown);
+        matrix.configure_frame_buffer(&mut drawn);
+        let full = lit_columns(&shown);
+        matrix.set_pending(&mut drawn);
+        let transfer = unsafe { FrameCountingDma::default().start_reference(&mut shown) }.unwrap();
+
+        let mut animator =
+            unsafe { Animator::new(&mut matrix, transfer, 1) }.with_startup_ramp_frames(4);
+        let mut ramp = Vec::new();
+        let shown_lit_columns = |animator: &mut Animator<_, _, 64, 32, 1, 8, 16, 64, 16, 64>| {
+            let transfer = animator.output.as_mut().unwrap().as_mut().unwrap();
+            lit_columns(transfer.frame_buffer_mut())
+        };
+        ramp.push(shown_lit_columns(&mut animator));
+        for _ in 0..6 {
+            animator.step(|_| ()).unwrap();
+            ramp.push(shown_lit_columns(&mut animator));
+        }
+        // Brightness goes up every frame of the ramp, then stays at the full brightness.
+        assert!(ramp[0] > 0);
+        assert!(
+            ramp[..5].windows(2).all(|pair| pair[0] < pair[1]),
+            "{ramp:?}"
+        );
+        assert!(ramp[4..].iter().all(|lit| *lit == full), "{ramp:?}");
+
+        // The frame buffer being drawn into is back at the full brightness too.
+        let (matrix, transfer) = animator.into_parts();
+        let (matrix_dma, shown) = transfer.unwrap().stop().unwrap();
+        let drawn = matrix.set_pending(shown).unwrap();
+        assert_eq!(lit_columns(drawn), full);
+        assert_eq!(matrix_dma.polls, 6);
+    }
+
+    #[test]
+    fn frames_run_in_order() {
+        let mut shown = declare_frame_buffer!(64, 32, 8, 1, 16);
+        let mut drawn = declare_frame_buffer!(64, 32, 8, 1, 16);
+        let mut matrix = TestMatrix::new(MatrixConfig::default());
+        matrix.configure_frame_buffer(&mut shown);
+        matrix.configure_frame_buffer(&mut drawn);
+        matrix.set_pending(&mut drawn);
+        let transfer = unsafe { FrameCountingDma::default().start_reference(&mut shown) }.unwrap();
+
+        let order = RefCell::new(Vec::new());
+        let frame = |index: usize| {
+            let order = &order;
+            move |matrix: &mut TestMatrix| {
+                order.borrow_mut().push(index);
+                matrix.set_pixel(index, 0, Rgb888::RED).unwrap();
+            }
+        };
+        let mut animator = unsafe { Animator::new(&mut matrix, transfer, 2) };
+        animator.run([frame(0), frame(1), frame(2)]).unwrap();
+
+        assert_eq!(*order.borrow(), [0, 1, 2]);
+        let (matrix, transfer) = animator.into_parts();
+        let (matrix_dma, shown) = transfer.unwrap().stop().unwrap();
+        assert_eq!(matrix_dma.polls, 3 * 2);
+        assert_eq!(matrix_dma.starts, 1 + 3);
+        // Every step is shown, including the last one.
+        for x in 0..3 {
+            assert!(red_at(shown, x, 0));
+        }
+        assert_eq!(matrix.get_pixel(2, 0).unwrap(), Rgb888::RED);
+    }
+
+    #[test]
+    fn frame_left_open_in_step() {
+        let mut shown = declare_frame_buffer!(64, 32, 8, 1, 16);
+        let mut drawn = declare_frame_buffer!(64, 32, 8, 1, 16);
+        let mut matrix = TestMatrix::new(MatrixConfig::default());
+        matrix.configure_frame_buffer(&mut shown);
+        matrix.configure_frame_buffer(&mut drawn);
+        matrix.set_pending(&mut drawn);
+        let transfer = unsafe { FrameCountingDma::default().start_reference(&mut shown) }.unwrap();
+
+        let mut animator = unsafe { Animator::new(&mut matrix, transfer, 1) };
+        animator
+            .step(|matrix: &mut TestMatrix| {
+                matrix.begin_frame();
+                matrix.set_pixel(0, 0, Rgb888::RED).unwrap();
+            })
+            .unwrap();
+        animator
+            .step(|matrix: &mut TestMatrix| {
+                matrix.begin_frame();
+                matrix.set_pixel(1, 0, Rgb888::RED).unwrap();
+            })
+            .unwrap();
+        let (_, transfer) = animator.into_parts();
+        let (matrix_dma, shown) = transfer.unwrap().stop().unwrap();
+        assert_eq!(matrix_dma.starts, 1 + 2);
+        // The open frames were ended and shown, instead of holding up the swap.
+        assert!(red_at(shown, 0, 0));
+        assert!(red_at(shown, 1, 0));
+    }
+
+    #[test]
+    fn frame_hook_kept_across_steps() {
+        let mut shown = declare_frame_buffer!(64, 32, 8, 1, 16);
+        let mut drawn = declare_frame_buffer!(64, 32, 8, 1, 16);
+        let mut matrix = TestMatrix::new(MatrixConfig::default());
+        matrix.configure_frame_buffer(&mut shown);
+        matrix.configure_frame_buffer(&mut drawn);
+        matrix.set_pending(&mut drawn);
+        let mut hook_calls = 0;
+        let mut hook = |_: &mut TestFrameBuffer| hook_calls += 1;
+        let mut transfer =
+            unsafe { FrameCountingDma::default().start_reference(&mut shown) }.unwrap();
+        transfer.set_frame_hook(&mut hook);
+
+        let mut animator = unsafe { Animator::new(&mut matrix, transfer, 2) };
+        animator.run([|_: &mut TestMatrix| (); 3]).unwrap();
+        let (_, transfer) = animator.into_parts();
+        let mut transfer = transfer.unwrap();
+        // Every frame sent ran the hook, and it is still set on the last transfer.
+        assert!(transfer.take_frame_hook().is_some());
+        let _ = transfer.stop();
+        assert_eq!(hook_calls, 3 * 2);
+    }
+}
@@ -0,0 +1,222 @@
+use embedded_graphics_core::draw_target::DrawTarget;
+use embedded_graphics_core::geometry::{OriginDimensions, Size};
+use embedded_graphics_core::pixelcolor::PixelColor;
+use embedded_graphics_core::Pixel;
+
+use super::color::Color;
+use super::config::MatrixConfig;
+use super::rgb_matrix::RgbMatrix;
+
+/// A panel geometry supported by `AnyMatrix`, for listing them at runtime.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PanelSpec {
+    /// The variant of `AnyMatrix` (and argument to `AnyMatrix::new()`) for this geometry.
+    pub geometry: PanelGeometry,
+
+    pub width: usize,
+
+    pub height: usize,
+
+    /// The scan rate, as the denominator of the fraction of rows lit at once (so 16 for 1/16 scan).
+    pub scan: u8,
+
+    /// The color depth.
+    pub depth: usize,
+}
+
+// Everything with a case for each geometry is generated from the one list, so adding a geometry
+// can't leave any of them (or `SUPPORTED_PANELS`) out.
+macro_rules! any_matrix_geometries {
+    ($(
+        $(#[$meta:meta])*
+        $variant:ident($alias:ident): $width:literal x $height:literal, 1 / $scan:literal;
+    )+) => {
+        /// The panel geometries supported by `AnyMatrix`.
+        #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+        #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+        pub enum PanelGeometry {
+            $(
+                $(#[$meta])*
+                $variant,
+            )+
+        }
+
+        $(
+            pub type $alias<'a, ColorType> = RgbMatrix<
+                'a,
+                ColorType,
+                $width,
+                $height,
+                1,
+                8,
+                $scan,
+                { $width * $height / $scan / 2 },
+                $scan,
+                { (($width * $height) as usize).div_ceil(32) },
+            >;
+        )+
+
+        /// Every geometry supported by `AnyMatrix`, in the order they are declared.
+        pub const SUPPORTED_PANELS: &[PanelSpec] = &[
+            $(
+                PanelSpec {
+                    geometry: PanelGeometry::$variant,
+                    width: $width,
+                    height: $height,
+                    scan: $scan,
+                    depth: 8,
+                },
+            )+
+        ];
+
+        /// An `RgbMatrix` with the panel geometry chosen at runtime, for firmware that supports
+        /// several panels.
+        ///
+        /// Drawing is forwarded to the matrix for the selected geometry. Every geometry is
+        /// compiled in, and the matrix is as large as the largest of them. Anything specific to a
+        /// geometry (such as setting the pending frame buffer) is done by matching on the
+        /// variants.
+        // The size of the largest variant is the point, so that any geometry can be chosen.
+        #[allow(clippy::large_enum_variant)]
+        pub enum AnyMatrix<'a, ColorType> {
+            $(
+                $variant($alias<'a, ColorType>),
+            )+
+        }
+
+        impl<'a, ColorType> AnyMatrix<'a, ColorType>
+        where
+            ColorType: Default + PartialEq + Copy + Color<8>,
+        {
+            /// Create a matrix for the given geometry, with the default configuration.
+            pub fn new(geometry: PanelGeometry) -> Self {
+                match geometry {
+                    $(
+                        PanelGeometry::$variant => {
+                            Self::$variant(RgbMatrix::new(MatrixConfig::default()))
+                        }
+                    )+
+                }
+            }
+        }
+
+        impl<'a, ColorType> AnyMatrix<'a, ColorType> {
+            pub fn geometry(&self) -> PanelGeometry {
+                match self {
+                    $(
+                        Self::$variant(_) => PanelGeometry::$variant,
+                    )+
+                }
+            }
+        }
+
+        impl<'a, ColorType> OriginDimensions for AnyMatrix<'a, ColorType> {
+            fn size(&self) -> Size {
+                match self {
+                    $(
+                        Self::$variant(matrix) => matrix.size(),
+                    )+
+                }
+            }
+        }
+
+        impl<'a, ColorType> DrawTarget for AnyMatrix<'a, ColorType>
+        where
+            ColorType: PixelColor + Color<8>,
+        {
+            type Color = ColorType;
+
+            type Error = core::convert::Infallible;
+
+            fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+            where
+                I: IntoIterator<Item = Pixel<Self::Color>>,
+            {
+                match self {
+                    $(
+                        Self::$variant(matrix) => matrix.draw_iter(pixels),
+                    )+
+                }
+            }
+        }
+    };
+}
+
+any_matrix_geometries! {
+    /// A 32x16 panel with 1/8 scanning.
+    Panel32x16(Matrix32x16): 32 x 16, 1 / 8;
+
+    /// A 64x32 panel with 1/16 scanning.
+    Panel64x32(Matrix64x32): 64 x 32, 1 / 16;
+
+    /// A 64x64 panel with 1/32 scanning.
+    Panel64x64(Matrix64x64): 64 x 64, 1 / 32;
+}
+
+impl PanelGeometry {
+    /// The details of this geometry, from `SUPPORTED_PANELS`.
+    pub fn spec(self) -> PanelSpec {
+        *SUPPORTED_PANELS
+            .iter()
+            .find(|spec| spec.geometry == self)
+            .expect("Every geometry is in SUPPORTED_PANELS")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use embedded_graphics_core::geometry::Point;
+    use embedded_graphics_core::pixelcolor::{Rgb888, RgbColor};
+
+    #[test]
+    fn size_per_geometry() {
+        for (geometry, size) in [
+            (PanelGeometry::Panel32x16, Size::new(32, 16)),
+            (PanelGeometry::Panel64x32, Size::new(64, 32)),
+            (PanelGeometry::Panel64x64, Size::new(64, 64)),
+        ] {
+            let matrix = AnyMatrix::<Rgb888>::new(geometry);
+            assert_eq!(matrix.geometry(), geometry);
+            assert_eq!(matrix.size(), size, "{geometry:?}");
+        }
+    }
+
+    #[test]
+    fn supported_panels_match_variants() {
+        assert_eq!(SUPPORTED_PANELS.len(), 3);
+        for spec in SUPPORTED_PANELS {
+            assert_eq!(spec.geometry.spec(), *spec);
+            let matrix = AnyMatrix::<Rgb888>::new(spec.geometry);
+            assert_eq!(matrix.geometry(), spec.geometry);
+            assert_eq!(
+                matrix.size(),
+                Size::new(spec.width as u32, spec.height as u32)
+            );
+            let scanlines = match matrix {
+                AnyMatrix::Panel32x16(_) => Matrix32x16::<Rgb888>::SCANLINES_PER_FRAME,
+                AnyMatrix::Panel64x32(_) => Matrix64x32::<Rgb888>::SCANLINES_PER_FRAME,
+                AnyMatrix::Panel64x64(_) => Matrix64x64::<Rgb888>::SCANLINES_PER_FRAME,
+            };
+            assert_eq!(scanlines, spec.scan as usize);
+            // Every variant is drawn with `Color<8>` colors.
+            assert_eq!(spec.depth, 8);
+        }
+    }
+
+    #[test]
+    fn draw_forwarded() {
+        let mut matrix = AnyMatrix::<Rgb888>::new(PanelGeometry::Panel64x64);
+        matrix
+            .draw_iter([Pixel(Point::new(10, 50), Rgb888::RED)])
+            .unwrap();
+        match matrix {
+            AnyMatrix::Panel64x64(matrix) => {
+                assert_eq!(matrix.get_pixel(10, 50), Ok(Rgb888::RED));
+            }
+            _ => panic!("wrong geometry"),
+        }
+    }
+}
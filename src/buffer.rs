@@ -1,14 +1,60 @@
 use core::iter;
 use core::marker::PhantomData;
+use core::ops::Range;
+
+use embedded_graphics_core::geometry::{Point, Size};
+use embedded_graphics_core::pixelcolor::{Rgb888, RgbColor};
+use embedded_graphics_core::primitives::Rectangle;
 
 use crate::{const_check, const_not_zero};
 
-use super::config::MatrixConfig;
-use super::matrix_word::{MatrixPixel, MatrixWordMut};
+use super::config::{MatrixConfig, LATCH_BLANKING_COUNT_MAX};
+use super::matrix_word::{control_word, BufferWord, MatrixPixel};
+
+/// The type of each component of a color with `COLOR_DEPTH` bits.
+///
+/// A storage type that is too narrow for the color depth fails to compile when it is used:
+///
+/// ```compile_fail,E0080
+/// use hub75_bcm::buffer::{ColorStorage, FrameBuffer};
+///
+/// #[derive(Clone, Copy)]
+/// struct Narrow(u8);
+///
+/// impl ColorStorage<12> for Narrow {
+///     fn iter_bits(&self) -> impl Iterator<Item = bool> {
+///         let value = self.0;
+///         (0..12).map(move |shift| value.checked_shr(shift).unwrap_or(0) & 1 == 1)
+///     }
+///
+///     fn to_u16(&self) -> u16 {
+///         self.0 as u16
+///     }
+///
+///     fn from_u16(value: u16) -> Self {
+///         Self(value as u8)
+///     }
+/// }
+///
+/// let mut frame_buffer = FrameBuffer::<64, 32, 1, 12, 16, 64, 16>::new();
+/// frame_buffer.set_pixel_raw(0, 0, Narrow(1), Narrow(2), Narrow(3));
+/// ```
+pub trait ColorStorage<const COLOR_DEPTH: usize>: Sized {
+    /// Checked wherever colors are split into their color planes, so that a storage type that
+    /// can't hold `COLOR_DEPTH` bits fails to compile instead of losing the upper bits.
+    const COLOR_DEPTH: usize = const_check!(
+        COLOR_DEPTH,
+        core::mem::size_of::<Self>() * 8 >= COLOR_DEPTH,
+        "The color storage type is too narrow for COLOR_DEPTH"
+    );
 
-pub trait ColorStorage<const COLOR_DEPTH: usize> {
-    const COLOR_DEPTH: usize = COLOR_DEPTH;
     fn iter_bits(&self) -> impl Iterator<Item = bool>;
+
+    /// The value of the component, from 0 to `2^COLOR_DEPTH - 1`.
+    fn to_u16(&self) -> u16;
+
+    /// A component with the given value, which must be less than `2^COLOR_DEPTH`.
+    fn from_u16(value: u16) -> Self;
 }
 
 macro_rules! impl_color_storage {
@@ -18,6 +64,14 @@ macro_rules! impl_color_storage {
                 let self_copy = *self;
                 (0..$depth).map(move |shift| (self_copy & (1 << shift)) > 0)
             }
+
+            fn to_u16(&self) -> u16 {
+                *self as u16
+            }
+
+            fn from_u16(value: u16) -> Self {
+                value as $type
+            }
         }
     };
 }
@@ -48,17 +102,148 @@ impl_color_storage!(u16, 14);
 impl_color_storage!(u16, 15);
 impl_color_storage!(u16, 16);
 
-#[derive(Debug, PartialEq, Eq)]
-pub(crate) struct PixelRef<'a> {
-    pub(crate) scanline: usize,
-    pub(crate) column: usize,
-    pub(crate) color_plane: usize,
-    pub(crate) word: &'a mut u16,
-}
-
 // Defining this here to make it easier if this needs to be added as a parameter later.
 pub(crate) const PIXELS_PER_CLOCK: usize = 2;
 
+/// The number of words in each blank segment sent between scanlines when ghost reduction is
+/// enabled (see `FrameBuffer::set_ghost_reduction()`), and in each address transition segment.
+pub const GHOST_BLANKING_WORDS: usize = 16;
+
+pub(crate) const PLANE_BIT_RED: u8 = 1 << 0;
+pub(crate) const PLANE_BIT_GREEN: u8 = 1 << 1;
+pub(crate) const PLANE_BIT_BLUE: u8 = 1 << 2;
+
+/// Split the components of a color into the bits for each color plane.
+///
+/// Each element has the red, green, and blue bits for that plane, in the `PLANE_BIT_*` positions.
+pub(crate) fn plane_bits<CS: ColorStorage<COLOR_DEPTH>, const COLOR_DEPTH: usize>(
+    red: CS,
+    green: CS,
+    blue: CS,
+) -> [u8; COLOR_DEPTH] {
+    // Force the compiler to evaluate the const check
+    let _ = CS::COLOR_DEPTH;
+    let mut planes = [0u8; COLOR_DEPTH];
+    let bits_iter = red.iter_bits().zip(green.iter_bits()).zip(blue.iter_bits());
+    for (plane, ((red_bit, green_bit), blue_bit)) in planes.iter_mut().zip(bits_iter) {
+        if red_bit {
+            *plane |= PLANE_BIT_RED;
+        }
+        if green_bit {
+            *plane |= PLANE_BIT_GREEN;
+        }
+        if blue_bit {
+            *plane |= PLANE_BIT_BLUE;
+        }
+    }
+    planes
+}
+
+/// How the rows of a panel are split between the two data lanes (R1/G1/B1 and R2/G2/B2).
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum LaneMapping {
+    /// The top half of the panel is on the first lane, and the bottom half on the second lane.
+    #[default]
+    Halves,
+
+    /// Even rows are on the first lane, and odd rows are on the second lane.
+    Interleaved,
+}
+
+/// Which data lane is in the low bits of each word.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum LaneOrder {
+    /// The first lane (R1/G1/B1) is in the low bits, and the second lane in the bits above it.
+    #[default]
+    Normal,
+
+    /// The lanes are swapped, for panels that expect the second pixel of each clock first.
+    Swapped,
+}
+
+/// How many times each color plane is repeated in a frame.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BcmOrder {
+    /// Plane `n` is repeated `2^n` times, so the most significant plane is shown the longest.
+    #[default]
+    Ascending,
+
+    /// The weights are reversed, so the most significant plane is shown the shortest.
+    ///
+    /// This is for checking that the planes are in the order the panel expects, as colors look
+    /// visibly wrong (but not black) if the weighting is backwards.
+    Descending,
+}
+
+/// Where the bits for a pixel are stored in a frame buffer, from `FrameBuffer::locate()`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PixelLocation {
+    /// The scanline index, which is also the address the pixel is shown at.
+    pub scanline: usize,
+
+    /// The index of the word within each color plane of the scanline.
+    pub word_index: usize,
+
+    /// Which of the two pixels packed into each word is used.
+    pub lane: MatrixPixel,
+}
+
+/// A problem with the address bits of a frame buffer, from `FrameBuffer::verify_addressing()`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum AddressingError {
+    /// The words of the first color plane of a scanline don't all have the same address.
+    Inconsistent { scanline: usize },
+
+    /// More than one scanline is shown at this address.
+    Duplicate { address: u8 },
+
+    /// No scanline is shown at this address, so those rows are never lit.
+    Missing { address: u8 },
+}
+
+/// The configuration a frame buffer appears to have, from `FrameBuffer::inferred_config()`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct InferredConfig {
+    /// The number of columns blanked on each side of the latch, or `None` if no columns are lit
+    /// (so the start of the output enable window can't be found).
+    pub latch_blanking_count: Option<u8>,
+
+    /// The brightness, assuming the linear brightness curve.
+    ///
+    /// The number of lit columns is rounded down when a brightness is set, so this can be a little
+    /// lower than the brightness that was actually set.
+    pub brightness_estimate: u8,
+}
+
+/// The columns of each scanline that have output enabled, from `MatrixConfig::lit_columns()`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct LitColumns {
+    pub(crate) columns: Range<usize>,
+
+    /// Only light the least significant color plane for half of the columns.
+    pub(crate) half_lsb: bool,
+}
+
+impl LitColumns {
+    pub(crate) fn for_plane(&self, plane: usize) -> Range<usize> {
+        if self.half_lsb && plane == 0 {
+            let start = self.columns.start;
+            start..(start + self.columns.len() / 2)
+        } else {
+            self.columns.clone()
+        }
+    }
+
+    pub(crate) fn contains(&self, plane: usize, column: usize) -> bool {
+        self.for_plane(plane).contains(&column)
+    }
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub(crate) struct ColorPlane<
     const WIDTH: usize,
@@ -67,10 +252,12 @@ pub(crate) struct ColorPlane<
     const COLOR_DEPTH: usize,
     const PER_FRAME_DENOMINATOR: u8,
     const WORDS_PER_PLANE: usize,
+    Word = u16,
 > {
-    // We will always need a u16 for the buffer; the RGB bits will take up 6 bits, then OE and LAT
-    // bring it up to 8. Any address bits will push it over 8, and there must be at least 1 of them.
-    buffer: [u16; WORDS_PER_PLANE],
+    // We will always need at least a u16 for the buffer; the RGB bits will take up 6 bits, then OE
+    // and LAT bring it up to 8. Any address bits will push it over 8, and there must be at least 1
+    // of them.
+    buffer: [Word; WORDS_PER_PLANE],
 
     _config:
         PhantomData<MatrixConfig<WIDTH, HEIGHT, CHAIN_LENGTH, COLOR_DEPTH, PER_FRAME_DENOMINATOR>>,
@@ -83,7 +270,17 @@ impl<
         const COLOR_DEPTH: usize,
         const PER_FRAME_DENOMINATOR: u8,
         const WORDS_PER_PLANE: usize,
-    > ColorPlane<WIDTH, HEIGHT, CHAIN_LENGTH, COLOR_DEPTH, PER_FRAME_DENOMINATOR, WORDS_PER_PLANE>
+        Word: BufferWord,
+    >
+    ColorPlane<
+        WIDTH,
+        HEIGHT,
+        CHAIN_LENGTH,
+        COLOR_DEPTH,
+        PER_FRAME_DENOMINATOR,
+        WORDS_PER_PLANE,
+        Word,
+    >
 {
     const_not_zero!(WIDTH, usize);
     const_not_zero!(HEIGHT, usize);
@@ -110,7 +307,7 @@ impl<
         let _ = Self::WORDS_PER_PLANE;
 
         Self {
-            buffer: [0u16; WORDS_PER_PLANE],
+            buffer: [Word::ZERO; WORDS_PER_PLANE],
             _config: PhantomData,
         }
     }
@@ -124,6 +321,7 @@ pub(crate) struct Scanline<
     const COLOR_DEPTH: usize,
     const PER_FRAME_DENOMINATOR: u8,
     const WORDS_PER_PLANE: usize,
+    Word = u16,
 > {
     planes: [ColorPlane<
         WIDTH,
@@ -132,6 +330,7 @@ pub(crate) struct Scanline<
         COLOR_DEPTH,
         PER_FRAME_DENOMINATOR,
         WORDS_PER_PLANE,
+        Word,
     >; COLOR_DEPTH],
 
     _config:
@@ -145,7 +344,9 @@ impl<
         const COLOR_DEPTH: usize,
         const PER_FRAME_DENOMINATOR: u8,
         const WORDS_PER_PLANE: usize,
-    > Scanline<WIDTH, HEIGHT, CHAIN_LENGTH, COLOR_DEPTH, PER_FRAME_DENOMINATOR, WORDS_PER_PLANE>
+        Word: BufferWord,
+    >
+    Scanline<WIDTH, HEIGHT, CHAIN_LENGTH, COLOR_DEPTH, PER_FRAME_DENOMINATOR, WORDS_PER_PLANE, Word>
 {
     const_not_zero!(WIDTH, usize);
     const_not_zero!(HEIGHT, usize);
@@ -178,15 +379,93 @@ impl<
             COLOR_DEPTH,
             PER_FRAME_DENOMINATOR,
             WORDS_PER_PLANE,
+            Word,
         >::new(); COLOR_DEPTH];
         Self {
             planes,
             _config: PhantomData,
         }
     }
+
+    // The control bits of each plane for address 0, for each latch blanking count up to
+    // `LATCH_BLANKING_COUNT_MAX`. Counts that don't fit in a plane are left empty.
+    const CONTROL_ROWS: [[u16; WORDS_PER_PLANE]; LATCH_BLANKING_COUNT_MAX as usize + 1] = {
+        let mut rows = [[0u16; WORDS_PER_PLANE]; LATCH_BLANKING_COUNT_MAX as usize + 1];
+        let mut count = 0;
+        while count < rows.len() && count < WORDS_PER_PLANE {
+            rows[count] = control_row::<WORDS_PER_PLANE>(0, count as u8);
+            count += 1;
+        }
+        rows
+    };
+
+    pub(crate) fn plane(&self, plane: usize) -> &[Word] {
+        &self.planes[plane].buffer[..]
+    }
+
+    /// Set the address, output enable, and latch values for this scanline.
+    ///
+    /// `address` is the index of this scanline, and `scanlines_per_frame` is how many scanlines
+    /// there are in total.
+    pub(crate) fn set_control_bits(
+        &mut self,
+        address: usize,
+        scanlines_per_frame: usize,
+        latch_blanking_count: u8,
+    ) {
+        // The first color plane has the previous scanline's address values as we're clocking
+        // in the new scanline of data (except for the first row).
+        let previous_address = (address + scanlines_per_frame - 1) % scanlines_per_frame;
+        let blanking = latch_blanking_count as usize;
+        let row = if blanking < Self::CONTROL_ROWS.len() && blanking < WORDS_PER_PLANE {
+            Self::CONTROL_ROWS[blanking]
+        } else {
+            control_row::<WORDS_PER_PLANE>(0, latch_blanking_count)
+        };
+        let first_address = control_word(previous_address as u8, false, false);
+        let address = control_word(address as u8, false, false);
+        for (plane_index, plane) in self.planes.iter_mut().enumerate() {
+            let address = if plane_index == 0 {
+                first_address
+            } else {
+                address
+            };
+            for (word, control) in plane.buffer.iter_mut().zip(row.iter()) {
+                word.replace_control_bits(control | address);
+            }
+        }
+    }
+
+    pub(crate) fn set_brightness_bits(&mut self, lit_columns: &LitColumns) {
+        for (plane_index, plane) in self.planes.iter_mut().enumerate() {
+            for (column, word) in plane.buffer.iter_mut().enumerate() {
+                // OE is active low, so the bit is set for every column that should be dark.
+                word.set_output_enable_to(!lit_columns.contains(plane_index, column));
+            }
+        }
+    }
+
+    /// Set the color bits (and optionally the brightness bits) at a location in this scanline.
+    pub(crate) fn set_pixel_planes(
+        &mut self,
+        location: &PixelLocation,
+        plane_bits: &[u8; COLOR_DEPTH],
+        lit_columns: Option<&LitColumns>,
+    ) {
+        for (plane_index, bits) in plane_bits.iter().enumerate() {
+            let plane = &mut self.planes[plane_index];
+            let word = &mut plane.buffer[location.word_index];
+            word.set_red_to(location.lane, bits & PLANE_BIT_RED != 0);
+            word.set_green_to(location.lane, bits & PLANE_BIT_GREEN != 0);
+            word.set_blue_to(location.lane, bits & PLANE_BIT_BLUE != 0);
+            if let Some(lit_columns) = lit_columns {
+                word.set_output_enable_to(!lit_columns.contains(plane_index, location.word_index));
+            }
+        }
+    }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug)]
 pub struct FrameBuffer<
     const WIDTH: usize,
     const HEIGHT: usize,
@@ -195,6 +474,7 @@ pub struct FrameBuffer<
     const PER_FRAME_DENOMINATOR: u8,
     const WORDS_PER_PLANE: usize,
     const SCANLINES_PER_FRAME: usize,
+    Word = u16,
 > {
     scanlines: [Scanline<
         WIDTH,
@@ -203,14 +483,52 @@ pub struct FrameBuffer<
         COLOR_DEPTH,
         PER_FRAME_DENOMINATOR,
         WORDS_PER_PLANE,
+        Word,
     >; SCANLINES_PER_FRAME],
 
     configured: bool,
 
+    lane_mapping: LaneMapping,
+
+    lane_order: LaneOrder,
+
+    column_offset: u16,
+
+    ghost_reduction: bool,
+
+    bcm_order: BcmOrder,
+
+    address_transition: bool,
+
+    lazy_control_bits: bool,
+
+    // The scanlines that haven't had their control bits written yet, one bit for each scanline,
+    // along with what to write to them.
+    deferred_control: u32,
+
+    deferred_latch_blanking_count: u8,
+
+    deferred_lit_columns: Option<LitColumns>,
+
+    // A segment with output disabled for each address, sent between segments when ghost reduction
+    // is enabled, and before each scanline with address transitions enabled.
+    ghost_blanking: [[Word; GHOST_BLANKING_WORDS]; SCANLINES_PER_FRAME],
+
+    // Set while the frame buffer shows an idle pattern instead of the pixels of a matrix, so every
+    // pixel is redrawn the first time it is caught up.
+    shows_idle_pattern: bool,
+
+    // How many words have had their color or brightness bits written, to compare the work done by
+    // different ways of updating a frame buffer.
+    #[cfg(test)]
+    pub(crate) word_writes: usize,
+
     _config:
         PhantomData<MatrixConfig<WIDTH, HEIGHT, CHAIN_LENGTH, COLOR_DEPTH, PER_FRAME_DENOMINATOR>>,
 }
 
+// Frame buffers are compared as they would be sent, so a buffer with deferred control bits is equal
+// to one that has had them written.
 impl<
         const WIDTH: usize,
         const HEIGHT: usize,
@@ -219,6 +537,95 @@ impl<
         const PER_FRAME_DENOMINATOR: u8,
         const WORDS_PER_PLANE: usize,
         const SCANLINES_PER_FRAME: usize,
+        Word: BufferWord,
+    > PartialEq
+    for FrameBuffer<
+        WIDTH,
+        HEIGHT,
+        CHAIN_LENGTH,
+        COLOR_DEPTH,
+        PER_FRAME_DENOMINATOR,
+        WORDS_PER_PLANE,
+        SCANLINES_PER_FRAME,
+        Word,
+    >
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.configured == other.configured
+            && self.lane_mapping == other.lane_mapping
+            && self.lane_order == other.lane_order
+            && self.column_offset == other.column_offset
+            && self.ghost_reduction == other.ghost_reduction
+            && self.bcm_order == other.bcm_order
+            && self.address_transition == other.address_transition
+            && self.ghost_blanking == other.ghost_blanking
+            && self.shows_idle_pattern == other.shows_idle_pattern
+            && (0..SCANLINES_PER_FRAME).all(|scanline| {
+                self.with_resolved_scanline(scanline, |ours| {
+                    other.with_resolved_scanline(scanline, |theirs| ours == theirs)
+                })
+            })
+    }
+}
+
+impl<
+        const WIDTH: usize,
+        const HEIGHT: usize,
+        const CHAIN_LENGTH: usize,
+        const COLOR_DEPTH: usize,
+        const PER_FRAME_DENOMINATOR: u8,
+        const WORDS_PER_PLANE: usize,
+        const SCANLINES_PER_FRAME: usize,
+        Word: BufferWord,
+    > Eq
+    for FrameBuffer<
+        WIDTH,
+        HEIGHT,
+        CHAIN_LENGTH,
+        COLOR_DEPTH,
+        PER_FRAME_DENOMINATOR,
+        WORDS_PER_PLANE,
+        SCANLINES_PER_FRAME,
+        Word,
+    >
+{
+}
+
+impl<
+        const WIDTH: usize,
+        const HEIGHT: usize,
+        const CHAIN_LENGTH: usize,
+        const COLOR_DEPTH: usize,
+        const PER_FRAME_DENOMINATOR: u8,
+        const WORDS_PER_PLANE: usize,
+        const SCANLINES_PER_FRAME: usize,
+        Word: BufferWord,
+    > Default
+    for FrameBuffer<
+        WIDTH,
+        HEIGHT,
+        CHAIN_LENGTH,
+        COLOR_DEPTH,
+        PER_FRAME_DENOMINATOR,
+        WORDS_PER_PLANE,
+        SCANLINES_PER_FRAME,
+        Word,
+    >
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<
+        const WIDTH: usize,
+        const HEIGHT: usize,
+        const CHAIN_LENGTH: usize,
+        const COLOR_DEPTH: usize,
+        const PER_FRAME_DENOMINATOR: u8,
+        const WORDS_PER_PLANE: usize,
+        const SCANLINES_PER_FRAME: usize,
+        Word: BufferWord,
     >
     FrameBuffer<
         WIDTH,
@@ -228,6 +635,7 @@ impl<
         PER_FRAME_DENOMINATOR,
         WORDS_PER_PLANE,
         SCANLINES_PER_FRAME,
+        Word,
     >
 {
     const_not_zero!(WIDTH, usize);
@@ -251,6 +659,10 @@ impl<
         "SCANLINES_PER_FRAME must equal HEIGHT / (HEIGHT / PER_FRAME_DENOMINATOR), and be less than or equal to 32"
     );
 
+    pub const PLANE_COUNT: usize = COLOR_DEPTH;
+
+    pub const EMITTED_SEGMENTS: usize = ((1 << COLOR_DEPTH) - 1) * SCANLINES_PER_FRAME;
+
     pub const fn width(&self) -> usize {
         Self::WIDTH
     }
@@ -279,6 +691,39 @@ impl<
         Self::SCANLINES_PER_FRAME
     }
 
+    /// The number of distinct color planes in each scanline, which is the color depth.
+    pub const fn plane_count(&self) -> usize {
+        Self::PLANE_COUNT
+    }
+
+    /// The number of segments sent for each frame.
+    ///
+    /// Plane `n` is sent `2^n` times for every scanline, so each bit of color depth roughly doubles
+    /// the number of segments (and so the time taken to send a frame). Ghost reduction adds a blank
+    /// segment after every one of those, and address transitions add one for each scanline:
+    ///
+    /// ```
+    /// use hub75_bcm::panel;
+    ///
+    /// let six_bit = <panel!(width: 64, height: 32, color_depth: 6)>::new();
+    /// let seven_bit = <panel!(width: 64, height: 32, color_depth: 7)>::new();
+    /// assert_eq!(six_bit.plane_count(), 6);
+    /// assert_eq!(six_bit.emitted_segments(), 63 * 16);
+    /// assert_eq!(seven_bit.emitted_segments(), 127 * 16);
+    /// ```
+    pub const fn emitted_segments(&self) -> usize {
+        let segments = if self.ghost_reduction {
+            Self::EMITTED_SEGMENTS * 2
+        } else {
+            Self::EMITTED_SEGMENTS
+        };
+        if self.address_transition {
+            segments + SCANLINES_PER_FRAME
+        } else {
+            segments
+        }
+    }
+
     pub const fn new() -> Self {
         // Force the compiler to evaluate all the const checks
         let _ = Self::WIDTH;
@@ -296,105 +741,478 @@ impl<
             COLOR_DEPTH,
             PER_FRAME_DENOMINATOR,
             WORDS_PER_PLANE,
+            Word,
         >::new(); SCANLINES_PER_FRAME];
         Self {
             scanlines,
             configured: false,
+            lane_mapping: LaneMapping::Halves,
+            lane_order: LaneOrder::Normal,
+            column_offset: 0,
+            ghost_reduction: false,
+            bcm_order: BcmOrder::Ascending,
+            address_transition: false,
+            lazy_control_bits: false,
+            deferred_control: 0,
+            deferred_latch_blanking_count: 0,
+            deferred_lit_columns: None,
+            ghost_blanking: [[Word::ZERO; GHOST_BLANKING_WORDS]; SCANLINES_PER_FRAME],
+            shows_idle_pattern: false,
+            #[cfg(test)]
+            word_writes: 0,
             _config: PhantomData,
         }
     }
 
-    pub(crate) fn iter_mut_pixels<'a>(&'a mut self) -> impl Iterator<Item = PixelRef<'a>> {
-        self.scanlines
-            .iter_mut()
-            .enumerate()
-            .flat_map(|(line_index, scanline)| {
-                scanline
-                    .planes
-                    .iter_mut()
-                    .enumerate()
-                    .map(move |(plane_index, plane)| (plane_index, line_index, plane))
-            })
-            .flat_map(|(plane_index, scanline_index, plane)| {
-                plane
-                    .buffer
-                    .iter_mut()
-                    .enumerate()
-                    .map(move |(word_index, word)| PixelRef {
-                        scanline: scanline_index,
-                        column: word_index,
-                        color_plane: plane_index,
-                        word,
-                    })
-            })
+    /// Create a frame buffer configured for `config` and `brightness`, with every pixel set to the
+    /// same color.
+    ///
+    /// This is quicker than configuring a new frame buffer and drawing every pixel, as both lanes
+    /// of every word have the same color bits. Like `set_pixel_raw`, no gamma correction is
+    /// applied to the color.
+    pub fn filled<CS: ColorStorage<COLOR_DEPTH>>(
+        red: CS,
+        green: CS,
+        blue: CS,
+        config: &MatrixConfig<WIDTH, HEIGHT, CHAIN_LENGTH, COLOR_DEPTH, PER_FRAME_DENOMINATOR>,
+        brightness: u8,
+    ) -> Self {
+        let mut frame_buffer = Self::new();
+        frame_buffer.apply_config(config);
+        frame_buffer.configure(
+            config.latch_blanking_count(),
+            &config.lit_columns(brightness),
+        );
+        let bits = plane_bits(red, green, blue);
+        for scanline in frame_buffer.scanlines.iter_mut() {
+            for (plane, bits) in scanline.planes.iter_mut().zip(bits) {
+                for word in plane.buffer.iter_mut() {
+                    for lane in [MatrixPixel::One, MatrixPixel::Two] {
+                        word.set_red_to(lane, bits & PLANE_BIT_RED != 0);
+                        word.set_green_to(lane, bits & PLANE_BIT_GREEN != 0);
+                        word.set_blue_to(lane, bits & PLANE_BIT_BLUE != 0);
+                    }
+                }
+            }
+        }
+        frame_buffer
     }
 
     pub fn is_configured(&self) -> bool {
         self.configured
     }
 
-    pub(crate) fn configure(&mut self, latch_blanking_count: u8, brightness: u8) {
+    pub(crate) fn shows_idle_pattern(&self) -> bool {
+        self.shows_idle_pattern
+    }
+
+    pub(crate) fn set_shows_idle_pattern(&mut self, shows_idle_pattern: bool) {
+        self.shows_idle_pattern = shows_idle_pattern;
+    }
+
+    pub fn lane_mapping(&self) -> LaneMapping {
+        self.lane_mapping
+    }
+
+    /// Set how rows are split between the two data lanes.
+    ///
+    /// This only affects pixels set after it is changed.
+    pub fn set_lane_mapping(&mut self, lane_mapping: LaneMapping) {
+        self.lane_mapping = lane_mapping;
+    }
+
+    pub fn lane_order(&self) -> LaneOrder {
+        self.lane_order
+    }
+
+    /// Set which data lane is in the low bits of each word.
+    ///
+    /// This only affects pixels set after it is changed.
+    pub fn set_lane_order(&mut self, lane_order: LaneOrder) {
+        self.lane_order = lane_order;
+    }
+
+    pub fn column_offset(&self) -> u16 {
+        self.column_offset
+    }
+
+    /// Set how many columns to the right pixels are written, wrapping around at the end of the
+    /// chain.
+    ///
+    /// This only affects pixels set after it is changed.
+    pub fn set_column_offset(&mut self, column_offset: u16) {
+        self.column_offset = column_offset;
+    }
+
+    pub fn ghost_reduction(&self) -> bool {
+        self.ghost_reduction
+    }
+
+    /// Send a short blank segment between every pair of segments.
+    ///
+    /// Some panels ghost (faintly show a row's content on the next row) when the address changes
+    /// too quickly. Each blank segment has output disabled and the address the next segment starts
+    /// with, giving the row drivers time to switch before output is enabled again. This adds
+    /// `GHOST_BLANKING_WORDS` words for every segment, lowering the refresh rate.
+    pub fn set_ghost_reduction(&mut self, ghost_reduction: bool) {
+        self.ghost_reduction = ghost_reduction;
+        self.set_ghost_blanking();
+    }
+
+    pub fn bcm_order(&self) -> BcmOrder {
+        self.bcm_order
+    }
+
+    /// Set how many times each color plane is repeated in a frame.
+    ///
+    /// The total number of segments in a frame is the same either way.
+    pub fn set_bcm_order(&mut self, bcm_order: BcmOrder) {
+        self.bcm_order = bcm_order;
+    }
+
+    pub fn address_transition(&self) -> bool {
+        self.address_transition
+    }
+
+    /// Change the address in a blank segment before each scanline, instead of while sending the
+    /// first color plane.
+    ///
+    /// Normally the first color plane of a scanline is sent with the previous scanline's address,
+    /// which some panels show as a faint ghost of that plane on the wrong row. With this enabled,
+    /// every plane of a scanline has that scanline's address, and a segment of
+    /// `GHOST_BLANKING_WORDS` words with output disabled is sent before the first plane of each
+    /// scanline, so no color data is clocked in while the address changes.
+    ///
+    /// The blank segment is only sent before the first round of the first color plane. Every
+    /// later segment also moves to another scanline, and there the address changes at the start
+    /// of the segment, with only the latch blanking columns keeping the panel dark. Enable ghost
+    /// reduction as well to send a blank segment with the next address at every boundary.
+    pub fn set_address_transition(&mut self, address_transition: bool) {
+        self.address_transition = address_transition;
+        self.set_first_plane_addresses();
+    }
+
+    /// Apply every setting of `config` that changes how pixels and segments are laid out in the
+    /// frame buffer (the lane mapping and order, column offset, ghost reduction, BCM order, and
+    /// address transitions).
+    ///
+    /// The latch blanking and brightness are written by `configure` instead.
+    pub(crate) fn apply_config(
+        &mut self,
+        config: &MatrixConfig<WIDTH, HEIGHT, CHAIN_LENGTH, COLOR_DEPTH, PER_FRAME_DENOMINATOR>,
+    ) {
+        self.set_lane_mapping(config.lane_mapping());
+        self.set_lane_order(config.lane_order());
+        self.set_column_offset(config.column_offset());
+        self.set_ghost_reduction(config.ghost_reduction());
+        self.set_bcm_order(config.bcm_order());
+        self.set_address_transition(config.address_transition());
+    }
+
+    pub fn lazy_control_bits(&self) -> bool {
+        self.lazy_control_bits
+    }
+
+    /// Only write the control bits of each scanline when a pixel on it is first set.
+    ///
+    /// Configuring a frame buffer normally writes the control bits of every scanline, which is
+    /// wasted time if only a few scanlines are ever drawn to. With this enabled, configuring only
+    /// records what to write, and each scanline is written when a pixel on it is set (or one of its
+    /// planes is borrowed with `plane_mut`). Scanlines without control bits can't be shown
+    /// properly, so starting a transfer writes any that are left. Turning this off writes them
+    /// immediately.
+    pub fn set_lazy_control_bits(&mut self, lazy_control_bits: bool) {
+        self.lazy_control_bits = lazy_control_bits;
+        if !lazy_control_bits {
+            self.flush_control_bits();
+        }
+    }
+
+    /// Write the control bits of every scanline that hasn't been written yet.
+    pub fn flush_control_bits(&mut self) {
+        for scanline in 0..SCANLINES_PER_FRAME {
+            self.write_deferred_control_bits(scanline);
+        }
+    }
+
+    fn write_deferred_control_bits(&mut self, scanline: usize) {
+        if self.deferred_control & (1 << scanline) == 0 {
+            return;
+        }
+        self.deferred_control &= !(1 << scanline);
+        Self::apply_deferred_control(
+            &mut self.scanlines[scanline],
+            scanline,
+            self.deferred_latch_blanking_count,
+            self.deferred_lit_columns.as_ref(),
+            self.address_transition,
+        );
+    }
+
+    /// Write the control bits (and brightness bits) that were deferred for a scanline into it.
+    fn apply_deferred_control(
+        line: &mut Scanline<
+            WIDTH,
+            HEIGHT,
+            CHAIN_LENGTH,
+            COLOR_DEPTH,
+            PER_FRAME_DENOMINATOR,
+            WORDS_PER_PLANE,
+            Word,
+        >,
+        scanline: usize,
+        latch_blanking_count: u8,
+        lit_columns: Option<&LitColumns>,
+        address_transition: bool,
+    ) {
+        line.set_control_bits(scanline, Self::SCANLINES_PER_FRAME, latch_blanking_count);
+        if let Some(lit_columns) = lit_columns {
+            line.set_brightness_bits(lit_columns);
+        }
+        if address_transition {
+            for word in line.planes[0].buffer.iter_mut() {
+                word.set_address(scanline as u8);
+            }
+        }
+    }
+
+    /// Call `f` with a scanline as it will be sent, filling in any deferred control bits on a copy
+    /// instead of writing them to the frame buffer.
+    fn with_resolved_scanline<R>(
+        &self,
+        scanline: usize,
+        f: impl FnOnce(
+            &Scanline<
+                WIDTH,
+                HEIGHT,
+                CHAIN_LENGTH,
+                COLOR_DEPTH,
+                PER_FRAME_DENOMINATOR,
+                WORDS_PER_PLANE,
+                Word,
+            >,
+        ) -> R,
+    ) -> R {
+        if self.deferred_control & (1 << scanline) == 0 {
+            return f(&self.scanlines[scanline]);
+        }
+        let mut line = self.scanlines[scanline];
+        Self::apply_deferred_control(
+            &mut line,
+            scanline,
+            self.deferred_latch_blanking_count,
+            self.deferred_lit_columns.as_ref(),
+            self.address_transition,
+        );
+        f(&line)
+    }
+
+    /// Call `f` with a color plane of a scanline as it will be sent, including any control bits
+    /// that are still deferred. Returns `None` if either index is out of bounds.
+    pub(crate) fn with_resolved_plane<R>(
+        &self,
+        scanline: usize,
+        plane: usize,
+        f: impl FnOnce(&[Word]) -> R,
+    ) -> Option<R> {
+        if scanline >= SCANLINES_PER_FRAME || plane >= COLOR_DEPTH {
+            return None;
+        }
+        Some(self.with_resolved_scanline(scanline, |line| f(line.plane(plane))))
+    }
+
+    fn set_first_plane_addresses(&mut self) {
+        for (address, scanline) in self.scanlines.iter_mut().enumerate() {
+            let address = if self.address_transition {
+                address
+            } else {
+                (address + SCANLINES_PER_FRAME - 1) % SCANLINES_PER_FRAME
+            };
+            for word in scanline.planes[0].buffer.iter_mut() {
+                word.set_address(address as u8);
+            }
+        }
+    }
+
+    fn set_ghost_blanking(&mut self) {
+        for (address, segment) in self.ghost_blanking.iter_mut().enumerate() {
+            let control = control_word(address as u8, false, true);
+            for word in segment.iter_mut() {
+                *word = Word::ZERO;
+                word.replace_control_bits(control);
+            }
+        }
+    }
+
+    /// Check that every address is shown exactly once each frame.
+    ///
+    /// The first color plane of each scanline is sent while the previous scanline is still being
+    /// shown, so it has the address of the previous scanline (unless address transitions are
+    /// enabled, in which case it has its own address). This is a debugging aid for catching
+    /// control bit problems (which look like rows that never light up), and is too slow to run
+    /// every frame.
+    pub fn verify_addressing(&self) -> Result<(), AddressingError> {
+        let mut seen = 0u32;
+        for scanline_index in 0..SCANLINES_PER_FRAME {
+            let consistent_address = self.with_resolved_scanline(scanline_index, |scanline| {
+                let plane = scanline.plane(0);
+                let previous_address = plane[0].address();
+                plane
+                    .iter()
+                    .all(|word| word.address() == previous_address)
+                    .then_some(previous_address)
+            });
+            let Some(previous_address) = consistent_address else {
+                return Err(AddressingError::Inconsistent {
+                    scanline: scanline_index,
+                });
+            };
+            let address = if self.address_transition {
+                previous_address as usize
+            } else {
+                (previous_address as usize + 1) % Self::SCANLINES_PER_FRAME
+            };
+            if seen & (1 << address) != 0 {
+                return Err(AddressingError::Duplicate {
+                    address: address as u8,
+                });
+            }
+            seen |= 1 << address;
+        }
+        match (0..Self::SCANLINES_PER_FRAME).find(|address| seen & (1 << address) == 0) {
+            Some(address) => Err(AddressingError::Missing {
+                address: address as u8,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Work out the latch blanking count and brightness a frame buffer was configured with, from
+    /// the output enable window of the first scanline.
+    ///
+    /// This is useful when a frame buffer has been passed around (for example, returned from
+    /// stopping a transfer) and the configuration it was last given isn't known. The most
+    /// significant plane is used, as it is never shortened by `lsb_half_duty`. A post-latch delay
+    /// can't be told apart from the latch blanking, so it is counted in the latch blanking count
+    /// (and the brightness estimate is a little high). `None` is returned if the frame buffer
+    /// hasn't been configured.
+    pub fn inferred_config(&self) -> Option<InferredConfig> {
+        if !self.configured {
+            return None;
+        }
+        let (first_lit, lit) = self
+            .with_resolved_plane(0, Self::COLOR_DEPTH - 1, |plane| {
+                (
+                    plane.iter().position(|word| !word.output_enable()),
+                    plane.iter().filter(|word| !word.output_enable()).count(),
+                )
+            })
+            .unwrap_or_default();
+        let Some(first_lit) = first_lit else {
+            return Some(InferredConfig {
+                latch_blanking_count: None,
+                brightness_estimate: 0,
+            });
+        };
+        // The same number of columns are blanked after the lit columns and before the latch.
+        let available = Self::WORDS_PER_PLANE.saturating_sub(1 + 2 * first_lit);
+        let brightness = if available == 0 {
+            u8::MAX as usize
+        } else {
+            (lit * u8::MAX as usize).div_ceil(available)
+        };
+        Some(InferredConfig {
+            latch_blanking_count: Some(first_lit as u8),
+            brightness_estimate: brightness.min(u8::MAX as usize) as u8,
+        })
+    }
+
+    /// Check that every word in the buffer is zero, including the control bits.
+    ///
+    /// A new frame buffer is blank, but one that has been configured or drawn to is not.
+    pub fn is_blank(&self) -> bool {
+        // Scanlines with deferred control bits will always have at least the latch bit set.
+        if self.deferred_control != 0 {
+            return false;
+        }
+        self.scanlines
+            .iter()
+            .flat_map(|scanline| scanline.planes.iter())
+            .all(|plane| plane.buffer.iter().all(|word| *word == Word::ZERO))
+    }
+
+    pub(crate) fn configure(&mut self, latch_blanking_count: u8, lit_columns: &LitColumns) {
         self.set_control_bits(latch_blanking_count);
-        self.set_brightness_bits(latch_blanking_count, brightness);
+        self.set_brightness_bits(lit_columns);
         self.configured = true;
     }
 
     /// Set the address, output enable, and latch values across all pixels in a framebuffer.
+    ///
+    /// With lazy control bits enabled, this is deferred until each scanline is drawn to.
     pub(crate) fn set_control_bits(&mut self, latch_blanking_count: u8) {
-        let last_column = Self::WORDS_PER_PLANE - 1;
-        let non_blanked_range_start = latch_blanking_count as usize;
-        // Always at least one column, so subtract 1, then subtract the additional blanking
-        // columns.
-        let non_blanked_range_end = Self::WORDS_PER_PLANE - 1 - latch_blanking_count as usize;
-        let non_blanked_range = non_blanked_range_start..non_blanked_range_end;
-        for pixel_ref in self.iter_mut_pixels() {
-            // The first color plane has the previous scanline's address values as we're clocking
-            // in the new scanline of data (except for the first row).
-            let address = if pixel_ref.color_plane == 0 {
-                (pixel_ref.scanline + Self::SCANLINES_PER_FRAME - 1) % Self::SCANLINES_PER_FRAME
-            } else {
-                pixel_ref.scanline
-            } as u8;
-            pixel_ref.word.set_address(address);
-            // Set LAT at the last pixel in each scanline
-            if pixel_ref.column == last_column {
-                pixel_ref.word.set_latch();
-            } else {
-                pixel_ref.word.clear_latch();
+        if self.lazy_control_bits {
+            // `Self::SCANLINES_PER_FRAME` is checked to be at most 32, so every scanline has a bit.
+            self.deferred_control = u32::MAX >> (u32::BITS as usize - Self::SCANLINES_PER_FRAME);
+            self.deferred_latch_blanking_count = latch_blanking_count;
+            self.deferred_lit_columns = None;
+        } else {
+            for (address, scanline) in self.scanlines.iter_mut().enumerate() {
+                scanline.set_control_bits(address, Self::SCANLINES_PER_FRAME, latch_blanking_count);
+            }
+            if self.address_transition {
+                self.set_first_plane_addresses();
             }
-            // Set OE (output enable) for all columns besides the blanking range.
-            pixel_ref
-                .word
-                .set_output_enable_to(!non_blanked_range.contains(&pixel_ref.column));
         }
+        self.set_ghost_blanking();
     }
 
-    pub(crate) fn set_brightness_bits(&mut self, latch_blanking_count: u8, brightness: u8) {
-        let last_column = Self::WORDS_PER_PLANE - 1;
-        for pixel_ref in self.iter_mut_pixels() {
-            let blanking_count = latch_blanking_count as usize;
-            if pixel_ref.column >= blanking_count
-                || (pixel_ref.column <= last_column - blanking_count)
-            {
-                // TODO: For now, always enable output. So super bright :/
-                *pixel_ref.word |= 1 << 7;
+    pub(crate) fn set_brightness_bits(&mut self, lit_columns: &LitColumns) {
+        for (index, scanline) in self.scanlines.iter_mut().enumerate() {
+            // The brightness of deferred scanlines is written along with their control bits.
+            if self.deferred_control & (1 << index) == 0 {
+                scanline.set_brightness_bits(lit_columns);
+                #[cfg(test)]
+                {
+                    self.word_writes += WORDS_PER_PLANE * COLOR_DEPTH;
+                }
             }
         }
+        if self.deferred_control != 0 {
+            self.deferred_lit_columns = Some(lit_columns.clone());
+        }
     }
 
-    fn scanline_for(
+    /// Set the brightness bits of a single scanline, leaving the other scanlines unchanged.
+    pub(crate) fn set_scanline_brightness_bits(
         &mut self,
+        scanline: usize,
+        lit_columns: &LitColumns,
+    ) {
+        self.write_deferred_control_bits(scanline);
+        self.scanlines[scanline].set_brightness_bits(lit_columns);
+        #[cfg(test)]
+        {
+            self.word_writes += WORDS_PER_PLANE * COLOR_DEPTH;
+        }
+    }
+
+    /// Set the color of a pixel exactly as given.
+    ///
+    /// Unlike drawing through `RgbMatrix`, no gamma correction is applied, so this is the way to
+    /// write colors that have already been corrected. Only the color bits are changed.
+    ///
+    /// Exactly one word is written in each of the `COLOR_DEPTH` color planes, all at the scanline
+    /// and word index given by `locate()`.
+    pub fn set_pixel_raw<CS: ColorStorage<COLOR_DEPTH>>(
+        &mut self,
+        x: usize,
         y: usize,
-    ) -> &mut Scanline<
-        WIDTH,
-        HEIGHT,
-        CHAIN_LENGTH,
-        COLOR_DEPTH,
-        PER_FRAME_DENOMINATOR,
-        WORDS_PER_PLANE,
-    > {
-        let offset = y % Self::PER_FRAME_DENOMINATOR as usize;
-        &mut self.scanlines[offset]
+        red: CS,
+        green: CS,
+        blue: CS,
+    ) {
+        self.set_pixel(x, y, red, green, blue)
     }
 
     pub(crate) fn set_pixel<CS: ColorStorage<COLOR_DEPTH>>(
@@ -405,42 +1223,411 @@ impl<
         green: CS,
         blue: CS,
     ) {
-        let bits_index_iter = red
-            .iter_bits()
-            .zip(green.iter_bits())
-            .zip(blue.iter_bits())
-            .enumerate();
-        let scanline = self.scanline_for(y);
-        for (plane_index, ((red_bit, green_bit), blue_bit)) in bits_index_iter {
-            let scanline_idx = y % Self::SCANLINES_PER_FRAME;
-            let buffer_idx =
-                (x + ((y / Self::SCANLINES_PER_FRAME) * Self::WIDTH) % Self::WORDS_PER_PLANE);
-            let scanline = &mut self.scanlines[scanline_idx];
-            let plane = &mut scanline.planes[plane_index];
-            let word = &mut plane.buffer[buffer_idx];
-            let pixel_selection = if y < Self::HEIGHT / PIXELS_PER_CLOCK {
-                MatrixPixel::One
+        self.set_pixel_planes(x, y, &plane_bits(red, green, blue), None)
+    }
+
+    /// Set the color of a pixel from bits that have already been split up by `plane_bits()`.
+    ///
+    /// If `lit_columns` is given (the value of `MatrixConfig::lit_columns()` for the brightness
+    /// being applied), the brightness bits are also set for every word the pixel is packed into.
+    /// When every pixel in the buffer is being rewritten this is cheaper than calling
+    /// `set_brightness_bits` first, as each word is only visited once.
+    pub(crate) fn set_pixel_planes(
+        &mut self,
+        x: usize,
+        y: usize,
+        plane_bits: &[u8; COLOR_DEPTH],
+        lit_columns: Option<&LitColumns>,
+    ) {
+        let location = self.locate(x, y);
+        self.write_deferred_control_bits(location.scanline);
+        self.scanlines[location.scanline].set_pixel_planes(&location, plane_bits, lit_columns);
+        #[cfg(test)]
+        {
+            self.word_writes += COLOR_DEPTH;
+        }
+    }
+
+    /// Clear the color bits of every pixel in a rectangle, leaving the control and brightness bits
+    /// unchanged.
+    ///
+    /// The parts of the rectangle outside the chain of panels are ignored.
+    pub fn clear_colors_region(&mut self, area: Rectangle) {
+        let area = area.intersection(&Rectangle::new(
+            Point::zero(),
+            Size::new(
+                (Self::WIDTH * Self::CHAIN_LENGTH) as u32,
+                Self::HEIGHT as u32,
+            ),
+        ));
+        let Some(bottom_right) = area.bottom_right() else {
+            return;
+        };
+        let black = [0u8; COLOR_DEPTH];
+        for y in area.top_left.y as usize..=bottom_right.y as usize {
+            for x in area.top_left.x as usize..=bottom_right.x as usize {
+                let location = self.locate(x, y);
+                self.scanlines[location.scanline].set_pixel_planes(&location, &black, None);
+            }
+        }
+    }
+
+    /// Fill a rectangle with a single color, writing both pixels of a word at once wherever
+    /// possible.
+    ///
+    /// Each word holds a pixel from each of two rows (which rows depends on the lane mapping). Where
+    /// both rows are inside the rectangle, every word of the span is written in one pass for both
+    /// rows. Rows without their partner in the rectangle only have their own lane of each word
+    /// written, leaving the partner's pixels unchanged. Like `set_pixel_raw`, no gamma correction
+    /// is applied, and the parts of the rectangle outside the chain of panels are ignored.
+    pub fn fill_rect_fast<CS: ColorStorage<COLOR_DEPTH>>(
+        &mut self,
+        area: Rectangle,
+        red: CS,
+        green: CS,
+        blue: CS,
+    ) {
+        let chain_width = Self::WIDTH * Self::CHAIN_LENGTH;
+        let area = area.intersection(&Rectangle::new(
+            Point::zero(),
+            Size::new(chain_width as u32, Self::HEIGHT as u32),
+        ));
+        let Some(bottom_right) = area.bottom_right() else {
+            return;
+        };
+        let rows = area.top_left.y as usize..=bottom_right.y as usize;
+        let columns = area.top_left.x as usize..=bottom_right.x as usize;
+        let bits = plane_bits(red, green, blue);
+        let column_offset = self.column_offset as usize;
+        for y in rows.clone() {
+            let panel_y = y % Self::HEIGHT;
+            let partner = match self.lane_mapping {
+                LaneMapping::Halves if panel_y < Self::HEIGHT / PIXELS_PER_CLOCK => {
+                    y + Self::HEIGHT / PIXELS_PER_CLOCK
+                }
+                LaneMapping::Halves => y - Self::HEIGHT / PIXELS_PER_CLOCK,
+                LaneMapping::Interleaved if panel_y % PIXELS_PER_CLOCK == 0 => y + 1,
+                LaneMapping::Interleaved => y - 1,
+            };
+            let paired = rows.contains(&partner);
+            if paired && partner < y {
+                // Already filled along with the partner row.
+                continue;
+            }
+            let start = self.locate(*columns.start(), y);
+            let lanes: &[MatrixPixel] = if paired {
+                &[MatrixPixel::One, MatrixPixel::Two]
             } else {
-                MatrixPixel::Two
+                core::slice::from_ref(&start.lane)
             };
-            word.set_red_to(pixel_selection, red_bit);
-            word.set_green_to(pixel_selection, green_bit);
-            word.set_blue_to(pixel_selection, blue_bit);
+            // Consecutive columns are in consecutive words, wrapping around at the end of the
+            // chain when there is a column offset.
+            let offset_x = |x: usize| (x + column_offset) % chain_width;
+            let row_base = start.word_index - offset_x(*columns.start());
+            let words = columns.clone().map(|x| row_base + offset_x(x));
+            self.write_deferred_control_bits(start.scanline);
+            let scanline = &mut self.scanlines[start.scanline];
+            for word_index in words {
+                for (plane, bits) in scanline.planes.iter_mut().zip(bits.iter()) {
+                    let word = &mut plane.buffer[word_index];
+                    for lane in lanes {
+                        word.set_red_to(*lane, bits & PLANE_BIT_RED != 0);
+                        word.set_green_to(*lane, bits & PLANE_BIT_GREEN != 0);
+                        word.set_blue_to(*lane, bits & PLANE_BIT_BLUE != 0);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Set or clear the color bits of every pixel in a single color plane.
+    ///
+    /// Every other plane, and the control and brightness bits of the chosen plane, are left
+    /// unchanged. Lighting one plane at a time is a way to check the timing and brightness of each
+    /// plane on its own. Planes past `COLOR_DEPTH` are ignored.
+    pub fn write_single_plane(&mut self, plane: usize, on: bool) {
+        let words = self
+            .scanlines
+            .iter_mut()
+            .filter_map(|scanline| scanline.planes.get_mut(plane))
+            .flat_map(|plane| plane.buffer.iter_mut());
+        for word in words {
+            for lane in [MatrixPixel::One, MatrixPixel::Two] {
+                word.set_red_to(lane, on);
+                word.set_green_to(lane, on);
+                word.set_blue_to(lane, on);
+            }
+        }
+    }
+
+    /// Blend the colors of two frame buffers into a third, for crossfading between scenes.
+    ///
+    /// `t` is how far through the fade `out` is, from 0 (the colors of `a`) to 255 (the colors of
+    /// `b`). The colors of every pixel are read back from the color planes of both buffers,
+    /// interpolated, and packed into `out`, leaving its control and brightness bits unchanged.
+    /// This touches every word of all three buffers, so it is too slow to do every frame on a
+    /// large chain.
+    pub fn crossfade(a: &Self, b: &Self, t: u8, out: &mut Self) {
+        let t = t as u32;
+        let max = u8::MAX as u32;
+        for y in 0..Self::HEIGHT {
+            for x in 0..(Self::WIDTH * Self::CHAIN_LENGTH) {
+                let from = a.pixel_components(x, y);
+                let to = b.pixel_components(x, y);
+                let mut planes = [0u8; COLOR_DEPTH];
+                for (bit, (from, to)) in [PLANE_BIT_RED, PLANE_BIT_GREEN, PLANE_BIT_BLUE]
+                    .into_iter()
+                    .zip(from.into_iter().zip(to))
+                {
+                    // Rounded to the nearest value.
+                    let value = (from * (max - t) + to * t + max / 2) / max;
+                    for (plane_index, plane) in planes.iter_mut().enumerate() {
+                        if value & (1 << plane_index) != 0 {
+                            *plane |= bit;
+                        }
+                    }
+                }
+                out.set_pixel_planes(x, y, &planes, None);
+            }
+        }
+    }
+
+    /// The fraction of each frame that the red, green, and blue channels of a pixel are lit, as
+    /// `(red, green, blue)`.
+    ///
+    /// This adds up the lit columns of every segment the pixel's color bit is set in, so the plane
+    /// weighting, brightness, and any extra blank segments are all accounted for. Comparing the
+    /// duties of a white pixel is a way of checking the white balance of a color correction.
+    pub fn channel_duty(&self, x: usize, y: usize) -> (f32, f32, f32) {
+        let location = self.locate(x, y);
+        let lit = self.with_resolved_scanline(location.scanline, |scanline| {
+            let mut lit = [0usize; 3];
+            for (plane_index, plane) in scanline.planes.iter().enumerate() {
+                let word = &plane.buffer[location.word_index];
+                let lit_columns = plane
+                    .buffer
+                    .iter()
+                    .filter(|word| !word.output_enable())
+                    .count();
+                let bits = [
+                    word.red(location.lane),
+                    word.green(location.lane),
+                    word.blue(location.lane),
+                ];
+                for (lit, bit) in lit.iter_mut().zip(bits) {
+                    if bit {
+                        *lit += lit_columns * self.plane_repeats(plane_index);
+                    }
+                }
+            }
+            lit
+        });
+        let frame_words = self
+            .buffer_iter()
+            .map(|segment| segment.len())
+            .sum::<usize>() as f32;
+        (
+            lit[0] as f32 / frame_words,
+            lit[1] as f32 / frame_words,
+            lit[2] as f32 / frame_words,
+        )
+    }
+
+    /// Read the red, green, and blue components of a pixel back from the color planes.
+    fn pixel_components(&self, x: usize, y: usize) -> [u32; 3] {
+        let location = self.locate(x, y);
+        let mut components = [0u32; 3];
+        for (plane_index, plane) in self.scanlines[location.scanline].planes.iter().enumerate() {
+            let word = &plane.buffer[location.word_index];
+            let bits = [
+                word.red(location.lane),
+                word.green(location.lane),
+                word.blue(location.lane),
+            ];
+            for (component, bit) in components.iter_mut().zip(bits) {
+                *component |= (bit as u32) << plane_index;
+            }
         }
+        components
     }
 
-    pub fn buffer_iter<'a>(&'a self) -> impl Iterator<Item = &'a [u16]> {
+    /// Find where the bits for a pixel are stored, without changing anything.
+    ///
+    /// The same location is used in every color plane.
+    pub fn locate(&self, x: usize, y: usize) -> PixelLocation {
+        Self::locate_with(self.lane_mapping, self.lane_order, self.column_offset, x, y)
+    }
+
+    /// Find where the bits for a pixel would be stored with a given lane mapping, lane order, and
+    /// column offset.
+    pub(crate) fn locate_with(
+        lane_mapping: LaneMapping,
+        lane_order: LaneOrder,
+        column_offset: u16,
+        x: usize,
+        y: usize,
+    ) -> PixelLocation {
+        // The lanes are split within each physical panel, so panels stacked below the first one use
+        // the row within their own panel to pick a lane.
+        let panel_y = y % Self::HEIGHT;
+        // `lane_row` is the row counted within the rows of the selected lane.
+        let (lane, lane_row) = match lane_mapping {
+            LaneMapping::Halves => {
+                let pixel_selection = if panel_y < Self::HEIGHT / PIXELS_PER_CLOCK {
+                    MatrixPixel::One
+                } else {
+                    MatrixPixel::Two
+                };
+                (pixel_selection, y)
+            }
+            LaneMapping::Interleaved => {
+                let pixel_selection = match panel_y % PIXELS_PER_CLOCK {
+                    0 => MatrixPixel::One,
+                    _ => MatrixPixel::Two,
+                };
+                (pixel_selection, y / PIXELS_PER_CLOCK)
+            }
+        };
+        let lane = match (lane_order, lane) {
+            (LaneOrder::Normal, lane) => lane,
+            (LaneOrder::Swapped, MatrixPixel::One) => MatrixPixel::Two,
+            (LaneOrder::Swapped, MatrixPixel::Two) => MatrixPixel::One,
+        };
+        // The offset makes up for panels that shift the data by a few columns before latching it.
+        let x = (x + column_offset as usize) % (Self::WIDTH * Self::CHAIN_LENGTH);
+        PixelLocation {
+            scanline: lane_row % Self::SCANLINES_PER_FRAME,
+            // `x` already spans the whole chain, so rows sharing a scanline are a chain width apart.
+            word_index: x
+                + ((lane_row / Self::SCANLINES_PER_FRAME) * Self::WIDTH * Self::CHAIN_LENGTH)
+                    % Self::WORDS_PER_PLANE,
+            lane,
+        }
+    }
+
+    /// Write a full frame of pixels at once.
+    ///
+    /// `pixels` is in row-major order, and covers the entire chain of panels. Only the most
+    /// significant `COLOR_DEPTH` bits of each component are used.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the length of `pixels` is not `WIDTH * CHAIN_LENGTH * HEIGHT`.
+    pub fn pack_rgb888(&mut self, pixels: &[Rgb888])
+    where
+        u8: ColorStorage<COLOR_DEPTH>,
+    {
+        let chain_width = Self::WIDTH * Self::CHAIN_LENGTH;
+        assert_eq!(
+            pixels.len(),
+            chain_width * Self::HEIGHT,
+            "pixels must cover the entire chain of panels"
+        );
+        let shift = 8 - Self::COLOR_DEPTH;
+        for (index, pixel) in pixels.iter().enumerate() {
+            let bits = plane_bits(pixel.r() >> shift, pixel.g() >> shift, pixel.b() >> shift);
+            self.set_pixel_planes(index % chain_width, index / chain_width, &bits, None);
+        }
+    }
+
+    /// The raw words for a single color plane of a scanline.
+    ///
+    /// Returns `None` if either the scanline or color plane index is out of bounds, or if the
+    /// scanline's control bits are still deferred (see `set_lazy_control_bits`). Call
+    /// `flush_control_bits` first to read those scanlines.
+    pub fn plane(&self, scanline: usize, plane: usize) -> Option<&[Word]> {
+        if scanline < SCANLINES_PER_FRAME && self.deferred_control & (1 << scanline) != 0 {
+            return None;
+        }
+        self.scanlines
+            .get(scanline)
+            .and_then(|scanline| scanline.planes.get(plane))
+            .map(|plane| &plane.buffer[..])
+    }
+
+    /// Mutable access to the raw words for a single color plane of a scanline.
+    ///
+    /// This allows writing the control bits (address, latch, and output enable) as well as the
+    /// color bits, so care needs to be taken to not disrupt the timing of the panel.
+    ///
+    /// Returns `None` if either the scanline or color plane index is out of bounds.
+    pub fn plane_mut(&mut self, scanline: usize, plane: usize) -> Option<&mut [Word]> {
+        if scanline < SCANLINES_PER_FRAME {
+            self.write_deferred_control_bits(scanline);
+        }
+        self.scanlines
+            .get_mut(scanline)
+            .and_then(|scanline| scanline.planes.get_mut(plane))
+            .map(|plane| &mut plane.buffer[..])
+    }
+
+    /// How many times a color plane is sent in each frame, which is its weight in the BCM order.
+    fn plane_repeats(&self, plane: usize) -> usize {
+        let weight = match self.bcm_order {
+            BcmOrder::Ascending => plane,
+            BcmOrder::Descending => Self::COLOR_DEPTH - 1 - plane,
+        };
+        1 << weight
+    }
+
+    /// Iterate over every segment of a frame, in the order they are sent.
+    ///
+    /// Scanlines with deferred control bits are yielded as they are stored, without their control
+    /// bits, so `flush_control_bits` needs to be called before sending them.
+    pub fn buffer_iter(&self) -> impl Iterator<Item = &[Word]> {
+        let next_segments = self.buffer_iter_meta().cycle().skip(1);
+        self.buffer_iter_meta()
+            .enumerate()
+            .zip(next_segments)
+            .flat_map(
+                move |((index, (_, scanline, buffer)), (next_plane, next_scanline, _))| {
+                    // Each scanline is first sent in the first round of the first color plane.
+                    let transition = (self.address_transition && index < SCANLINES_PER_FRAME)
+                        .then(|| &self.ghost_blanking[scanline][..]);
+                    // The blank segment switches to the address the next segment starts with. The
+                    // first color plane starts with the previous scanline's address.
+                    let address = if next_plane == 0 && !self.address_transition {
+                        (next_scanline + SCANLINES_PER_FRAME - 1) % SCANLINES_PER_FRAME
+                    } else {
+                        next_scanline
+                    };
+                    let blanking = self
+                        .ghost_reduction
+                        .then(|| &self.ghost_blanking[address][..]);
+                    transition
+                        .into_iter()
+                        .chain(iter::once(buffer))
+                        .chain(blanking)
+                },
+            )
+    }
+
+    /// Iterate over the color plane segments of `buffer_iter` (skipping any ghost reduction or
+    /// address transition segments), along with the color plane and scanline index of each segment, as
+    /// `(plane, scanline, segment)`.
+    ///
+    /// The order of the segments is what gives each color plane its weight, so it is part of the
+    /// timing of the panel. Like `buffer_iter`, this doesn't fill in deferred control bits.
+    pub fn buffer_iter_meta(&self) -> impl Iterator<Item = (usize, usize, &[Word])> + Clone {
         // Loop from 0 to COLOR_DEPTH
         (0..Self::COLOR_DEPTH)
-            // Repeat each color plane index 2^(plane index) times
-            .flat_map(|plane| iter::repeat(plane).take(1 << plane))
+            // Repeat each color plane index 2^(plane index) times (or the reverse)
+            .flat_map(move |plane| iter::repeat_n(plane, self.plane_repeats(plane)))
             // For each color plane, iterate through each scanline index
             .flat_map(|plane| (0..SCANLINES_PER_FRAME).zip(iter::repeat(plane)))
             // Yield a slice for the given scanline index and color plane index
-            .map(|(scanline, plane)| &self.scanlines[scanline].planes[plane].buffer[..])
+            .map(|(scanline, plane)| {
+                (
+                    plane,
+                    scanline,
+                    &self.scanlines[scanline].planes[plane].buffer[..],
+                )
+            })
     }
 
-    pub(crate) fn buffer_ptr_iter<'a>(&'a self) -> impl Iterator<Item = (*const u8, usize)> + 'a {
+    // Only used by `esp32s3` outside of the tests.
+    #[cfg_attr(not(feature = "esp32s3"), allow(dead_code))]
+    pub(crate) fn buffer_ptr_iter(&self) -> impl Iterator<Item = (*const u8, usize)> + '_ {
         self.buffer_iter().map(|buf| {
             let ptr_range = buf.as_ptr_range();
             // Safety:
@@ -470,27 +1657,195 @@ impl<
 }
 
 #[macro_export]
-macro_rules! alias_frame_buffer {
-    ($name:ident, $width:literal, $height:literal, $color_depth:literal, $chain_length:literal, $per_frame_denominator:literal) => {
-        type $name = FrameBuffer<
+macro_rules! alias_frame_buffer {
+    ($name:ident, $width:literal, $height:literal, $color_depth:literal, $chain_length:literal, $per_frame_denominator:literal) => {
+        type $name = FrameBuffer<
+            $width,
+            $height,
+            $chain_length,
+            $color_depth,
+            $per_frame_denominator,
+            // NOTE: the "2" here is the value of PIXELS_PER_CLOCK
+            { $width * $chain_length * $height / $per_frame_denominator / 2 },
+            { $height / ($height / $per_frame_denominator) },
+        >;
+    };
+    ($name:ident, $width:literal, $height:literal, $color_depth:literal, $chain_length:literal) => {
+        alias_frame_buffer!($name, $width, $height, $color_depth, $chain_length, 16);
+    };
+    ($name:ident, $width:literal, $height:literal, $color_depth:literal) => {
+        alias_frame_buffer!($name, $width, $height, $color_depth, 1);
+    };
+    ($name:ident, $width:literal, $height:literal) => {
+        alias_frame_buffer!($name, $width, $height, 8);
+    };
+}
+
+/// The control bits for one color plane of a scanline, with every color bit cleared.
+///
+/// The latch is set on the last column, and output is disabled for `latch_blanking_count` columns
+/// on either side of the latch (and the latch column itself).
+pub const fn control_row<const WORDS_PER_PLANE: usize>(
+    address: u8,
+    latch_blanking_count: u8,
+) -> [u16; WORDS_PER_PLANE] {
+    let last_column = WORDS_PER_PLANE - 1;
+    let non_blanked_range_start = latch_blanking_count as usize;
+    // Always at least one column, so subtract 1, then subtract the additional blanking
+    // columns.
+    let non_blanked_range_end = WORDS_PER_PLANE - 1 - latch_blanking_count as usize;
+    let mut row = [0u16; WORDS_PER_PLANE];
+    let mut column = 0;
+    while column < WORDS_PER_PLANE {
+        let blanked = column < non_blanked_range_start || column >= non_blanked_range_end;
+        row[column] = control_word(address, column == last_column, blanked);
+        column += 1;
+    }
+    row
+}
+
+/// The control bits for an entire frame buffer, computed at compile time.
+///
+/// The words are in the same order they are stored in a frame buffer: each scanline in turn, with
+/// every color plane of a scanline before the next scanline. `WORDS` must be
+/// `WORDS_PER_PLANE * COLOR_DEPTH * SCANLINES_PER_FRAME`. The brightness is not applied, so every
+/// column outside of the blanking columns has output enabled.
+pub const fn control_words<
+    const WORDS_PER_PLANE: usize,
+    const COLOR_DEPTH: usize,
+    const SCANLINES_PER_FRAME: usize,
+    const WORDS: usize,
+>(
+    latch_blanking_count: u8,
+) -> [u16; WORDS] {
+    if WORDS != WORDS_PER_PLANE * COLOR_DEPTH * SCANLINES_PER_FRAME {
+        panic!("WORDS must be WORDS_PER_PLANE * COLOR_DEPTH * SCANLINES_PER_FRAME");
+    }
+    let mut words = [0u16; WORDS];
+    let mut scanline = 0;
+    while scanline < SCANLINES_PER_FRAME {
+        let previous_scanline = (scanline + SCANLINES_PER_FRAME - 1) % SCANLINES_PER_FRAME;
+        let first_row =
+            control_row::<WORDS_PER_PLANE>(previous_scanline as u8, latch_blanking_count);
+        let row = control_row::<WORDS_PER_PLANE>(scanline as u8, latch_blanking_count);
+        let mut plane = 0;
+        while plane < COLOR_DEPTH {
+            let source = if plane == 0 { &first_row } else { &row };
+            let offset = (scanline * COLOR_DEPTH + plane) * WORDS_PER_PLANE;
+            let mut column = 0;
+            while column < WORDS_PER_PLANE {
+                words[offset + column] = source[column];
+                column += 1;
+            }
+            plane += 1;
+        }
+        scanline += 1;
+    }
+    words
+}
+
+/// Check a panel geometry, returning the number of words in each color plane.
+///
+/// This is used by `panel!`, and panics (which is a compile error in a const context) with a
+/// description of the first problem with the geometry.
+#[doc(hidden)]
+pub const fn __checked_words_per_plane(
+    width: usize,
+    height: usize,
+    color_depth: usize,
+    chain_length: usize,
+    per_frame_denominator: usize,
+) -> usize {
+    if width == 0 || height == 0 || color_depth == 0 || chain_length == 0 {
+        panic!("width, height, color_depth, and chain_length cannot be 0");
+    }
+    if color_depth > 16 {
+        panic!("color_depth cannot be more than 16");
+    }
+    if per_frame_denominator == 0 || per_frame_denominator > height {
+        panic!("per_frame_denominator must be between 1 and the height");
+    }
+    match height % per_frame_denominator {
+        0 => (),
+        _ => panic!("per_frame_denominator must divide the height evenly"),
+    }
+    if per_frame_denominator > 32 {
+        panic!("per_frame_denominator cannot be more than 32 (there are only 5 address lines)");
+    }
+    let pixels_per_scanline = width * chain_length * height / per_frame_denominator;
+    match pixels_per_scanline % PIXELS_PER_CLOCK {
+        0 => (),
+        _ => panic!("each scanline must have an even number of pixels"),
+    }
+    pixels_per_scanline / PIXELS_PER_CLOCK
+}
+
+/// The `FrameBuffer` type for a panel, with each dimension named.
+///
+/// `chain_length` defaults to 1 and `per_frame_denominator` defaults to 16 if they are left out,
+/// but the fields that are given must be in this order. The geometry is checked when the macro is
+/// expanded, so a combination that doesn't make sense (such as a `per_frame_denominator` that
+/// doesn't divide the height) fails to compile.
+///
+/// ```
+/// use hub75_bcm::panel;
+///
+/// type Panel = panel!(width: 64, height: 32, color_depth: 8, chain_length: 1, per_frame_denominator: 16);
+/// let frame_buffer = Panel::new();
+/// assert_eq!(frame_buffer.words_per_plane(), 64);
+/// ```
+///
+/// Transposing the height and per-frame denominator is caught:
+///
+/// ```compile_fail
+/// use hub75_bcm::panel;
+///
+/// type Panel = panel!(width: 64, height: 16, color_depth: 8, chain_length: 1, per_frame_denominator: 32);
+/// let frame_buffer = Panel::new();
+/// ```
+#[macro_export]
+macro_rules! panel {
+    (
+        width: $width:literal,
+        height: $height:literal,
+        color_depth: $color_depth:literal
+        $(, chain_length: $chain_length:literal)?
+        $(, per_frame_denominator: $per_frame_denominator:literal)?
+        $(,)?
+    ) => {
+        $crate::panel!(
+            @checked
             $width,
             $height,
-            $chain_length,
             $color_depth,
-            $per_frame_denominator,
-            // NOTE: the "2" here is the value of PIXELS_PER_CLOCK
-            { $width * $chain_length * $height / $per_frame_denominator / 2 },
-            { $height / ($height / $per_frame_denominator) },
-        >;
+            $crate::panel!(@or $($chain_length)? ; 1),
+            $crate::panel!(@or $($per_frame_denominator)? ; 16)
+        )
     };
-    ($name:ident, $width:literal, $height:literal, $color_depth:literal, $chain_length:literal) => {
-        alias_frame_buffer!($name, $width, $height, $color_depth, $chain_length, 16);
+    (@checked $width:literal, $height:literal, $color_depth:literal, $chain_length:expr, $per_frame_denominator:expr) => {
+        $crate::buffer::FrameBuffer<
+            $width,
+            $height,
+            { $chain_length },
+            $color_depth,
+            { $per_frame_denominator },
+            {
+                $crate::buffer::__checked_words_per_plane(
+                    $width,
+                    $height,
+                    $color_depth,
+                    $chain_length,
+                    $per_frame_denominator,
+                )
+            },
+            { $height / ($height / $per_frame_denominator) },
+        >
     };
-    ($name:ident, $width:literal, $height:literal, $color_depth:literal) => {
-        alias_frame_buffer!($name, $width, $height, $color_depth, 1);
+    (@or $value:literal ; $default:literal) => {
+        $value
     };
-    ($name:ident, $width:literal, $height:literal) => {
-        alias_frame_buffer!($name, $width, $height, 8);
+    (@or ; $default:literal) => {
+        $default
     };
 }
 
@@ -523,6 +1878,7 @@ macro_rules! declare_frame_buffer {
 mod test {
 
     use super::*;
+    use crate::matrix_word::{MatrixWord, MatrixWordMut};
 
     // Test cases are using std
     extern crate std;
@@ -632,6 +1988,178 @@ mod test {
         }
     }
 
+    #[test]
+    fn u32_word_buffer_iter_lengths() {
+        let fb = FrameBuffer::<64, 32, 1, 8, 16, 64, 16, u32>::new();
+        assert_eq!(fb.buffer_iter().count(), 255 * 16);
+        for (_, len) in fb.buffer_ptr_iter() {
+            assert_eq!(len, 64 * core::mem::size_of::<u32>());
+        }
+    }
+
+    #[test]
+    fn u32_word_matches_u16() {
+        let config = MatrixConfig::<64, 32, 1, 8, 16>::new(2);
+        let mut narrow = FrameBuffer::<64, 32, 1, 8, 16, 64, 16>::new();
+        let mut wide = FrameBuffer::<64, 32, 1, 8, 16, 64, 16, u32>::new();
+        narrow.configure(2, &config.lit_columns(200));
+        wide.configure(2, &config.lit_columns(200));
+        narrow.set_pixel(10, 20, 0x12u8, 0x34u8, 0x56u8);
+        wide.set_pixel(10, 20, 0x12u8, 0x34u8, 0x56u8);
+        for (narrow, wide) in narrow.buffer_iter().zip(wide.buffer_iter()) {
+            for (narrow, wide) in narrow.iter().zip(wide.iter()) {
+                assert_eq!(*narrow as u32, *wide);
+            }
+        }
+    }
+
+    #[test]
+    fn fb_is_blank() {
+        let mut fb = declare_frame_buffer!(32, 32, 8, 1, 16);
+        assert!(fb.is_blank());
+        fb.set_pixel(3, 20, 0u8, 1u8, 0u8);
+        assert!(!fb.is_blank());
+    }
+
+    #[test]
+    fn pack_rgb888_matches_set_pixel() {
+        let pixels: Vec<Rgb888> = (0..32 * 16)
+            .map(|i| Rgb888::new(i as u8, (i * 7) as u8, (i / 3) as u8))
+            .collect();
+        let mut packed = declare_frame_buffer!(32, 16, 8, 1, 8);
+        packed.pack_rgb888(&pixels);
+        let mut expected = declare_frame_buffer!(32, 16, 8, 1, 8);
+        for (i, pixel) in pixels.iter().enumerate() {
+            expected.set_pixel(i % 32, i / 32, pixel.r(), pixel.g(), pixel.b());
+        }
+        assert_eq!(packed, expected);
+    }
+
+    #[test]
+    fn pack_rgb888_reduced_depth() {
+        let mut packed = declare_frame_buffer!(32, 16, 4, 1, 8);
+        packed.pack_rgb888(&[Rgb888::new(0xF0, 0x80, 0x0F); 32 * 16]);
+        let mut expected = declare_frame_buffer!(32, 16, 4, 1, 8);
+        for y in 0..16 {
+            for x in 0..32 {
+                expected.set_pixel(x, y, 0xFu8, 0x8u8, 0x0u8);
+            }
+        }
+        assert_eq!(packed, expected);
+    }
+
+    #[test]
+    #[should_panic]
+    fn pack_rgb888_wrong_length() {
+        let mut fb = declare_frame_buffer!(32, 16, 8, 1, 8);
+        fb.pack_rgb888(&[Rgb888::BLACK; 10]);
+    }
+
+    #[test]
+    fn locate_64x32_sixteenth() {
+        let fb = declare_frame_buffer!(64, 32, 8, 1, 16);
+        let location = |scanline, word_index, lane| PixelLocation {
+            scanline,
+            word_index,
+            lane,
+        };
+        assert_eq!(fb.locate(0, 0), location(0, 0, MatrixPixel::One));
+        assert_eq!(fb.locate(63, 0), location(0, 63, MatrixPixel::One));
+        assert_eq!(fb.locate(10, 15), location(15, 10, MatrixPixel::One));
+        // The bottom half of the panel is on the second lane, sharing scanlines with the top.
+        assert_eq!(fb.locate(0, 16), location(0, 0, MatrixPixel::Two));
+        assert_eq!(fb.locate(10, 31), location(15, 10, MatrixPixel::Two));
+    }
+
+    #[test]
+    fn locate_lane_per_panel() {
+        let fb = declare_frame_buffer!(64, 32, 8, 1, 16);
+        let lane = |y| fb.locate(5, y).lane;
+        // Rows of a second panel stacked below the first are split into lanes by their row within
+        // that panel, not by the combined height.
+        assert_eq!(lane(15), MatrixPixel::One);
+        assert_eq!(lane(16), MatrixPixel::Two);
+        assert_eq!(lane(31), MatrixPixel::Two);
+        assert_eq!(lane(32), MatrixPixel::One);
+        assert_eq!(lane(40), MatrixPixel::One);
+        assert_eq!(lane(47), MatrixPixel::One);
+        assert_eq!(lane(48), MatrixPixel::Two);
+        assert_eq!(lane(63), MatrixPixel::Two);
+    }
+
+    #[test]
+    fn locate_matches_set_pixel() {
+        let mut fb = declare_frame_buffer!(64, 32, 1, 1, 16);
+        fb.set_pixel(12, 21, 0u8, 0u8, 1u8);
+        let location = fb.locate(12, 21);
+        let word = fb.plane(location.scanline, 0).unwrap()[location.word_index];
+        assert!(word.blue(location.lane));
+    }
+
+    #[test]
+    fn interleaved_lane_mapping() {
+        // 16 rows at 1/8 scan, so each scanline has one row on each lane.
+        let mut fb = declare_frame_buffer!(32, 16, 1, 1, 8);
+        fb.set_lane_mapping(LaneMapping::Interleaved);
+        for y in 0..16 {
+            fb.set_pixel(5, y, 1u8, 0u8, 0u8);
+        }
+        for scanline in 0..8 {
+            let word = fb.plane(scanline, 0).unwrap()[5];
+            assert!(word.red(MatrixPixel::One), "scanline {}", scanline);
+            assert!(word.red(MatrixPixel::Two), "scanline {}", scanline);
+        }
+
+        // Even rows are only on the first lane, odd rows are only on the second.
+        let mut fb = declare_frame_buffer!(32, 16, 1, 1, 8);
+        fb.set_lane_mapping(LaneMapping::Interleaved);
+        fb.set_pixel(5, 6, 1u8, 0u8, 0u8);
+        fb.set_pixel(7, 11, 1u8, 0u8, 0u8);
+        let word = fb.plane(3, 0).unwrap()[5];
+        assert!(word.red(MatrixPixel::One));
+        assert!(!word.red(MatrixPixel::Two));
+        let word = fb.plane(5, 0).unwrap()[7];
+        assert!(!word.red(MatrixPixel::One));
+        assert!(word.red(MatrixPixel::Two));
+        // With the default mapping row 11 is in the bottom half, so it is on the second lane of
+        // scanline 3.
+        let mut fb = declare_frame_buffer!(32, 16, 1, 1, 8);
+        fb.set_pixel(7, 11, 1u8, 0u8, 0u8);
+        let word = fb.plane(3, 0).unwrap()[7];
+        assert!(!word.red(MatrixPixel::One));
+        assert!(word.red(MatrixPixel::Two));
+    }
+
+    #[test]
+    fn swapped_lane_order() {
+        let mut fb = declare_frame_buffer!(32, 16, 1, 1, 8);
+        fb.set_lane_order(LaneOrder::Swapped);
+        // Row 2 is in the top half, and row 11 in the bottom half.
+        fb.set_pixel(5, 2, 1u8, 0u8, 0u8);
+        fb.set_pixel(7, 11, 0u8, 0u8, 1u8);
+        let word = fb.plane(2, 0).unwrap()[5];
+        assert!(!word.red(MatrixPixel::One));
+        assert!(word.red(MatrixPixel::Two));
+        let word = fb.plane(3, 0).unwrap()[7];
+        assert!(word.blue(MatrixPixel::One));
+        assert!(!word.blue(MatrixPixel::Two));
+        assert_eq!(fb.locate(7, 11).lane, MatrixPixel::One);
+    }
+
+    #[test]
+    fn column_offset() {
+        let mut fb = declare_frame_buffer!(32, 16, 1, 2, 8);
+        fb.set_column_offset(3);
+        assert_eq!(fb.locate(5, 2).word_index, 8);
+        // Wraps around at the end of the chain, not the end of the panel.
+        assert_eq!(fb.locate(30, 2).word_index, 33);
+        assert_eq!(fb.locate(62, 2).word_index, 1);
+        // Rows on the second lane are shifted the same way.
+        assert_eq!(fb.locate(62, 10).word_index, 1);
+        fb.set_pixel(62, 2, 1u8, 0u8, 0u8);
+        assert!(fb.plane(2, 0).unwrap()[1].red(MatrixPixel::One));
+    }
+
     fn check_frame_buffer_control_bits<
         const W: usize,
         const H: usize,
@@ -736,30 +2264,27 @@ mod test {
         }
     }
 
-    #[test]
     #[test]
     fn set_control_bits_eighth_square() {
-        let mut fb = declare_frame_buffer!(32, 32, 8, 1, 8);
-        let latch_blanking_count = 0usize;
+        let fb = declare_frame_buffer!(32, 32, 8, 1, 8);
         check_frame_buffer_control_bits(fb, 0);
     }
 
+    #[test]
     fn set_control_bits_eighth_square_blanking() {
-        let mut fb = declare_frame_buffer!(32, 32, 8, 1, 8);
+        let fb = declare_frame_buffer!(32, 32, 8, 1, 8);
         check_frame_buffer_control_bits(fb, 2);
     }
 
     #[test]
     fn set_control_bits_sixteenth_square() {
-        let mut fb = declare_frame_buffer!(32, 32, 8, 1, 16);
-        let latch_blanking_count = 0usize;
+        let fb = declare_frame_buffer!(32, 32, 8, 1, 16);
         check_frame_buffer_control_bits(fb, 0);
     }
 
     #[test]
     fn set_control_bits_sixteenth_square_blanking() {
-        let mut fb = declare_frame_buffer!(32, 32, 8, 1, 16);
-        let latch_blanking_count = 0usize;
+        let fb = declare_frame_buffer!(32, 32, 8, 1, 16);
         check_frame_buffer_control_bits(fb, 2);
     }
 
@@ -768,7 +2293,7 @@ mod test {
         let mut fb = declare_frame_buffer!(64, 32, 8, 1, 16);
         // Set the control bits; we need to ensure we don't clobber them.
         fb.set_control_bits(0);
-        fb.set_brightness_bits(0, 255);
+        fb.set_brightness_bits(&MatrixConfig::<64, 32, 1, 8, 16>::new(0).lit_columns(255));
         // Choosing different x and y values so we know the dimensions are correct.
         let x = 5;
         let y = 9;
@@ -800,7 +2325,7 @@ mod test {
         let mut fb = declare_frame_buffer!(64, 32, 8, 1, 16);
         // Set the control bits; we need to ensure we don't clobber them.
         fb.set_control_bits(0);
-        fb.set_brightness_bits(0, 255);
+        fb.set_brightness_bits(&MatrixConfig::<64, 32, 1, 8, 16>::new(0).lit_columns(255));
         // Choosing different x and y values so we know the dimensions are correct.
         let x = 5;
         let y = 9;
@@ -832,7 +2357,7 @@ mod test {
         let mut fb = declare_frame_buffer!(64, 32, 8, 1, 16);
         // Set the control bits; we need to ensure we don't clobber them.
         fb.set_control_bits(0);
-        fb.set_brightness_bits(0, 255);
+        fb.set_brightness_bits(&MatrixConfig::<64, 32, 1, 8, 16>::new(0).lit_columns(255));
         // Choosing different x and y values so we know the dimensions are correct.
         let x = 5;
         let y = 9;
@@ -864,7 +2389,7 @@ mod test {
         let mut fb = declare_frame_buffer!(64, 32, 8, 1, 16);
         // Set the control bits; we need to ensure we don't clobber them.
         fb.set_control_bits(0);
-        fb.set_brightness_bits(0, 255);
+        fb.set_brightness_bits(&MatrixConfig::<64, 32, 1, 8, 16>::new(0).lit_columns(255));
         // Choosing different x and y values so we know the dimensions are correct.
         let x = 5;
         let y = 20;
@@ -891,12 +2416,28 @@ mod test {
         }
     }
 
+    #[test]
+    fn halves_boundary_rows() {
+        // Row 16 is the first row of the lower half of a 32 row panel.
+        for (y, scanline, red_bit) in [(15, 15, 0x1), (16, 0, 0x8), (17, 1, 0x8)] {
+            let mut fb = declare_frame_buffer!(64, 32, 8, 1, 16);
+            fb.set_control_bits(2);
+            let location = fb.locate(7, y);
+            assert_eq!(location.scanline, scanline, "row {y}");
+            fb.set_pixel(7, y, 0xFFu8, 0, 0);
+            for plane in 0..8 {
+                let word = fb.plane(scanline, plane).unwrap()[7];
+                assert_eq!(word & 0x3F, red_bit, "row {y} plane {plane}");
+            }
+        }
+    }
+
     #[test]
     fn set_lower_green() {
         let mut fb = declare_frame_buffer!(64, 32, 8, 1, 16);
         // Set the control bits; we need to ensure we don't clobber them.
         fb.set_control_bits(0);
-        fb.set_brightness_bits(0, 255);
+        fb.set_brightness_bits(&MatrixConfig::<64, 32, 1, 8, 16>::new(0).lit_columns(255));
         // Choosing different x and y values so we know the dimensions are correct.
         let x = 5;
         let y = 20;
@@ -928,7 +2469,7 @@ mod test {
         let mut fb = declare_frame_buffer!(64, 32, 8, 1, 16);
         // Set the control bits; we need to ensure we don't clobber them.
         fb.set_control_bits(0);
-        fb.set_brightness_bits(0, 255);
+        fb.set_brightness_bits(&MatrixConfig::<64, 32, 1, 8, 16>::new(0).lit_columns(255));
         // Choosing different x and y values so we know the dimensions are correct.
         let x = 5;
         let y = 20;
@@ -954,4 +2495,615 @@ mod test {
             assert_eq!(actual_word & 0x20 != 0, expected_bits[plane_idx]);
         }
     }
+
+    #[test]
+    fn plane_out_of_bounds() {
+        let mut fb = declare_frame_buffer!(64, 32, 8, 1, 16);
+        assert!(fb.plane(16, 0).is_none());
+        assert!(fb.plane(0, 8).is_none());
+        assert!(fb.plane_mut(16, 0).is_none());
+        assert!(fb.plane_mut(0, 8).is_none());
+        assert_eq!(fb.plane(15, 7).map(|p| p.len()), Some(64));
+    }
+
+    #[test]
+    fn plane_mut_visible_in_buffer_iter() {
+        let mut fb = declare_frame_buffer!(64, 32, 4, 1, 16);
+        let plane = fb.plane_mut(3, 2).unwrap();
+        plane[10] = 0x3F;
+        // Plane 2 starts after plane 0 (1 repetition) and plane 1 (2 repetitions) of every
+        // scanline.
+        let first_segment = (1 + 2) * 16 + 3;
+        for (index, segment) in fb.buffer_iter().enumerate() {
+            let is_written = index >= first_segment
+                && (index - first_segment) % 16 == 0
+                && index < first_segment + 4 * 16;
+            assert_eq!(
+                segment[10] == 0x3F,
+                is_written,
+                "Unexpected value in segment {}",
+                index
+            );
+        }
+        assert_eq!(fb.plane(3, 2).unwrap()[10], 0x3F);
+    }
+
+    #[test]
+    fn clear_colors_region() {
+        let mut fb = declare_frame_buffer!(64, 32, 8, 1, 16);
+        fb.configure(
+            2,
+            &MatrixConfig::<64, 32, 1, 8, 16>::default().lit_columns(128),
+        );
+        for y in 0..32 {
+            for x in 0..64 {
+                fb.set_pixel(x, y, 0xFFu8, 0xFF, 0xFF);
+            }
+        }
+        let mut expected_control = fb.clone();
+        expected_control.clear_colors_region(Rectangle::new(Point::zero(), Size::new(64, 32)));
+        // The region crosses the boundary between the two lanes, and hangs off the bottom.
+        fb.clear_colors_region(Rectangle::new(Point::new(10, 14), Size::new(5, 40)));
+        for y in 0..32 {
+            for x in 0..64 {
+                let location = fb.locate(x, y);
+                let inside = (10..15).contains(&x) && y >= 14;
+                for plane in 0..8 {
+                    let word = fb.plane(location.scanline, plane).unwrap()[location.word_index];
+                    let lit = word.red(location.lane)
+                        || word.green(location.lane)
+                        || word.blue(location.lane);
+                    assert_eq!(lit, !inside, "({x}, {y}) plane {plane}");
+                }
+            }
+        }
+        // Only color bits were cleared.
+        fb.clear_colors_region(Rectangle::new(Point::zero(), Size::new(64, 32)));
+        assert_eq!(fb, expected_control);
+        assert!(fb.verify_addressing().is_ok());
+    }
+
+    #[test]
+    fn set_pixel_overwrites_color() {
+        let mut fb = declare_frame_buffer!(64, 32, 8, 1, 16);
+        fb.configure(
+            2,
+            &MatrixConfig::<64, 32, 1, 8, 16>::default().lit_columns(128),
+        );
+        // The other pixel sharing the word is left alone.
+        fb.set_pixel(5, 25, 0x0Fu8, 0xF0, 0x3C);
+        let mut expected = fb.clone();
+        expected.set_pixel(5, 9, 0b0101_0011u8, 0b1100_0101, 0b0011_1010);
+
+        fb.set_pixel(5, 9, 0b1010_1100u8, 0b0011_1010, 0b1100_0101);
+        fb.set_pixel(5, 9, 0b0101_0011u8, 0b1100_0101, 0b0011_1010);
+        assert_eq!(fb, expected);
+        assert_eq!(
+            fb.pixel_components(5, 9),
+            [0b0101_0011, 0b1100_0101, 0b0011_1010]
+        );
+        assert_eq!(fb.pixel_components(5, 25), [0x0F, 0xF0, 0x3C]);
+
+        // Black after white clears every color bit, leaving the control bits.
+        let control: Vec<u16> = (0..8)
+            .map(|plane| fb.plane(9, plane).unwrap()[5] & 0xFFC0)
+            .collect();
+        fb.set_pixel(5, 9, 0xFFu8, 0xFF, 0xFF);
+        fb.set_pixel(5, 9, 0u8, 0, 0);
+        for (plane, control) in control.iter().enumerate() {
+            let word = fb.plane(9, plane).unwrap()[5];
+            assert_eq!(word & 0xFFC0, *control, "plane {plane}");
+            assert_eq!(word & 0x07, 0, "plane {plane}");
+        }
+    }
+
+    #[test]
+    fn set_pixel_writes_one_word_per_plane() {
+        let mut fb = declare_frame_buffer!(64, 32, 8, 1, 16);
+        fb.configure(
+            2,
+            &MatrixConfig::<64, 32, 1, 8, 16>::default().lit_columns(128),
+        );
+        let before = fb.clone();
+        let location = fb.locate(37, 21);
+        fb.set_pixel_raw(37, 21, 0xFFu8, 0xFF, 0xFF);
+        let mut changed = 0;
+        for scanline in 0..16 {
+            for plane in 0..8 {
+                let words = fb.plane(scanline, plane).unwrap();
+                let old_words = before.plane(scanline, plane).unwrap();
+                for (index, (word, old_word)) in words.iter().zip(old_words.iter()).enumerate() {
+                    if word != old_word {
+                        assert_eq!((scanline, index), (location.scanline, location.word_index));
+                        changed += 1;
+                    }
+                }
+            }
+        }
+        assert_eq!(changed, 8);
+    }
+
+    #[test]
+    fn fill_rect_fast_matches_per_pixel() {
+        let areas = [
+            // Both halves of the panel, so every word is written for both lanes.
+            Rectangle::new(Point::new(3, 0), Size::new(20, 32)),
+            // Only some rows have their partner in the other half.
+            Rectangle::new(Point::new(10, 10), Size::new(7, 12)),
+            // Only the top half, and hanging off the edge of the chain.
+            Rectangle::new(Point::new(60, -2), Size::new(8, 5)),
+        ];
+        for lane_mapping in [LaneMapping::Halves, LaneMapping::Interleaved] {
+            for area in areas {
+                let mut fast = declare_frame_buffer!(64, 32, 8, 1, 16);
+                fast.configure(
+                    2,
+                    &MatrixConfig::<64, 32, 1, 8, 16>::default().lit_columns(200),
+                );
+                fast.set_lane_mapping(lane_mapping);
+                fast.set_lane_order(LaneOrder::Swapped);
+                fast.set_column_offset(5);
+                for y in 0..32 {
+                    for x in 0..64 {
+                        fast.set_pixel(x, y, (x * 4) as u8, (y * 8) as u8, 0x5Au8);
+                    }
+                }
+                let mut slow = fast.clone();
+                fast.fill_rect_fast(area, 0xC3u8, 0x3C, 0x99);
+                for y in 0..32 {
+                    for x in 0..64 {
+                        if area.contains(Point::new(x as i32, y as i32)) {
+                            slow.set_pixel(x, y, 0xC3u8, 0x3C, 0x99);
+                        }
+                    }
+                }
+                assert_eq!(fast, slow, "{lane_mapping:?} {area:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn write_single_plane() {
+        let mut fb = declare_frame_buffer!(64, 32, 8, 1, 16);
+        fb.configure(
+            2,
+            &MatrixConfig::<64, 32, 1, 8, 16>::default().lit_columns(128),
+        );
+        fb.set_pixel(5, 20, 0x5Au8, 0xA5, 0x0F);
+        let before = fb.clone();
+        fb.write_single_plane(3, true);
+        for scanline in 0..16 {
+            for plane in 0..8 {
+                let words = fb.plane(scanline, plane).unwrap();
+                let old_words = before.plane(scanline, plane).unwrap();
+                if plane != 3 {
+                    assert_eq!(words, old_words, "scanline {scanline} plane {plane}");
+                    continue;
+                }
+                for (word, old_word) in words.iter().zip(old_words.iter()) {
+                    assert_eq!(word & !0x3F, old_word & !0x3F);
+                    assert_eq!(word & 0x3F, 0x3F);
+                }
+            }
+        }
+        fb.write_single_plane(3, false);
+        fb.set_pixel(5, 20, 0x5Au8, 0xA5, 0x0F);
+        assert_eq!(fb, before);
+        // Out of range planes are ignored.
+        fb.write_single_plane(8, true);
+        assert_eq!(fb, before);
+    }
+
+    #[test]
+    fn buffer_iter_order() {
+        // 3 color planes, 2 scanlines
+        let fb = declare_frame_buffer!(4, 4, 3, 1, 2);
+        let order: Vec<(usize, usize)> = fb
+            .buffer_iter_meta()
+            .map(|(plane, scanline, _)| (plane, scanline))
+            .collect();
+        let expected = [
+            // Plane 0 is shown once
+            (0, 0),
+            (0, 1),
+            // Plane 1 is shown twice
+            (1, 0),
+            (1, 1),
+            (1, 0),
+            (1, 1),
+            // Plane 2 is shown four times
+            (2, 0),
+            (2, 1),
+            (2, 0),
+            (2, 1),
+            (2, 0),
+            (2, 1),
+            (2, 0),
+            (2, 1),
+        ];
+        assert_eq!(order, expected);
+        assert_eq!(fb.buffer_iter().count(), expected.len());
+        assert_eq!(fb.emitted_segments(), expected.len());
+        assert_eq!(fb.plane_count(), 3);
+    }
+
+    #[test]
+    fn bcm_order_descending() {
+        let mut fb = declare_frame_buffer!(4, 4, 3, 1, 2);
+        let order = |fb: &FrameBuffer<4, 4, 1, 3, 2, 4, 2>| -> Vec<(usize, usize)> {
+            fb.buffer_iter_meta()
+                .map(|(plane, scanline, _)| (plane, scanline))
+                .collect()
+        };
+        let ascending = order(&fb);
+        fb.set_bcm_order(BcmOrder::Descending);
+        let descending = order(&fb);
+        assert_ne!(ascending, descending);
+        assert_eq!(ascending.len(), descending.len());
+        assert_eq!(fb.buffer_iter().count(), fb.emitted_segments());
+        let count = |plane: usize| descending.iter().filter(|(p, _)| *p == plane).count();
+        assert_eq!([count(0), count(1), count(2)], [4 * 2, 2 * 2, 2]);
+        // The planes are still sent in order, starting with the first plane.
+        assert!(descending.windows(2).all(|pair| pair[0].0 <= pair[1].0));
+    }
+
+    #[test]
+    fn crossfade() {
+        let config = MatrixConfig::<64, 32, 1, 8, 16>::default();
+        let mut a = declare_frame_buffer!(64, 32, 8, 1, 16);
+        a.configure(2, &config.lit_columns(255));
+        let mut b = a.clone();
+        let mut out = a.clone();
+        for y in 0..32 {
+            for x in 0..64 {
+                a.set_pixel(x, y, (x * 4) as u8, (y * 8) as u8, 0xA5u8);
+                b.set_pixel(x, y, 0xFFu8, (x * 2) as u8, (y * 3) as u8);
+            }
+        }
+        FrameBuffer::crossfade(&a, &b, 0, &mut out);
+        assert_eq!(out, a);
+        FrameBuffer::crossfade(&a, &b, 255, &mut out);
+        assert_eq!(out, b);
+
+        // Halfway between, each component is a weighted average.
+        FrameBuffer::crossfade(&a, &b, 128, &mut out);
+        // (40, 24, 165) fading into (255, 20, 9)
+        assert_eq!(out.pixel_components(10, 3), [0x94, 0x16, 0x57]);
+    }
+
+    #[test]
+    fn address_transition_segments() {
+        let mut fb = declare_frame_buffer!(4, 4, 3, 1, 2);
+        fb.set_control_bits(1);
+        let colors: Vec<(usize, usize, Vec<u16>)> = fb
+            .buffer_iter_meta()
+            .map(|(plane, scanline, segment)| (plane, scanline, segment.to_vec()))
+            .collect();
+        fb.set_address_transition(true);
+        assert_eq!(fb.emitted_segments(), 14 + 2);
+        assert_eq!(fb.verify_addressing(), Ok(()));
+        // Every plane of a scanline has that scanline's address.
+        for scanline in 0..2 {
+            for plane in 0..3 {
+                for word in fb.plane(scanline, plane).unwrap() {
+                    assert_eq!(word.address() as usize, scanline);
+                }
+            }
+        }
+        let segments: Vec<&[u16]> = fb.buffer_iter().collect();
+        assert_eq!(segments.len(), 16);
+        // A blank segment is sent before the first plane of each scanline.
+        for (index, scanline) in [(0, 0), (2, 1)] {
+            assert_eq!(segments[index].len(), GHOST_BLANKING_WORDS);
+            for word in segments[index] {
+                assert!(word.output_enable());
+                assert!(!word.latch());
+                assert_eq!(word.address() as usize, scanline);
+                assert_eq!(word & 0x3F, 0);
+            }
+        }
+        let sent_colors = [1, 3].into_iter().chain(4..16).map(|index| segments[index]);
+        for ((plane, scanline, original), sent) in colors.iter().zip(sent_colors) {
+            assert_eq!(sent.len(), original.len());
+            for (word, original) in sent.iter().zip(original) {
+                assert_eq!(word.address() as usize, *scanline, "plane {plane}");
+                assert_eq!(word & !0x1F00, original & !0x1F00);
+            }
+        }
+
+        // The control bits keep the first plane's address when they're rewritten.
+        fb.set_control_bits(1);
+        assert_eq!(fb.plane(1, 0).unwrap()[0].address(), 1);
+        fb.set_address_transition(false);
+        assert_eq!(fb.plane(1, 0).unwrap()[0].address(), 0);
+        assert_eq!(fb.buffer_iter().count(), 14);
+    }
+
+    #[test]
+    fn address_transition_with_ghost_reduction() {
+        let mut fb = declare_frame_buffer!(4, 4, 3, 1, 2);
+        fb.set_control_bits(1);
+        fb.set_address_transition(true);
+        fb.set_ghost_reduction(true);
+        let segments: Vec<&[u16]> = fb.buffer_iter().collect();
+        assert_eq!(segments.len(), 2 + 2 * 14);
+        // Every color segment comes straight after a blank segment already at its address.
+        for (index, segment) in segments.iter().enumerate() {
+            if segment.len() == GHOST_BLANKING_WORDS {
+                continue;
+            }
+            let previous = segments[(index + segments.len() - 1) % segments.len()];
+            assert_eq!(previous.len(), GHOST_BLANKING_WORDS);
+            for word in previous {
+                assert!(word.output_enable());
+                assert_eq!(word.address(), segment[0].address());
+            }
+        }
+    }
+
+    #[test]
+    fn filled() {
+        let config = MatrixConfig::<64, 32, 1, 8, 16>::default().with_column_offset(3);
+        let fb = FrameBuffer::<64, 32, 1, 8, 16, 64, 16>::filled(0x12u8, 0xEF, 0x80, &config, 200);
+        for y in 0..32 {
+            for x in 0..64 {
+                assert_eq!(fb.pixel_components(x, y), [0x12, 0xEF, 0x80], "({x}, {y})");
+            }
+        }
+        assert!(fb.is_configured());
+        assert_eq!(config.validate_buffer(&fb), Ok(()));
+
+        let mut drawn = declare_frame_buffer!(64, 32, 8, 1, 16);
+        drawn.set_column_offset(3);
+        drawn.configure(2, &config.lit_columns(200));
+        for y in 0..32 {
+            for x in 0..64 {
+                drawn.set_pixel(x, y, 0x12u8, 0xEF, 0x80);
+            }
+        }
+        assert_eq!(fb, drawn);
+    }
+
+    #[test]
+    fn channel_duty() {
+        let config = MatrixConfig::<64, 32, 1, 8, 16>::default();
+        let mut fb =
+            FrameBuffer::<64, 32, 1, 8, 16, 64, 16>::filled(0xFFu8, 0xFF, 0xFF, &config, 255);
+        let (red, green, blue) = fb.channel_duty(5, 20);
+        assert_eq!(red, green);
+        assert_eq!(green, blue);
+        // 59 of the 64 columns are lit, and each pixel is on one of 16 scanlines.
+        assert!((red - 59.0 / 64.0 / 16.0).abs() < 1e-6, "{red}");
+
+        // Only the most significant plane, which is 128/255 of the frame.
+        fb.set_pixel(5, 20, 0x80u8, 0x01, 0x00);
+        let (red_half, green_lsb, blue_off) = fb.channel_duty(5, 20);
+        assert!((red_half - red * 128.0 / 255.0).abs() < 1e-6, "{red_half}");
+        assert!((green_lsb - red / 255.0).abs() < 1e-6, "{green_lsb}");
+        assert_eq!(blue_off, 0.0);
+    }
+
+    #[test]
+    fn lazy_control_bits() {
+        let config = MatrixConfig::<64, 32, 1, 8, 16>::default();
+        let mut eager = declare_frame_buffer!(64, 32, 8, 1, 16);
+        eager.configure(2, &config.lit_columns(100));
+        let mut fb = declare_frame_buffer!(64, 32, 8, 1, 16);
+        fb.set_lazy_control_bits(true);
+        fb.configure(2, &config.lit_columns(100));
+        assert_eq!(fb.plane(3, 0), None);
+
+        // Drawing on row 3 writes the control bits for scanline 3, and no others.
+        fb.set_pixel(10, 3, 0xFFu8, 0, 0);
+        eager.set_pixel(10, 3, 0xFFu8, 0, 0);
+        for plane in 0..8 {
+            assert_eq!(fb.plane(3, plane), eager.plane(3, plane));
+        }
+        assert_eq!(fb.plane(4, 1), None);
+
+        // Everything that reads the buffer sees the deferred control bits.
+        assert_eq!(fb, eager);
+        assert!(!fb.is_blank());
+        assert_eq!(fb.verify_addressing(), eager.verify_addressing());
+        assert_eq!(fb.inferred_config(), eager.inferred_config());
+        assert_eq!(fb.channel_duty(10, 3), eager.channel_duty(10, 3));
+        assert_eq!(fb.channel_duty(10, 4), eager.channel_duty(10, 4));
+        assert_eq!(config.validate_buffer(&fb), config.validate_buffer(&eager));
+
+        // Starting a transfer writes the rest.
+        let mut recording = [0u16; 64 * 255 * 16];
+        let dma = crate::dma::null::NullDma::new(&mut recording);
+        let transfer = unsafe { crate::dma::MatrixDma::start_reference(dma, &mut fb) }.unwrap();
+        let (_, fb) = transfer.stop().unwrap();
+        for scanline in 0..16 {
+            for plane in 0..8 {
+                assert_eq!(fb.plane(scanline, plane), eager.plane(scanline, plane));
+            }
+        }
+    }
+
+    #[test]
+    fn ghost_reduction_segments() {
+        let mut fb = declare_frame_buffer!(4, 4, 3, 1, 2);
+        fb.set_control_bits(1);
+        let plain = fb.clone();
+        let colors: Vec<&[u16]> = plain.buffer_iter().collect();
+        fb.set_ghost_reduction(true);
+        assert_eq!(fb.emitted_segments(), 2 * 14);
+        let segments: Vec<&[u16]> = fb.buffer_iter().collect();
+        assert_eq!(segments.len(), colors.len() + 14);
+        for (index, pair) in segments.chunks(2).enumerate() {
+            assert_eq!(pair[0], colors[index]);
+            // Blank, and already at the address of the next segment.
+            let next_address = colors[(index + 1) % colors.len()][0].address();
+            assert_eq!(pair[1].len(), GHOST_BLANKING_WORDS);
+            for word in pair[1] {
+                assert!(word.output_enable());
+                assert!(!word.latch());
+                assert_eq!(word.address(), next_address);
+                assert_eq!(word & 0x3F, 0);
+            }
+        }
+        fb.set_ghost_reduction(false);
+        assert_eq!(fb.buffer_iter().count(), 14);
+    }
+
+    #[test]
+    fn lsb_half_duty_halves_lit_words() {
+        let lit_words = |fb: &FrameBuffer<64, 32, 1, 8, 16, 64, 16>, plane: usize| {
+            fb.plane(0, plane)
+                .unwrap()
+                .iter()
+                .filter(|word| !word.output_enable())
+                .count()
+        };
+        let mut config = MatrixConfig::<64, 32, 1, 8, 16>::new(2);
+        let mut fb = declare_frame_buffer!(64, 32, 8, 1, 16);
+        fb.configure(2, &config.lit_columns(255));
+        let full_duty = lit_words(&fb, 0);
+        assert_eq!(full_duty, lit_words(&fb, 1));
+
+        config.set_lsb_half_duty(true);
+        fb.set_brightness_bits(&config.lit_columns(255));
+        assert_eq!(lit_words(&fb, 0), full_duty / 2);
+        assert_eq!(lit_words(&fb, 1), full_duty);
+    }
+
+    #[test]
+    fn panel_macro() {
+        // The type annotations fail to compile if the macros produce different types.
+        let fb: panel!(width: 64, height: 32, color_depth: 8, chain_length: 2, per_frame_denominator: 8) =
+            declare_frame_buffer!(64, 32, 8, 2, 8);
+        assert_eq!(fb.words_per_plane(), 64 * 2 * 32 / 8 / 2);
+        assert_eq!(fb.scanlines_per_frame(), 8);
+        let fb: panel!(width: 64, height: 32, color_depth: 8) = declare_frame_buffer!(64, 32, 8);
+        assert_eq!(fb.chain_length(), 1);
+        assert_eq!(fb.per_frame_denominator(), 16);
+        let fb: panel!(width: 32, height: 16, color_depth: 4, chain_length: 3,) =
+            declare_frame_buffer!(32, 16, 4, 3);
+        assert_eq!(fb.words_per_plane(), 32 * 3 * 16 / 16 / 2);
+    }
+
+    #[test]
+    fn checked_words_per_plane() {
+        assert_eq!(__checked_words_per_plane(64, 32, 8, 1, 16), 64);
+        assert_eq!(__checked_words_per_plane(64, 64, 8, 2, 32), 128);
+    }
+
+    #[test]
+    #[should_panic(expected = "per_frame_denominator must be between 1 and the height")]
+    fn checked_words_per_plane_transposed() {
+        __checked_words_per_plane(64, 16, 8, 1, 32);
+    }
+
+    #[test]
+    #[should_panic(expected = "per_frame_denominator must divide the height evenly")]
+    fn checked_words_per_plane_uneven() {
+        __checked_words_per_plane(64, 32, 8, 1, 12);
+    }
+
+    #[test]
+    fn control_words_match_set_control_bits() {
+        const CONTROL: [u16; 64 * 8 * 16] = control_words::<64, 8, 16, { 64 * 8 * 16 }>(2);
+        let mut fb = declare_frame_buffer!(64, 32, 8, 1, 16);
+        fb.set_control_bits(2);
+        let runtime = (0..16).flat_map(|scanline| {
+            let fb = &fb;
+            (0..8).flat_map(move |plane| fb.plane(scanline, plane).unwrap().iter().copied())
+        });
+        assert!(CONTROL.iter().copied().eq(runtime));
+
+        // Both the precomputed rows and blanking counts past them.
+        for blanking in [0, LATCH_BLANKING_COUNT_MAX, LATCH_BLANKING_COUNT_MAX + 2] {
+            let control = control_words::<64, 8, 16, { 64 * 8 * 16 }>(blanking);
+            fb.set_control_bits(blanking);
+            let runtime = (0..16).flat_map(|scanline| {
+                let fb = &fb;
+                (0..8).flat_map(move |plane| fb.plane(scanline, plane).unwrap().iter().copied())
+            });
+            assert!(control.iter().copied().eq(runtime), "{blanking}");
+        }
+    }
+
+    #[test]
+    fn set_control_bits_keeps_colors() {
+        let mut fb = declare_frame_buffer!(64, 32, 8, 1, 16);
+        fb.set_pixel(3, 20, 0xFFu8, 0, 0xFF);
+        fb.set_control_bits(1);
+        let location = fb.locate(3, 20);
+        let word = fb.plane(location.scanline, 7).unwrap()[location.word_index];
+        assert!(word.red(location.lane));
+        assert!(!word.green(location.lane));
+        assert!(word.blue(location.lane));
+        assert_eq!(word.address(), location.scanline as u8);
+    }
+
+    #[test]
+    fn inferred_config() {
+        let fb = declare_frame_buffer!(64, 32, 8, 1, 16);
+        assert_eq!(fb.inferred_config(), None);
+
+        let mut fb = declare_frame_buffer!(64, 32, 8, 1, 16);
+        for (blanking, brightness) in [(1, 255), (2, 100), (4, 37), (0, 10), (3, 200)] {
+            let config = MatrixConfig::<64, 32, 1, 8, 16>::new(blanking);
+            fb.configure(blanking, &config.lit_columns(brightness));
+            let inferred = fb.inferred_config().unwrap();
+            assert_eq!(inferred.latch_blanking_count, Some(blanking));
+            // Each column is worth a little over 4 brightness steps.
+            assert!(
+                inferred.brightness_estimate.abs_diff(brightness) <= 5,
+                "{brightness} inferred as {}",
+                inferred.brightness_estimate
+            );
+        }
+
+        let config = MatrixConfig::<64, 32, 1, 8, 16>::new(2);
+        fb.configure(2, &config.lit_columns(0));
+        assert_eq!(
+            fb.inferred_config(),
+            Some(InferredConfig {
+                latch_blanking_count: None,
+                brightness_estimate: 0,
+            })
+        );
+    }
+
+    #[test]
+    fn verify_addressing_configured() {
+        let mut fb = declare_frame_buffer!(64, 32, 8, 1, 16);
+        fb.set_control_bits(2);
+        assert_eq!(fb.verify_addressing(), Ok(()));
+        let mut fb = declare_frame_buffer!(64, 64, 8, 1, 32);
+        fb.set_control_bits(2);
+        assert_eq!(fb.verify_addressing(), Ok(()));
+    }
+
+    #[test]
+    fn verify_addressing_broken() {
+        let mut fb = declare_frame_buffer!(64, 32, 8, 1, 16);
+        fb.set_control_bits(2);
+        // Show scanline 5 at the same address as scanline 4, skipping address 5.
+        for word in fb.plane_mut(5, 0).unwrap() {
+            word.set_address(3);
+        }
+        assert_eq!(
+            fb.verify_addressing(),
+            Err(AddressingError::Duplicate { address: 4 })
+        );
+
+        let mut fb = declare_frame_buffer!(64, 32, 8, 1, 16);
+        fb.set_control_bits(2);
+        fb.plane_mut(9, 0).unwrap()[20].set_address(0);
+        assert_eq!(
+            fb.verify_addressing(),
+            Err(AddressingError::Inconsistent { scanline: 9 })
+        );
+
+        // An unconfigured buffer shows every scanline at address 1.
+        let fb = declare_frame_buffer!(64, 32, 8, 1, 16);
+        assert_eq!(
+            fb.verify_addressing(),
+            Err(AddressingError::Duplicate { address: 1 })
+        );
+    }
 }
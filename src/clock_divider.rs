@@ -1,3 +1,6 @@
+/// The settings of a fractional clock divider, dividing by `div_num + div_b / div_a`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct ClockDivider {
     // Integral LCD clock divider value. (8 bits)
     // Value 0 is treated as 256
@@ -12,14 +15,58 @@ pub struct ClockDivider {
     pub div_a: usize,
 }
 
+impl ClockDivider {
+    /// Describe the divider settings, and the frequency they give from `source_frequency`.
+    ///
+    /// The description is formatted like `div_num 3 + div_b 1 / div_a 2: 160000000Hz ->
+    /// 45714285Hz`, for logging which divider was chosen.
+    pub fn describe(&self, source_frequency: usize) -> DividerDescription {
+        DividerDescription {
+            divider: *self,
+            source_frequency,
+        }
+    }
+}
+
+/// A `ClockDivider` and source frequency that can be displayed, from `ClockDivider::describe()`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DividerDescription {
+    divider: ClockDivider,
+
+    source_frequency: usize,
+}
+
+impl core::fmt::Display for DividerDescription {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "div_num {} + div_b {} / div_a {}: {}Hz -> {}Hz",
+            self.divider.div_num,
+            self.divider.div_b,
+            self.divider.div_a,
+            self.source_frequency,
+            calculate_output_frequency(self.source_frequency, &self.divider)
+        )
+    }
+}
+
+/// Find the source and divider that come closest to `desired_frequency` without going over.
+///
+/// If `only_source` is given, only that index of `source_frequencies` is considered, otherwise the
+/// source giving the fastest output is chosen. Returns the index of the chosen source and its
+/// divider.
 pub fn calculate_clkm(
     desired_frequency: usize,
     source_frequencies: &[usize],
+    only_source: Option<usize>,
 ) -> (usize, ClockDivider) {
     let mut result_freq = 0;
     let mut result = None;
 
     for (i, &source_frequency) in source_frequencies.iter().enumerate() {
+        if only_source.is_some_and(|only_source| only_source != i) {
+            continue;
+        }
         let div = calculate_closest_divider(source_frequency, desired_frequency);
         if let Some(div) = div {
             let freq = calculate_output_frequency(source_frequency, &div);
@@ -33,7 +80,7 @@ pub fn calculate_clkm(
     result.expect("Desired frequency was too low for the dividers to divide to")
 }
 
-fn calculate_output_frequency(source_frequency: usize, divider: &ClockDivider) -> usize {
+pub fn calculate_output_frequency(source_frequency: usize, divider: &ClockDivider) -> usize {
     let n = match divider.div_num {
         0 => 256,
         1 => 2,
@@ -49,8 +96,8 @@ fn calculate_output_frequency(source_frequency: usize, divider: &ClockDivider) -
 
         let source = source_frequency as u64;
         let n = n as u64;
-        let a = divider.div_b as u64;
-        let b = divider.div_a as u64;
+        let a = divider.div_a as u64;
+        let b = divider.div_b as u64;
 
         ((source * a) / (n * a + b)) as _
     } else {
@@ -148,3 +195,60 @@ fn farey_sequence(denominator: usize) -> impl Iterator<Item = Fraction> {
         Some(next)
     })
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Test cases are using std
+    extern crate std;
+    use std::format;
+
+    #[test]
+    fn describe_divider() {
+        let divider = ClockDivider {
+            div_num: 3,
+            div_b: 1,
+            div_a: 2,
+        };
+        assert_eq!(
+            format!("{}", divider.describe(160_000_000)),
+            "div_num 3 + div_b 1 / div_a 2: 160000000Hz -> 45714285Hz"
+        );
+        let divider = ClockDivider {
+            div_num: 0,
+            div_b: 0,
+            div_a: 0,
+        };
+        assert_eq!(
+            format!("{}", divider.describe(256_000_000)),
+            "div_num 0 + div_b 0 / div_a 0: 256000000Hz -> 1000000Hz"
+        );
+    }
+
+    #[test]
+    fn calculate_clkm_picks_fastest() {
+        let (source, divider) = calculate_clkm(20_000_000, &[40_000_000, 160_000_000], None);
+        assert_eq!(source, 0);
+        assert_eq!(calculate_output_frequency(40_000_000, &divider), 20_000_000);
+    }
+
+    #[test]
+    fn calculate_clkm_pinned_source() {
+        let sources = [40_000_000, 240_000_000, 160_000_000];
+        let (source, divider) = calculate_clkm(30_000_000, &sources, None);
+        assert_eq!(source, 1);
+        assert_eq!(
+            calculate_output_frequency(240_000_000, &divider),
+            30_000_000
+        );
+        // The crystal can only be divided by 2 at the least, so it falls short of the others.
+        let (source, divider) = calculate_clkm(30_000_000, &sources, Some(0));
+        assert_eq!(source, 0);
+        assert_eq!(
+            divider,
+            calculate_closest_divider(40_000_000, 30_000_000).unwrap()
+        );
+        assert_eq!(calculate_output_frequency(40_000_000, &divider), 20_000_000);
+    }
+}
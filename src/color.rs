@@ -2,9 +2,55 @@ use embedded_graphics_core::pixelcolor::{Rgb555, Rgb666, Rgb888, RgbColor};
 
 use super::buffer::ColorStorage;
 
+/// A color that can be shown on the matrix, with `DEPTH` bits per component.
+///
+/// The storage type has to be able to hold `DEPTH` bits, so pairing a depth with a storage type
+/// that is too narrow fails to compile:
+///
+/// ```compile_fail,E0277
+/// use hub75_bcm::color::Color;
+///
+/// #[derive(Clone, Copy)]
+/// struct Rgb12 {
+///     red: u8,
+///     green: u8,
+///     blue: u8,
+/// }
+///
+/// impl Color<12> for Rgb12 {
+///     type Storage = u8;
+///
+///     fn new<R: AsRef<u8>, G: AsRef<u8>, B: AsRef<u8>>(red: R, green: G, blue: B) -> Self {
+///         Self {
+///             red: *red.as_ref(),
+///             green: *green.as_ref(),
+///             blue: *blue.as_ref(),
+///         }
+///     }
+///
+///     fn red(&self) -> u8 {
+///         self.red
+///     }
+///
+///     fn green(&self) -> u8 {
+///         self.green
+///     }
+///
+///     fn blue(&self) -> u8 {
+///         self.blue
+///     }
+/// }
+/// ```
 pub trait Color<const DEPTH: usize> {
     type Storage: ColorStorage<DEPTH>;
 
+    /// How many bits the components are stored with, when that's more than `DEPTH`.
+    ///
+    /// Components are scaled down to `DEPTH` bits (with rounding) before they are shown, so a
+    /// color with 8 bit components can be shown at a color depth of 5 by setting this to 8. The
+    /// default is `DEPTH`, which uses the components as they are.
+    const NATIVE_DEPTH: usize = DEPTH;
+
     fn new<R: AsRef<Self::Storage>, G: AsRef<Self::Storage>, B: AsRef<Self::Storage>>(
         red: R,
         green: G,
@@ -49,3 +95,234 @@ macro_rules! impl_pixel_color {
 impl_pixel_color!(Rgb555, 5, u8);
 impl_pixel_color!(Rgb666, 6, u8);
 impl_pixel_color!(Rgb888, 8, u8);
+
+/// The components of a color scaled down to `DEPTH` bits, as they are packed into a frame buffer.
+pub(crate) fn depth_components<C, const DEPTH: usize>(
+    color: &C,
+) -> (C::Storage, C::Storage, C::Storage)
+where
+    C: Color<DEPTH>,
+{
+    let scale = |value: C::Storage| {
+        if C::NATIVE_DEPTH <= DEPTH {
+            return value;
+        }
+        let shift = C::NATIVE_DEPTH - DEPTH;
+        let max = (1u32 << DEPTH) - 1;
+        let rounded = (value.to_u16() as u32 + (1 << (shift - 1))) >> shift;
+        C::Storage::from_u16(rounded.min(max) as u16)
+    };
+    (
+        scale(color.red()),
+        scale(color.green()),
+        scale(color.blue()),
+    )
+}
+
+/// Convert a color to a different color depth, scaling each component with rounding.
+///
+/// This is the same scaling `embedded-graphics` uses between its own color types, but works for any
+/// pair of `Color` types. For example, assets loaded as `Rgb888` can be converted for a matrix with
+/// a color depth of 5 with `let color: Rgb555 = convert_to_depth(asset_color);`.
+pub fn convert_to_depth<T, const DEPTH: usize, F, const FROM_DEPTH: usize>(color: F) -> T
+where
+    T: Color<DEPTH>,
+    F: Color<FROM_DEPTH>,
+{
+    // Components are scaled from the depth they're shown at to the depth the new color stores.
+    let scale = |value: F::Storage| {
+        let from_max = (1u32 << FROM_DEPTH) - 1;
+        let to_max = (1u32 << T::NATIVE_DEPTH) - 1;
+        let scaled = (value.to_u16() as u32 * to_max + from_max / 2) / from_max;
+        Component(T::Storage::from_u16(scaled as u16))
+    };
+    let (red, green, blue) = depth_components(&color);
+    T::new(scale(red), scale(green), scale(blue))
+}
+
+// Lets a bare component be passed to `Color::new`.
+struct Component<S>(S);
+
+impl<S> AsRef<S> for Component<S> {
+    fn as_ref(&self) -> &S {
+        &self.0
+    }
+}
+
+/// A gamma correction curve, as a table of 256 evenly spaced points.
+///
+/// Entry `n` is the corrected value (from 0 to 65535) for an input of `n / 255`. Components with
+/// other color depths are scaled to and from the table.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GammaTable {
+    table: [u16; 256],
+}
+
+impl GammaTable {
+    pub const fn new(table: [u16; 256]) -> Self {
+        Self { table }
+    }
+
+    /// A gamma of 2, which is close to how LEDs are perceived.
+    pub const fn squared() -> Self {
+        let mut table = [0u16; 256];
+        let mut index = 0;
+        while index < table.len() {
+            table[index] = (index * index * u16::MAX as usize / (255 * 255)) as u16;
+            index += 1;
+        }
+        Self { table }
+    }
+
+    /// Apply the curve to a single color component.
+    pub fn correct<CS: ColorStorage<DEPTH>, const DEPTH: usize>(&self, value: CS) -> CS {
+        let max = (1u32 << DEPTH) - 1;
+        let index = (value.to_u16() as u32).min(max) * 255 / max;
+        let corrected = self.table[index as usize] as u32 * max / u16::MAX as u32;
+        CS::from_u16(corrected as u16)
+    }
+}
+
+/// Blend `foreground` over `background`, with `coverage` being how much of the foreground is used
+/// (0 is entirely the background, 255 is entirely the foreground).
+///
+/// The blending is done in (approximately) linear light, using a gamma of 2, so that partially
+/// covered pixels (such as the edges of antialiased text) don't look darker than they should.
+pub fn blend<C>(background: C, foreground: C, coverage: u8) -> C
+where
+    C: RgbColor + From<Rgb888> + Into<Rgb888>,
+{
+    let background: Rgb888 = background.into();
+    let foreground: Rgb888 = foreground.into();
+    let blend_component = |background: u8, foreground: u8| -> u8 {
+        let coverage = coverage as u32;
+        let background = background as u32 * background as u32;
+        let foreground = foreground as u32 * foreground as u32;
+        let linear = (background * (255 - coverage) + foreground * coverage) / 255;
+        isqrt(linear) as u8
+    };
+    Rgb888::new(
+        blend_component(background.r(), foreground.r()),
+        blend_component(background.g(), foreground.g()),
+        blend_component(background.b(), foreground.b()),
+    )
+    .into()
+}
+
+/// Add two colors together, with each component saturating at its maximum value.
+///
+/// The components are added as they are encoded, which is what additive effects such as glows and
+/// particle trails expect.
+pub fn saturating_add<C>(first: C, second: C) -> C
+where
+    C: RgbColor + From<Rgb888> + Into<Rgb888>,
+{
+    let first: Rgb888 = first.into();
+    let second: Rgb888 = second.into();
+    Rgb888::new(
+        first.r().saturating_add(second.r()),
+        first.g().saturating_add(second.g()),
+        first.b().saturating_add(second.b()),
+    )
+    .into()
+}
+
+// https://en.wikipedia.org/wiki/Integer_square_root#Algorithm_using_Newton's_method
+fn isqrt(value: u32) -> u32 {
+    if value < 2 {
+        return value;
+    }
+    let mut current = value;
+    let mut next = (current + value / current) / 2;
+    while next < current {
+        current = next;
+        next = (current + value / current) / 2;
+    }
+    current
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn isqrt_values() {
+        for value in 0u32..=(255 * 255) {
+            let root = isqrt(value);
+            assert!(root * root <= value, "{} is too large for {}", root, value);
+            assert!(
+                (root + 1) * (root + 1) > value,
+                "{} is too small for {}",
+                root,
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn blend_endpoints() {
+        let background = Rgb888::new(255, 0, 0);
+        let foreground = Rgb888::new(10, 128, 200);
+        assert_eq!(blend(background, foreground, 0), background);
+        assert_eq!(blend(background, foreground, 255), foreground);
+    }
+
+    #[test]
+    fn blend_gamma() {
+        let background = Rgb888::new(255, 0, 0);
+        let foreground = Rgb888::new(128, 128, 128);
+        // Linear light blending is brighter than blending the encoded values directly.
+        assert_eq!(blend(background, foreground, 128), Rgb888::new(201, 90, 90));
+    }
+
+    #[test]
+    fn saturating_add_components() {
+        assert_eq!(
+            saturating_add(Rgb888::new(10, 200, 0), Rgb888::new(20, 100, 0)),
+            Rgb888::new(30, 255, 0)
+        );
+    }
+
+    #[test]
+    fn convert_888_to_555() {
+        let convert = |value: u8| -> Rgb555 { convert_to_depth(Rgb888::new(value, 0, value)) };
+        assert_eq!(convert(0), Rgb555::new(0, 0, 0));
+        assert_eq!(convert(4), Rgb555::new(0, 0, 0));
+        assert_eq!(convert(5), Rgb555::new(1, 0, 1));
+        assert_eq!(convert(127), Rgb555::new(15, 0, 15));
+        assert_eq!(convert(128), Rgb555::new(16, 0, 16));
+        assert_eq!(convert(255), Rgb555::new(31, 0, 31));
+        for value in 0..=255 {
+            assert_eq!(convert(value), Rgb555::from(Rgb888::new(value, 0, value)));
+        }
+    }
+
+    #[test]
+    fn convert_888_to_666() {
+        let convert = |value: u8| -> Rgb666 { convert_to_depth(Rgb888::new(0, value, 0)) };
+        assert_eq!(convert(0), Rgb666::new(0, 0, 0));
+        assert_eq!(convert(2), Rgb666::new(0, 0, 0));
+        assert_eq!(convert(3), Rgb666::new(0, 1, 0));
+        assert_eq!(convert(127), Rgb666::new(0, 31, 0));
+        assert_eq!(convert(128), Rgb666::new(0, 32, 0));
+        assert_eq!(convert(255), Rgb666::new(0, 63, 0));
+        for value in 0..=255 {
+            assert_eq!(convert(value), Rgb666::from(Rgb888::new(0, value, 0)));
+        }
+        // And back up again.
+        let up: Rgb888 = convert_to_depth(Rgb666::new(63, 32, 1));
+        assert_eq!(up, Rgb888::new(255, 130, 4));
+    }
+
+    #[test]
+    fn gamma_squared() {
+        let gamma = GammaTable::squared();
+        assert_eq!(gamma.correct::<u8, 8>(0), 0);
+        assert_eq!(gamma.correct::<u8, 8>(255), 255);
+        assert_eq!(gamma.correct::<u8, 8>(128), 64);
+        // Other depths are scaled to and from the table.
+        assert_eq!(gamma.correct::<u8, 4>(15), 15);
+        assert_eq!(gamma.correct::<u8, 4>(8), 4);
+        assert_eq!(gamma.correct::<u16, 12>(4095), 4095);
+    }
+}
@@ -1,6 +1,159 @@
-use crate::util::Sealed;
+use fugit::HertzU32;
 
+use crate::buffer::{
+    BcmOrder, FrameBuffer, LaneMapping, LaneOrder, LitColumns, GHOST_BLANKING_WORDS,
+};
 use crate::const_not_zero;
+use crate::matrix_word::MatrixWord;
+
+/// The dummy cycles sent by the LCD peripheral before each frame.
+pub(crate) const LCD_DUMMY_CYCLES: u8 = 2;
+
+/// The largest latch blanking count that frame buffers have precomputed control bits for.
+pub(crate) const LATCH_BLANKING_COUNT_MAX: u8 = 4;
+
+/// The ways a frame buffer can disagree with a `MatrixConfig`, from `MatrixConfig::validate_buffer()`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Mismatch {
+    /// The frame buffer has not been configured yet.
+    NotConfigured,
+
+    /// The address bits do not match the scanlines.
+    Address,
+
+    /// The latch bit is not only set on the last column of each plane.
+    Latch,
+
+    /// The output enable bits do not match the latch blanking count.
+    Blanking,
+}
+
+/// The refresh rate a configuration can achieve, from `MatrixConfig::check_refresh()`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RefreshVerdict {
+    /// The number of full frames sent each second.
+    pub hz: u32,
+
+    /// If the refresh rate is high enough to avoid visible flicker.
+    pub ok: bool,
+}
+
+/// The order the two bytes of each 16-bit word are sent out on the data pins.
+///
+/// The built-in `MatrixWord` layout keeps the color, latch, and output enable bits in the low
+/// byte and the address bits in the high byte, matching data pins 0-7 and 8-15 respectively. That
+/// layout (on a little-endian chip like the ESP32-S3) needs `LittleEndian`. A layout, or a board,
+/// where the low byte of each word drives data pins 8-15 needs `BigEndian`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ByteOrder {
+    /// The low byte of each word is sent on data pins 0-7.
+    #[default]
+    LittleEndian,
+
+    /// The bytes of each word are swapped, so the low byte is sent on data pins 8-15.
+    BigEndian,
+}
+
+/// A source clock for the LCD peripheral's clock divider.
+///
+/// The discriminants are the indices of each clock in the source frequencies given to
+/// `clock_divider::calculate_clkm()`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ClockSource {
+    /// The crystal clock.
+    Xtal = 0,
+
+    /// The CPU clock.
+    Cpu = 1,
+
+    /// The crypto/PWM clock.
+    CryptoPwm = 2,
+}
+
+/// How far drawn content is rotated clockwise on the panels.
+///
+/// `Rot90` and `Rot270` swap the width and height seen by `RgbMatrix::set_pixel()` and
+/// `DrawTarget`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Rotation {
+    #[default]
+    Rot0,
+
+    Rot90,
+
+    Rot180,
+
+    Rot270,
+}
+
+impl Rotation {
+    /// The width and height of an area of `width` by `height` pixels after rotation.
+    pub(crate) const fn logical_size(self, width: usize, height: usize) -> (usize, usize) {
+        match self {
+            Rotation::Rot0 | Rotation::Rot180 => (width, height),
+            Rotation::Rot90 | Rotation::Rot270 => (height, width),
+        }
+    }
+
+    /// Map rotated coordinates to coordinates in an unrotated area of `width` by `height` pixels,
+    /// or `None` if they're outside the rotated area.
+    pub(crate) const fn to_physical(
+        self,
+        width: usize,
+        height: usize,
+        x: usize,
+        y: usize,
+    ) -> Option<(usize, usize)> {
+        let (logical_width, logical_height) = self.logical_size(width, height);
+        if x >= logical_width || y >= logical_height {
+            return None;
+        }
+        Some(match self {
+            Rotation::Rot0 => (x, y),
+            Rotation::Rot90 => (width - 1 - y, x),
+            Rotation::Rot180 => (width - 1 - x, height - 1 - y),
+            Rotation::Rot270 => (y, height - 1 - x),
+        })
+    }
+
+    /// Map the coordinates of a pixel on the chain of panels back to rotated coordinates, the
+    /// reverse of `to_physical`.
+    pub(crate) const fn to_logical(
+        self,
+        width: usize,
+        height: usize,
+        x: usize,
+        y: usize,
+    ) -> Option<(usize, usize)> {
+        if x >= width || y >= height {
+            return None;
+        }
+        Some(match self {
+            Rotation::Rot0 => (x, y),
+            Rotation::Rot90 => (y, width - 1 - x),
+            Rotation::Rot180 => (width - 1 - x, height - 1 - y),
+            Rotation::Rot270 => (height - 1 - y, x),
+        })
+    }
+}
+
+/// How a brightness value is mapped to the number of columns with output enabled.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BrightnessCurve {
+    /// The lit columns are proportional to the brightness.
+    #[default]
+    Linear,
+
+    /// The lit columns are proportional to the square of the brightness, so that equal steps in
+    /// brightness look like roughly equal steps in lightness.
+    Perceptual,
+}
 
 /// `PER_FRAME_DENOMINATOR` is the portion of the panel written to at once.
 ///
@@ -9,7 +162,20 @@ use crate::const_not_zero;
 /// For example, if you have a 32 pixel high 1/8 (or 1:8) panel, 4 rows (32 / 8) will be drawn
 /// to at a time. If you have a 32 pixel high 1/16 (or 1:16) panel, 2 rows (32 / 16) will
 /// be drawn to for each scanline
+///
+/// Every setting has a `set_*` method and a `with_*` method for chaining from the defaults, so
+/// only the settings that differ need to be given:
+///
+/// ```
+/// use hub75_bcm::config::MatrixConfig;
+///
+/// let config = MatrixConfig::<64, 32, 1, 8, 16>::default()
+///     .with_latch_blanking_count(3)
+///     .with_min_brightness(8);
+/// assert_eq!(config.latch_blanking_count(), 3);
+/// ```
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct MatrixConfig<
     const WIDTH: usize,
     const HEIGHT: usize,
@@ -21,6 +187,125 @@ pub struct MatrixConfig<
     ///
     /// The default value is 2, and there's a maximum value of 4.
     latch_blanking_count: u8,
+
+    /// Enable the blank region when the LCD peripheral sends data out.
+    ///
+    /// This works around a design flaw in the ESP32-S3, but inserts extra blank time which reduces
+    /// the maximum brightness. The default is enabled.
+    blank_region_workaround: bool,
+
+    /// The number of setup cycles sent once at the start of each LCD transaction (one frame),
+    /// before the first word of data. These are the VFK cycles of the LCD peripheral.
+    ///
+    /// They are not added to every word. The default value is 2, and the valid range is 1 to 64.
+    setup_cycles: u8,
+
+    /// The number of hold cycles sent once at the end of each LCD transaction (one frame), after
+    /// the last word of data. These are the VBK cycles of the LCD peripheral.
+    ///
+    /// They are not added to every word. The default value is 2, and the valid range is 1 to
+    /// 8192.
+    hold_cycles: u16,
+
+    /// Only light the least significant color plane for half as long as normal.
+    ///
+    /// This gives the dimmest colors a finer step in brightness than a single plane can. The
+    /// default is disabled.
+    lsb_half_duty: bool,
+
+    /// The lowest brightness allowed by `RgbMatrix::set_brightness_checked()`.
+    ///
+    /// The default value is 1, so that the panel can't accidentally be turned completely off.
+    min_brightness: u8,
+
+    /// The threshold for the asynchronous transmit FIFO full event.
+    ///
+    /// A higher threshold can reduce the chance of FIFO underruns when the DMA bandwidth is shared
+    /// with other peripherals, at the cost of some latency. The default value is 0, and the maximum
+    /// value is 31.
+    afifo_threshold: u8,
+
+    /// How the rows of the panel are split between the two data lanes.
+    ///
+    /// The default is `LaneMapping::Halves`, which is what most panels use.
+    lane_mapping: LaneMapping,
+
+    /// Which data lane is in the low bits of each word.
+    ///
+    /// The default is `LaneOrder::Normal`, with R1/G1/B1 in the lowest bits.
+    lane_order: LaneOrder,
+
+    /// How many columns to the right each pixel is written, wrapping around at the end of the
+    /// chain.
+    ///
+    /// Some panels shift the data by a few columns before latching it, moving the whole image
+    /// sideways. An offset of the same number of columns the other way around the chain (the
+    /// chain width minus the shift) undoes it. The default is 0.
+    column_offset: u16,
+
+    /// Send a short blank segment between every pair of segments, to stop rows ghosting onto the
+    /// next.
+    ///
+    /// This lowers the refresh rate, and needs more DMA descriptors. The default is `false`.
+    ghost_reduction: bool,
+
+    /// The order the bytes of each word are sent to the data pins.
+    ///
+    /// The default is `ByteOrder::LittleEndian`, which is what the built-in word layout needs.
+    byte_order: ByteOrder,
+
+    /// A brightness trim for each panel in the chain, with 255 being full brightness.
+    ///
+    /// Output enable is shared by every panel in the chain, so the trim is applied by scaling the
+    /// color values of each panel's pixels as they're packed. The default is full brightness for
+    /// every panel.
+    panel_brightness: [u8; CHAIN_LENGTH],
+
+    /// How drawn content is rotated on the chain of panels.
+    ///
+    /// The default is `Rotation::Rot0`.
+    rotation: Rotation,
+
+    /// The fewest columns of each scanline that have output disabled, regardless of brightness.
+    ///
+    /// Some panel driver chips overheat if output is left enabled for (almost) the entire
+    /// scanline at high brightness. The columns blanked around the latch count towards this. The
+    /// default is 0, which leaves only the latch blanking.
+    min_blanking_columns: u16,
+
+    /// How brightness values are mapped to lit columns.
+    ///
+    /// This only changes how long output is enabled, not the color values. The default is
+    /// `BrightnessCurve::Linear`.
+    brightness_curve: BrightnessCurve,
+
+    /// The only clock source the pixel clock may be divided from.
+    ///
+    /// Pinning the source (usually to the crystal, for less jitter) can leave the pixel clock
+    /// further from the requested frequency. The default is `None`, which picks whichever source
+    /// gets closest.
+    clock_source: Option<ClockSource>,
+
+    /// The number of extra columns to leave output disabled after the latch, before the lit
+    /// columns start.
+    ///
+    /// Some panels show artifacts on the top row if output is enabled too soon after latching.
+    /// Unlike `latch_blanking_count`, this only adds blanking after the latch, and the brightness
+    /// is scaled from the columns left over. The default is 0.
+    post_latch_delay: u8,
+
+    /// How many times each color plane is repeated in a frame.
+    ///
+    /// This is for checking the plane weighting against what a panel expects. The default is
+    /// `BcmOrder::Ascending`, with the most significant plane shown the longest.
+    bcm_order: BcmOrder,
+
+    /// Change the address in a blank segment before each scanline, instead of while sending the
+    /// first color plane.
+    ///
+    /// This stops some panels from ghosting the first plane onto the previous row, at the cost of
+    /// an extra segment for each scanline. The default is `false`.
+    address_transition: bool,
 }
 
 impl<
@@ -34,6 +319,25 @@ impl<
     fn default() -> Self {
         Self {
             latch_blanking_count: Self::DEFAULT_LATCH_BLANKING_COUNT,
+            blank_region_workaround: true,
+            setup_cycles: Self::DEFAULT_SETUP_CYCLES,
+            hold_cycles: Self::DEFAULT_HOLD_CYCLES,
+            lsb_half_duty: false,
+            min_brightness: Self::DEFAULT_MIN_BRIGHTNESS,
+            afifo_threshold: 0,
+            lane_mapping: LaneMapping::Halves,
+            lane_order: LaneOrder::Normal,
+            column_offset: 0,
+            ghost_reduction: false,
+            byte_order: ByteOrder::LittleEndian,
+            panel_brightness: [u8::MAX; CHAIN_LENGTH],
+            rotation: Rotation::Rot0,
+            min_blanking_columns: 0,
+            brightness_curve: BrightnessCurve::Linear,
+            clock_source: None,
+            post_latch_delay: 0,
+            bcm_order: BcmOrder::Ascending,
+            address_transition: false,
         }
     }
 }
@@ -48,7 +352,20 @@ impl<
 {
     const DEFAULT_LATCH_BLANKING_COUNT: u8 = 2;
 
-    const LATCH_BLANKING_COUNT_MAX: u8 = 4;
+    const DEFAULT_MIN_BRIGHTNESS: u8 = 1;
+
+    const DEFAULT_SETUP_CYCLES: u8 = 2;
+
+    // The register field is 6 bits, and holds the number of cycles - 1.
+    const SETUP_CYCLES_MAX: u8 = 64;
+
+    const DEFAULT_HOLD_CYCLES: u16 = 2;
+
+    // The register field is 13 bits, and holds the number of cycles - 1.
+    const HOLD_CYCLES_MAX: u16 = 8192;
+
+    // The register field is 5 bits.
+    const AFIFO_THRESHOLD_MAX: u8 = 31;
 
     const_not_zero!(WIDTH, usize);
     const_not_zero!(HEIGHT, usize);
@@ -77,21 +394,29 @@ impl<
         let pixels_per_row = Self::WIDTH * Self::CHAIN_LENGTH;
         let rows_per_scanline = Self::HEIGHT / (Self::PER_FRAME_DENOMINATOR as usize);
         // Each bit of color depth needs a separate word of storage as we're using BCD
-        let pixels_per_scanline = pixels_per_row * (Self::COLOR_DEPTH as usize) * rows_per_scanline;
+        let pixels_per_scanline = pixels_per_row * Self::COLOR_DEPTH * rows_per_scanline;
         // Each word already encodes 2 pixels
         pixels_per_scanline / 2
     };
 
+    const WORDS_PER_PLANE: usize = Self::WORDS_PER_SCANLINE / Self::COLOR_DEPTH;
+
     const SCANLINES_PER_FRAME: usize = {
         let rows_per_scanline = Self::HEIGHT / (Self::PER_FRAME_DENOMINATOR as usize);
         Self::HEIGHT / rows_per_scanline
     };
 
-    const WORDS_PER_FRAME: usize = { Self::WORDS_PER_SCANLINE * Self::SCANLINES_PER_FRAME };
+    /// The number of words sent for each frame, with each color plane repeated for its weight.
+    const STREAMED_WORDS_PER_FRAME: usize =
+        Self::WORDS_PER_PLANE * ((1 << Self::COLOR_DEPTH) - 1) * Self::SCANLINES_PER_FRAME;
+
+    /// Refresh rates below this tend to have visible flicker.
+    pub const MIN_FLICKER_FREE_REFRESH: u32 = 100;
 
     pub fn new(latch_blanking_count: u8) -> Self {
         Self {
             latch_blanking_count,
+            ..Self::default()
         }
     }
 
@@ -103,15 +428,675 @@ impl<
         self.latch_blanking_count = latch_blanking_count;
     }
 
-    pub(crate) const fn words_per_scanline(&self) -> usize {
-        Self::WORDS_PER_SCANLINE
+    pub fn with_latch_blanking_count(mut self, latch_blanking_count: u8) -> Self {
+        self.set_latch_blanking_count(latch_blanking_count);
+        self
+    }
+
+    pub fn blank_region_workaround(&self) -> bool {
+        self.blank_region_workaround
+    }
+
+    pub fn set_blank_region_workaround(&mut self, blank_region_workaround: bool) {
+        self.blank_region_workaround = blank_region_workaround;
+    }
+
+    pub fn with_blank_region_workaround(mut self, blank_region_workaround: bool) -> Self {
+        self.set_blank_region_workaround(blank_region_workaround);
+        self
+    }
+
+    pub fn setup_cycles(&self) -> u8 {
+        self.setup_cycles
+    }
+
+    /// Set the number of setup cycles sent once before each frame.
+    ///
+    /// The value is clamped to the range 1 to 64.
+    pub fn set_setup_cycles(&mut self, setup_cycles: u8) {
+        self.setup_cycles = setup_cycles.clamp(1, Self::SETUP_CYCLES_MAX);
+    }
+
+    pub fn with_setup_cycles(mut self, setup_cycles: u8) -> Self {
+        self.set_setup_cycles(setup_cycles);
+        self
+    }
+
+    pub fn hold_cycles(&self) -> u16 {
+        self.hold_cycles
+    }
+
+    /// Set the number of hold cycles sent once after each frame.
+    ///
+    /// The value is clamped to the range 1 to 8192.
+    pub fn set_hold_cycles(&mut self, hold_cycles: u16) {
+        self.hold_cycles = hold_cycles.clamp(1, Self::HOLD_CYCLES_MAX);
+    }
+
+    pub fn with_hold_cycles(mut self, hold_cycles: u16) -> Self {
+        self.set_hold_cycles(hold_cycles);
+        self
+    }
+
+    pub fn lsb_half_duty(&self) -> bool {
+        self.lsb_half_duty
+    }
+
+    pub fn set_lsb_half_duty(&mut self, lsb_half_duty: bool) {
+        self.lsb_half_duty = lsb_half_duty;
+    }
+
+    pub fn with_lsb_half_duty(mut self, lsb_half_duty: bool) -> Self {
+        self.set_lsb_half_duty(lsb_half_duty);
+        self
+    }
+
+    pub fn min_brightness(&self) -> u8 {
+        self.min_brightness
+    }
+
+    pub fn set_min_brightness(&mut self, min_brightness: u8) {
+        self.min_brightness = min_brightness;
+    }
+
+    pub fn with_min_brightness(mut self, min_brightness: u8) -> Self {
+        self.set_min_brightness(min_brightness);
+        self
+    }
+
+    pub fn afifo_threshold(&self) -> u8 {
+        self.afifo_threshold
     }
 
-    pub(crate) const fn scanlines_per_frame(&self) -> usize {
-        Self::SCANLINES_PER_FRAME
+    /// Set the asynchronous transmit FIFO threshold.
+    ///
+    /// The value is clamped to a maximum of 31.
+    pub fn set_afifo_threshold(&mut self, afifo_threshold: u8) {
+        self.afifo_threshold = afifo_threshold.min(Self::AFIFO_THRESHOLD_MAX);
+    }
+
+    pub fn with_afifo_threshold(mut self, afifo_threshold: u8) -> Self {
+        self.set_afifo_threshold(afifo_threshold);
+        self
+    }
+
+    pub fn lane_mapping(&self) -> LaneMapping {
+        self.lane_mapping
+    }
+
+    pub fn set_lane_mapping(&mut self, lane_mapping: LaneMapping) {
+        self.lane_mapping = lane_mapping;
+    }
+
+    pub fn with_lane_mapping(mut self, lane_mapping: LaneMapping) -> Self {
+        self.set_lane_mapping(lane_mapping);
+        self
+    }
+
+    pub fn lane_order(&self) -> LaneOrder {
+        self.lane_order
+    }
+
+    pub fn set_lane_order(&mut self, lane_order: LaneOrder) {
+        self.lane_order = lane_order;
+    }
+
+    pub fn with_lane_order(mut self, lane_order: LaneOrder) -> Self {
+        self.set_lane_order(lane_order);
+        self
+    }
+
+    pub fn column_offset(&self) -> u16 {
+        self.column_offset
+    }
+
+    /// The offset is reduced modulo the width of the chain.
+    pub fn set_column_offset(&mut self, column_offset: u16) {
+        self.column_offset = (column_offset as usize % (WIDTH * CHAIN_LENGTH)) as u16;
+    }
+
+    pub fn with_column_offset(mut self, column_offset: u16) -> Self {
+        self.set_column_offset(column_offset);
+        self
+    }
+
+    pub fn ghost_reduction(&self) -> bool {
+        self.ghost_reduction
+    }
+
+    pub fn set_ghost_reduction(&mut self, ghost_reduction: bool) {
+        self.ghost_reduction = ghost_reduction;
+    }
+
+    pub fn with_ghost_reduction(mut self, ghost_reduction: bool) -> Self {
+        self.set_ghost_reduction(ghost_reduction);
+        self
+    }
+
+    pub fn byte_order(&self) -> ByteOrder {
+        self.byte_order
+    }
+
+    pub fn set_byte_order(&mut self, byte_order: ByteOrder) {
+        self.byte_order = byte_order;
+    }
+
+    pub fn with_byte_order(mut self, byte_order: ByteOrder) -> Self {
+        self.set_byte_order(byte_order);
+        self
+    }
+
+    pub fn panel_brightness(&self) -> &[u8; CHAIN_LENGTH] {
+        &self.panel_brightness
+    }
+
+    pub fn set_panel_brightness(&mut self, panel_brightness: [u8; CHAIN_LENGTH]) {
+        self.panel_brightness = panel_brightness;
+    }
+
+    pub fn with_panel_brightness(mut self, panel_brightness: [u8; CHAIN_LENGTH]) -> Self {
+        self.set_panel_brightness(panel_brightness);
+        self
+    }
+
+    pub fn rotation(&self) -> Rotation {
+        self.rotation
+    }
+
+    pub fn set_rotation(&mut self, rotation: Rotation) {
+        self.rotation = rotation;
+    }
+
+    pub fn with_rotation(mut self, rotation: Rotation) -> Self {
+        self.set_rotation(rotation);
+        self
+    }
+
+    pub fn min_blanking_columns(&self) -> u16 {
+        self.min_blanking_columns
+    }
+
+    pub fn set_min_blanking_columns(&mut self, min_blanking_columns: u16) {
+        self.min_blanking_columns = min_blanking_columns;
+    }
+
+    pub fn with_min_blanking_columns(mut self, min_blanking_columns: u16) -> Self {
+        self.set_min_blanking_columns(min_blanking_columns);
+        self
+    }
+
+    pub fn brightness_curve(&self) -> BrightnessCurve {
+        self.brightness_curve
+    }
+
+    pub fn set_brightness_curve(&mut self, brightness_curve: BrightnessCurve) {
+        self.brightness_curve = brightness_curve;
+    }
+
+    pub fn with_brightness_curve(mut self, brightness_curve: BrightnessCurve) -> Self {
+        self.set_brightness_curve(brightness_curve);
+        self
+    }
+
+    pub fn clock_source(&self) -> Option<ClockSource> {
+        self.clock_source
+    }
+
+    pub fn set_clock_source(&mut self, clock_source: Option<ClockSource>) {
+        self.clock_source = clock_source;
+    }
+
+    pub fn with_clock_source(mut self, clock_source: Option<ClockSource>) -> Self {
+        self.set_clock_source(clock_source);
+        self
+    }
+
+    pub fn post_latch_delay(&self) -> u8 {
+        self.post_latch_delay
+    }
+
+    pub fn set_post_latch_delay(&mut self, post_latch_delay: u8) {
+        self.post_latch_delay = post_latch_delay;
+    }
+
+    pub fn with_post_latch_delay(mut self, post_latch_delay: u8) -> Self {
+        self.set_post_latch_delay(post_latch_delay);
+        self
+    }
+
+    pub fn bcm_order(&self) -> BcmOrder {
+        self.bcm_order
+    }
+
+    pub fn set_bcm_order(&mut self, bcm_order: BcmOrder) {
+        self.bcm_order = bcm_order;
+    }
+
+    pub fn with_bcm_order(mut self, bcm_order: BcmOrder) -> Self {
+        self.set_bcm_order(bcm_order);
+        self
+    }
+
+    pub fn address_transition(&self) -> bool {
+        self.address_transition
+    }
+
+    pub fn set_address_transition(&mut self, address_transition: bool) {
+        self.address_transition = address_transition;
+    }
+
+    pub fn with_address_transition(mut self, address_transition: bool) -> Self {
+        self.set_address_transition(address_transition);
+        self
+    }
+
+    /// The columns of each scanline that have output enabled for the given brightness.
+    ///
+    /// The lit columns always start immediately after the leading blanking columns (and the
+    /// post-latch delay), and are scaled from the columns left over after removing the blanking
+    /// columns on either side of the latch column, following the brightness curve. At least
+    /// `min_blanking_columns` columns are left dark.
+    pub(crate) fn lit_columns(&self, brightness: u8) -> LitColumns {
+        let blanking = self.latch_blanking_count as usize;
+        let start = blanking + self.post_latch_delay as usize;
+        let end = Self::WORDS_PER_PLANE.saturating_sub(1 + blanking);
+        let available = end.saturating_sub(start);
+        let brightness = brightness as usize;
+        let max = u8::MAX as usize;
+        let lit = match self.brightness_curve {
+            BrightnessCurve::Linear => available * brightness / max,
+            BrightnessCurve::Perceptual => available * brightness * brightness / (max * max),
+        };
+        let lit = lit.min(Self::WORDS_PER_PLANE.saturating_sub(self.min_blanking_columns as usize));
+        LitColumns {
+            columns: start..(start + lit),
+            half_lsb: self.lsb_half_duty,
+        }
+    }
+
+    /// The number of pixel clock cycles it takes to send one full frame.
+    ///
+    /// Besides the words of every segment, this counts the dummy, setup and hold cycles sent with
+    /// each frame, and the blank segments added by ghost reduction and address transitions.
+    fn cycles_per_frame(&self) -> usize {
+        let ghost_words = if self.ghost_reduction {
+            ((1 << Self::COLOR_DEPTH) - 1) * Self::SCANLINES_PER_FRAME * GHOST_BLANKING_WORDS
+        } else {
+            0
+        };
+        let transition_words = if self.address_transition {
+            Self::SCANLINES_PER_FRAME * GHOST_BLANKING_WORDS
+        } else {
+            0
+        };
+        Self::STREAMED_WORDS_PER_FRAME
+            + ghost_words
+            + transition_words
+            + LCD_DUMMY_CYCLES as usize
+            + self.setup_cycles as usize
+            + self.hold_cycles as usize
+    }
+
+    /// The number of full frames sent each second with the given pixel clock frequency.
+    pub fn refresh_rate(&self, pixel_clock_hz: u32) -> u32 {
+        (pixel_clock_hz as u64 / self.cycles_per_frame() as u64) as u32
+    }
+
+    /// The pixel clock needed to send `target_hz` full frames each second, to pass to
+    /// `Esp32s3Dma::create()`.
+    ///
+    /// This counts the same cycles as `refresh_rate()`, so passing the result back to it gives
+    /// `target_hz`. The LCD clock is divided down to twice this frequency (to work around an
+    /// erratum), so the pixel clock can be at most a quarter of the fastest source clock.
+    pub fn pixel_clock_for_refresh(&self, target_hz: u32) -> HertzU32 {
+        let hz = (target_hz as u64 * self.cycles_per_frame() as u64).min(u32::MAX as u64);
+        HertzU32::from_raw(hz as u32)
+    }
+
+    /// Check if the given pixel clock frequency is fast enough to avoid flicker.
+    ///
+    /// If it isn't, either the pixel clock needs to be increased or the color depth decreased.
+    pub fn check_refresh(&self, pixel_clock_hz: u32) -> RefreshVerdict {
+        let hz = self.refresh_rate(pixel_clock_hz);
+        RefreshVerdict {
+            hz,
+            ok: hz >= Self::MIN_FLICKER_FREE_REFRESH,
+        }
+    }
+
+    /// Check that a frame buffer was configured with this config.
+    ///
+    /// This samples the control bits of the first scanline, so it is cheap enough to use as a debug
+    /// assertion, but may not catch every difference.
+    pub fn validate_buffer<const WORDS_PER_PLANE: usize, const SCANLINES_PER_FRAME: usize>(
+        &self,
+        frame_buffer: &FrameBuffer<
+            WIDTH,
+            HEIGHT,
+            CHAIN_LENGTH,
+            COLOR_DEPTH,
+            PER_FRAME_DENOMINATOR,
+            WORDS_PER_PLANE,
+            SCANLINES_PER_FRAME,
+        >,
+    ) -> Result<(), Mismatch> {
+        if !frame_buffer.is_configured() {
+            return Err(Mismatch::NotConfigured);
+        }
+        // The most significant plane is never shortened by lsb_half_duty, and isn't the first
+        // plane, which has the previous scanline's address (unless address transitions are
+        // enabled).
+        let plane_index = Self::COLOR_DEPTH - 1;
+        // Any control bits that are still deferred are filled in, so lazy frame buffers can be
+        // checked before they are flushed.
+        frame_buffer
+            .with_resolved_plane(0, plane_index, |plane| {
+                let expected_address = if plane_index == 0 && !self.address_transition {
+                    Self::SCANLINES_PER_FRAME - 1
+                } else {
+                    0
+                } as u8;
+                if plane.iter().any(|word| word.address() != expected_address) {
+                    return Err(Mismatch::Address);
+                }
+                let last_column = plane.len() - 1;
+                let latch_columns = plane.iter().enumerate().filter(|(_, word)| word.latch());
+                if latch_columns.map(|(column, _)| column).ne([last_column]) {
+                    return Err(Mismatch::Latch);
+                }
+                // If the brightness is 0 there are no lit columns, and the blanking can't be
+                // checked.
+                let first_lit = plane.iter().position(|word| !word.output_enable());
+                let last_lit = plane.iter().rposition(|word| !word.output_enable());
+                if let (Some(first_lit), Some(last_lit)) = (first_lit, last_lit) {
+                    let blanking = self.latch_blanking_count as usize;
+                    let start = blanking + self.post_latch_delay as usize;
+                    if first_lit != start || last_lit + blanking >= last_column {
+                        return Err(Mismatch::Blanking);
+                    }
+                }
+                Ok(())
+            })
+            .unwrap_or(Err(Mismatch::NotConfigured))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    type TestConfig = MatrixConfig<64, 32, 1, 8, 16>;
+
+    #[test]
+    fn blank_region_workaround_default() {
+        assert!(TestConfig::default().blank_region_workaround());
+        assert!(TestConfig::new(2).blank_region_workaround());
+    }
+
+    #[test]
+    fn blank_region_workaround_toggle() {
+        let mut config = TestConfig::default();
+        config.set_blank_region_workaround(false);
+        assert!(!config.blank_region_workaround());
+        config.set_blank_region_workaround(true);
+        assert!(config.blank_region_workaround());
+    }
+
+    #[test]
+    fn setup_hold_cycles_default() {
+        let config = TestConfig::default();
+        assert_eq!(config.setup_cycles(), 2);
+        assert_eq!(config.hold_cycles(), 2);
+    }
+
+    #[test]
+    fn setup_hold_cycles_clamped() {
+        let mut config = TestConfig::default();
+        config.set_setup_cycles(10);
+        config.set_hold_cycles(300);
+        assert_eq!(config.setup_cycles(), 10);
+        assert_eq!(config.hold_cycles(), 300);
+        config.set_setup_cycles(0);
+        config.set_hold_cycles(0);
+        assert_eq!(config.setup_cycles(), 1);
+        assert_eq!(config.hold_cycles(), 1);
+        config.set_setup_cycles(100);
+        config.set_hold_cycles(10000);
+        assert_eq!(config.setup_cycles(), 64);
+        assert_eq!(config.hold_cycles(), 8192);
+    }
+
+    #[test]
+    fn clock_source() {
+        let config = TestConfig::default();
+        assert_eq!(config.clock_source(), None);
+        let config = config.with_clock_source(Some(ClockSource::Xtal));
+        assert_eq!(config.clock_source(), Some(ClockSource::Xtal));
+    }
+
+    #[test]
+    fn byte_order() {
+        let mut config = TestConfig::default();
+        assert_eq!(config.byte_order(), ByteOrder::LittleEndian);
+        config.set_byte_order(ByteOrder::BigEndian);
+        assert_eq!(config.byte_order(), ByteOrder::BigEndian);
+    }
+
+    #[test]
+    fn with_overrides_defaults() {
+        let config = TestConfig::default().with_hold_cycles(4);
+        let mut expected = TestConfig::default();
+        expected.set_hold_cycles(4);
+        assert_eq!(config, expected);
+        // The same clamping applies as the setters.
+        assert_eq!(TestConfig::default().with_setup_cycles(0).setup_cycles(), 1);
+    }
+
+    #[test]
+    fn panel_brightness() {
+        let mut config = MatrixConfig::<64, 32, 2, 8, 16>::default();
+        assert_eq!(config.panel_brightness(), &[255, 255]);
+        config.set_panel_brightness([255, 128]);
+        assert_eq!(config.panel_brightness(), &[255, 128]);
+    }
+
+    #[test]
+    fn afifo_threshold_clamped() {
+        let mut config = TestConfig::default();
+        assert_eq!(config.afifo_threshold(), 0);
+        config.set_afifo_threshold(12);
+        assert_eq!(config.afifo_threshold(), 12);
+        config.set_afifo_threshold(32);
+        assert_eq!(config.afifo_threshold(), 31);
+    }
+
+    #[test]
+    fn validate_buffer_matching() {
+        let config = TestConfig::new(2);
+        let mut fb = crate::declare_frame_buffer!(64, 32, 8, 1, 16);
+        assert_eq!(config.validate_buffer(&fb), Err(Mismatch::NotConfigured));
+        fb.configure(2, &config.lit_columns(255));
+        assert_eq!(config.validate_buffer(&fb), Ok(()));
+        // Nothing is lit, so the blanking can't be checked.
+        fb.configure(2, &config.lit_columns(0));
+        assert_eq!(config.validate_buffer(&fb), Ok(()));
+    }
+
+    #[test]
+    fn validate_buffer_blanking_mismatch() {
+        let config = TestConfig::new(2);
+        let other_config = TestConfig::new(4);
+        let mut fb = crate::declare_frame_buffer!(64, 32, 8, 1, 16);
+        fb.configure(4, &other_config.lit_columns(255));
+        assert_eq!(config.validate_buffer(&fb), Err(Mismatch::Blanking));
+        assert_eq!(other_config.validate_buffer(&fb), Ok(()));
+        fb.configure(1, &TestConfig::new(1).lit_columns(255));
+        assert_eq!(config.validate_buffer(&fb), Err(Mismatch::Blanking));
+    }
+
+    #[test]
+    fn validate_buffer_control_bits_mismatch() {
+        let config = TestConfig::new(2);
+        let mut fb = crate::declare_frame_buffer!(64, 32, 8, 1, 16);
+        fb.configure(2, &config.lit_columns(255));
+        fb.plane_mut(0, 7).unwrap()[10] |= 1 << 6;
+        assert_eq!(config.validate_buffer(&fb), Err(Mismatch::Latch));
+        fb.configure(2, &config.lit_columns(255));
+        fb.plane_mut(0, 7).unwrap()[10] |= 1 << 8;
+        assert_eq!(config.validate_buffer(&fb), Err(Mismatch::Address));
+    }
+
+    #[cfg(feature = "defmt")]
+    #[test]
+    fn defmt_format() {
+        fn assert_format<T: defmt::Format>() {}
+        assert_format::<TestConfig>();
+        assert_format::<Mismatch>();
+        assert_format::<LaneMapping>();
+        assert_format::<LaneOrder>();
+        assert_format::<crate::rgb_matrix::MatrixError>();
+    }
+
+    #[test]
+    fn check_refresh_deep_color_flickers() {
+        // 64 words * 16 scanlines * 255 plane repetitions, plus 6 cycles of overhead per frame
+        let verdict = TestConfig::default().check_refresh(20_000_000);
+        assert_eq!(verdict, RefreshVerdict { hz: 76, ok: false });
+    }
+
+    #[test]
+    fn pixel_clock_for_refresh_round_trip() {
+        for target in [60, 100, 120, 240] {
+            let config = TestConfig::default();
+            let clock = config.pixel_clock_for_refresh(target).to_Hz();
+            // 64 words * 16 scanlines * 255 plane repetitions, plus 2 dummy, 2 setup and 2 hold
+            // cycles.
+            assert_eq!(clock, target * (261_120 + 6));
+            for config in [
+                config,
+                config.with_ghost_reduction(true),
+                config.with_address_transition(true),
+                config
+                    .with_ghost_reduction(true)
+                    .with_address_transition(true),
+            ] {
+                let clock = config.pixel_clock_for_refresh(target).to_Hz();
+                assert_eq!(config.refresh_rate(clock), target, "{config:?}");
+                // One cycle less can't reach the target.
+                assert_eq!(config.refresh_rate(clock - 1), target - 1, "{config:?}");
+            }
+        }
+        let ghost = TestConfig::default().with_ghost_reduction(true);
+        assert_eq!(
+            ghost.pixel_clock_for_refresh(100).to_Hz(),
+            100 * (261_120 + 255 * 16 * GHOST_BLANKING_WORDS as u32 + 6)
+        );
+        let transition = TestConfig::default().with_address_transition(true);
+        assert_eq!(
+            transition.pixel_clock_for_refresh(100).to_Hz(),
+            100 * (261_120 + 16 * GHOST_BLANKING_WORDS as u32 + 6)
+        );
+    }
+
+    #[test]
+    fn check_refresh_shallow_color_ok() {
+        // 64 words * 16 scanlines * 15 plane repetitions, plus 6 cycles of overhead per frame
+        let verdict = MatrixConfig::<64, 32, 1, 4, 16>::default().check_refresh(20_000_000);
+        assert_eq!(verdict, RefreshVerdict { hz: 1301, ok: true });
+    }
+
+    #[test]
+    fn lit_columns_brightness() {
+        let mut config = TestConfig::new(2);
+        // 64 words per plane, less the latch column and 2 blanking columns on each side.
+        assert_eq!(config.lit_columns(255).columns, 2..61);
+        assert_eq!(config.lit_columns(0).columns, 2..2);
+        config.set_latch_blanking_count(0);
+        assert_eq!(config.lit_columns(255).columns, 0..63);
+    }
+
+    #[test]
+    fn post_latch_delay() {
+        let config = TestConfig::new(2).with_post_latch_delay(3);
+        // The delay only comes out of the columns after the latch, which wraps around to the
+        // start of the next plane.
+        assert_eq!(config.lit_columns(255).columns, 5..61);
+        assert_eq!(config.lit_columns(128).columns, 5..33);
+
+        let mut fb = crate::declare_frame_buffer!(64, 32, 8, 1, 16);
+        fb.configure(2, &config.lit_columns(128));
+        for scanline in 0..16 {
+            for plane in 0..8 {
+                let words = fb.plane(scanline, plane).unwrap();
+                for (column, word) in words.iter().enumerate() {
+                    assert_eq!(
+                        word.output_enable(),
+                        !(5..33).contains(&column),
+                        "scanline {scanline} plane {plane} column {column}"
+                    );
+                }
+            }
+        }
+        assert_eq!(config.validate_buffer(&fb), Ok(()));
+        assert_eq!(
+            TestConfig::new(2).validate_buffer(&fb),
+            Err(Mismatch::Blanking)
+        );
+    }
+
+    #[test]
+    fn min_blanking_columns() {
+        let mut config = TestConfig::new(2);
+        // The latch blanking already leaves 5 columns dark.
+        config.set_min_blanking_columns(4);
+        assert_eq!(config.lit_columns(255).columns, 2..61);
+        config.set_min_blanking_columns(16);
+        assert_eq!(config.lit_columns(255).columns, 2..50);
+        // Lower brightnesses are already dark enough.
+        assert_eq!(config.lit_columns(64).columns, 2..16);
+
+        let mut fb = crate::declare_frame_buffer!(64, 32, 8, 1, 16);
+        fb.configure(2, &config.lit_columns(255));
+        for scanline in 0..16 {
+            for plane in 0..8 {
+                let dark = fb
+                    .plane(scanline, plane)
+                    .unwrap()
+                    .iter()
+                    .filter(|word| word.output_enable())
+                    .count();
+                assert!(dark >= 16, "scanline {scanline} plane {plane}: {dark}");
+            }
+        }
+    }
+
+    #[test]
+    fn brightness_curve() {
+        // 59 columns are available between the blanking columns.
+        let lit = |config: &TestConfig, brightness| config.lit_columns(brightness).columns.len();
+        let linear = TestConfig::new(2);
+        assert_eq!(lit(&linear, 64), 14);
+        assert_eq!(lit(&linear, 128), 29);
+        assert_eq!(lit(&linear, 191), 44);
+        let perceptual = linear.with_brightness_curve(BrightnessCurve::Perceptual);
+        assert_eq!(lit(&perceptual, 64), 3);
+        assert_eq!(lit(&perceptual, 128), 14);
+        assert_eq!(lit(&perceptual, 191), 33);
+        // Both curves meet at the ends.
+        for brightness in [0, 255] {
+            assert_eq!(lit(&linear, brightness), lit(&perceptual, brightness));
+        }
     }
 
-    pub(crate) const fn words_per_frame(&self) -> usize {
-        Self::WORDS_PER_FRAME
+    #[test]
+    fn lsb_half_duty() {
+        let mut config = TestConfig::new(2);
+        let full = config.lit_columns(200);
+        assert_eq!(full.for_plane(0), full.for_plane(1));
+        config.set_lsb_half_duty(true);
+        let halved = config.lit_columns(200);
+        assert_eq!(halved.for_plane(0).len(), full.for_plane(0).len() / 2);
+        assert_eq!(halved.for_plane(1), full.for_plane(1));
+        assert_eq!(halved.for_plane(7), full.for_plane(7));
     }
 }
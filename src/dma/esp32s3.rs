@@ -1,9 +1,9 @@
 use esp32s3_hal::clock::Clocks;
 use esp32s3_hal::dma::{
-    self, Channel, ChannelTx, ChannelTypes, DmaDescriptor, DmaError, DmaPeripheral, DmaPriority,
-    LcdCamPeripheral, RegisterAccess, Tx, TxChannel, TxPrivate,
+    self, Channel, ChannelTx, ChannelTypes, DmaDescriptor, DmaError, DmaExtMemBKSize,
+    DmaPeripheral, DmaPriority, LcdCamPeripheral, RegisterAccess, Tx, TxChannel, TxPrivate,
 };
-use esp32s3_hal::gpio::{DriveStrength, OutputPin, OutputSignal};
+use esp32s3_hal::gpio::{DriveStrength, OutputPin, OutputSignal, Pin};
 use esp32s3_hal::lcd_cam::lcd::Lcd;
 use esp32s3_hal::lcd_cam::LcdCam;
 use esp32s3_hal::peripheral::{Peripheral, PeripheralRef};
@@ -15,13 +15,22 @@ use crate::util::Sealed;
 use crate::{const_check, const_not_zero};
 
 use crate::buffer::FrameBuffer;
-use crate::clock_divider::calculate_clkm;
-use crate::config::MatrixConfig;
+use crate::clock_divider::{calculate_clkm, ClockDivider};
+use crate::config::{MatrixConfig, LCD_DUMMY_CYCLES};
 
-use super::{MatrixDma, Transfer};
+use super::lcd_cam::{self, descriptor_fault, lcd_byte_order_bit, lcd_misc_bits, LcdControl};
+pub use super::lcd_cam::{
+    address_transition_descriptor_count, descriptors_for_segments,
+    ghost_reduction_descriptor_count, min_descriptor_count, BufferPlacement,
+};
+use super::{DescriptorFault, MatrixDma, PinCollision, Transfer};
 
 pub trait MatrixPins: Sealed {
     fn configure(&mut self);
+
+    /// Hold the output enable pin high, blanking the panel whatever the LCD is sending, or
+    /// connect it back to the LCD.
+    fn set_blanked(&mut self, blanked: bool);
 }
 
 pub struct Pins<
@@ -219,6 +228,32 @@ where
     PixelClock: OutputPin,
 {
     fn configure(&mut self) {
+        // Passing the same GPIO for two signals (which the pin types can't always prevent) silently
+        // breaks the panel, so check for it while debugging.
+        if cfg!(debug_assertions) {
+            let pins = [
+                ("red_1", Some(self.red_1.number())),
+                ("green_1", Some(self.green_1.number())),
+                ("blue_1", Some(self.blue_1.number())),
+                ("red_2", Some(self.red_2.number())),
+                ("green_2", Some(self.green_2.number())),
+                ("blue_2", Some(self.blue_2.number())),
+                ("address_a", Some(self.address_a.number())),
+                ("address_b", Some(self.address_b.number())),
+                ("address_c", Some(self.address_c.number())),
+                ("address_d", self.address_d.as_ref().map(|pin| pin.number())),
+                ("address_e", self.address_e.as_ref().map(|pin| pin.number())),
+                ("output_enable", Some(self.output_enable.number())),
+                ("latch", Some(self.latch.number())),
+                ("clock", Some(self.clock.number())),
+            ];
+            if let Some(collision) = PinCollision::find(&pins) {
+                panic!(
+                    "GPIO{} is assigned to both {} and {}",
+                    collision.pin, collision.first, collision.second
+                );
+            }
+        }
         self.red_1
             .set_to_push_pull_output()
             .set_drive_strength(Self::DEFAULT_DRIVE_STRENGTH)
@@ -280,6 +315,18 @@ where
             .set_drive_strength(Self::DEFAULT_DRIVE_STRENGTH)
             .connect_peripheral_to_output(OutputSignal::LCD_PCLK);
     }
+
+    fn set_blanked(&mut self, blanked: bool) {
+        if blanked {
+            // OE is active low, so drive it high before taking it from the LCD.
+            self.output_enable
+                .set_output_high(true)
+                .disconnect_peripheral_from_output();
+        } else {
+            self.output_enable
+                .connect_peripheral_to_output(OutputSignal::LCD_DATA_11);
+        }
+    }
 }
 
 impl<
@@ -346,6 +393,56 @@ impl_lcd_channel_creator!(dma::ChannelCreator2, dma::Channel2);
 impl_lcd_channel_creator!(dma::ChannelCreator3, dma::Channel3);
 impl_lcd_channel_creator!(dma::ChannelCreator4, dma::Channel4);
 
+/// An error from `Esp32s3Dma`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Esp32s3DmaError {
+    /// An error reported by the DMA driver.
+    Dma(DmaError),
+
+    /// The DMA channel raised a descriptor error while the transfer was running.
+    ///
+    /// The HAL doesn't expose the address of the descriptor that faulted, so this is the last
+    /// descriptor the channel finished before it stopped (from OUT_EOF_DES_ADDR). The faulting
+    /// descriptor is usually the one after it.
+    Descriptor(DescriptorFault),
+
+    /// A segment of a frame buffer in PSRAM doesn't start or end on a
+    /// `BufferPlacement::PSRAM_BLOCK_SIZE` boundary.
+    Unaligned,
+
+    /// The LCD didn't report that it finished sending after being stopped.
+    ///
+    /// The panel is still blanked, and `Esp32s3Dma::reset_peripheral()` can be used to recover.
+    StopTimeout,
+}
+
+impl From<DmaError> for Esp32s3DmaError {
+    fn from(err: DmaError) -> Self {
+        Self::Dma(err)
+    }
+}
+
+// Tell the linker about the ROM function for writing back the data cache.
+extern "C" {
+    fn Cache_WriteBack_Addr(addr: u32, size: u32);
+}
+
+impl BufferPlacement {
+    /// Write back any cached changes to `len` bytes starting at `address`, so that they are seen by
+    /// the DMA controller.
+    fn write_back(&self, address: usize, len: usize) {
+        if *self == Self::Psram {
+            // Safety: This only writes the cache back to memory, the contents of memory as seen by
+            // the CPU aren't changed.
+            unsafe { Cache_WriteBack_Addr(address as u32, len as u32) };
+        }
+    }
+}
+
+/// LCD_CAM DMA driver for an RGB matrix.
+///
+/// The DMA descriptors given to `create()` are owned by the DMA channel inside this struct, so
+/// they are kept across `stop()` and `start()` cycles and don't need to be passed in again.
 pub struct Esp32s3Dma<
     'd,
     TX,
@@ -364,7 +461,13 @@ pub struct Esp32s3Dma<
 
     config: MatrixConfig<WIDTH, HEIGHT, CHAIN_LENGTH, COLOR_DEPTH, PER_FRAME_DENOMINATOR>,
 
-    _pins: P,
+    buffer_placement: BufferPlacement,
+
+    clock_source: usize,
+
+    clock_divider: ClockDivider,
+
+    pins: P,
 }
 
 impl<
@@ -413,11 +516,142 @@ impl<
         "SCANLINES_PER_FRAME must equal HEIGHT / (HEIGHT / PER_FRAME_DENOMINATOR), and be less than or equal to 32"
     );
 
-    pub const MIN_DESCRIPTOR_COUNT: usize = {
-        ((Self::WORDS_PER_PLANE * core::mem::size_of::<u16>() + 4091) / 4092)
-            * ((1 << (Self::COLOR_DEPTH)) - 1)
-            * Self::SCANLINES_PER_FRAME
-    };
+    pub const MIN_DESCRIPTOR_COUNT: usize = min_descriptor_count(
+        Self::WORDS_PER_PLANE,
+        Self::COLOR_DEPTH,
+        Self::SCANLINES_PER_FRAME,
+    );
+
+    /// The number of descriptors needed when `MatrixConfig::ghost_reduction()` is enabled.
+    pub const GHOST_REDUCTION_DESCRIPTOR_COUNT: usize = ghost_reduction_descriptor_count(
+        Self::WORDS_PER_PLANE,
+        Self::COLOR_DEPTH,
+        Self::SCANLINES_PER_FRAME,
+    );
+
+    /// The number of descriptors needed when `MatrixConfig::address_transition()` is enabled.
+    ///
+    /// With ghost reduction enabled as well, add `SCANLINES_PER_FRAME` to
+    /// `GHOST_REDUCTION_DESCRIPTOR_COUNT` instead.
+    pub const ADDRESS_TRANSITION_DESCRIPTOR_COUNT: usize = address_transition_descriptor_count(
+        Self::WORDS_PER_PLANE,
+        Self::COLOR_DEPTH,
+        Self::SCANLINES_PER_FRAME,
+    );
+
+    pub fn buffer_placement(&self) -> BufferPlacement {
+        self.buffer_placement
+    }
+
+    /// The LCD_CLK source chosen by `create()`: 0 for the crystal clock, 1 for the CPU clock, or 2
+    /// for the crypto/PWM clock (as given by `Clocks`, and the same as `ClockSource`).
+    ///
+    /// This is always the source given by `MatrixConfig::clock_source()`, if there was one.
+    pub fn clock_source(&self) -> usize {
+        self.clock_source
+    }
+
+    /// The LCD_CLK divider chosen by `create()`.
+    ///
+    /// The pixel clock is half of the divided clock, to work around an erratum.
+    pub fn clock_divider(&self) -> ClockDivider {
+        self.clock_divider
+    }
+
+    /// Set where frame buffers given to `start()` are placed in memory.
+    ///
+    /// The default is `BufferPlacement::Internal`. Starting a transfer with a frame buffer that
+    /// isn't in the chosen placement fails with `Esp32s3DmaError::Dma(DmaError::UnsupportedMemoryRegion)`,
+    /// and one in PSRAM that isn't aligned to `BufferPlacement::PSRAM_BLOCK_SIZE` fails with
+    /// `Esp32s3DmaError::Unaligned`.
+    pub fn with_buffer_placement(mut self, buffer_placement: BufferPlacement) -> Self {
+        self.buffer_placement = buffer_placement;
+        self
+    }
+
+    /// The exact number of DMA descriptors a frame buffer uses, from the size of each segment.
+    ///
+    /// This is never more than `MIN_DESCRIPTOR_COUNT`, or `GHOST_REDUCTION_DESCRIPTOR_COUNT` with
+    /// ghost reduction enabled (and `ADDRESS_TRANSITION_DESCRIPTOR_COUNT` with address transitions
+    /// enabled).
+    pub fn descriptors_needed(
+        frame_buffer: &FrameBuffer<
+            WIDTH,
+            HEIGHT,
+            CHAIN_LENGTH,
+            COLOR_DEPTH,
+            PER_FRAME_DENOMINATOR,
+            WORDS_PER_PLANE,
+            SCANLINES_PER_FRAME,
+        >,
+    ) -> usize {
+        descriptors_for_segments(frame_buffer.buffer_iter())
+    }
+
+    /// Make changes to a frame buffer visible to the DMA controller.
+    ///
+    /// This needs to be called after changing a frame buffer in PSRAM, and does nothing for
+    /// frame buffers in internal RAM.
+    pub fn write_back_cache(
+        &self,
+        frame_buffer: &FrameBuffer<
+            WIDTH,
+            HEIGHT,
+            CHAIN_LENGTH,
+            COLOR_DEPTH,
+            PER_FRAME_DENOMINATOR,
+            WORDS_PER_PLANE,
+            SCANLINES_PER_FRAME,
+        >,
+    ) {
+        self.buffer_placement.write_back(
+            frame_buffer as *const _ as usize,
+            core::mem::size_of_val(frame_buffer),
+        );
+    }
+}
+
+#[doc(hidden)]
+pub use esp32s3_hal::dma::DmaDescriptor as __DmaDescriptor;
+
+/// Declare a static array of DMA descriptors sized for the given matrix geometry.
+///
+/// The arguments are the same as for `declare_frame_buffer!`: width, height, color depth, chain
+/// length, and the per-frame denominator. If ghost reduction or address transitions will be
+/// enabled on the frame buffer, add `ghost_reduction: true` or `address_transition: true` to make
+/// room for their extra segments. The macro evaluates to a
+/// `&'static mut [DmaDescriptor]` suitable for passing to `Esp32s3Dma::create()`. Like any
+/// `static mut`, it must only be evaluated once.
+#[macro_export]
+macro_rules! static_descriptors {
+    (
+        $name:ident,
+        $width:literal,
+        $height:literal,
+        $color_depth:literal,
+        $chain_length:literal,
+        $per_frame_denominator:literal
+        $(, ghost_reduction: $ghost_reduction:expr)?
+        $(, address_transition: $address_transition:expr)?
+        $(,)?
+    ) => {{
+        const DESCRIPTOR_COUNT: usize = $crate::descriptor_count!(
+            $width,
+            $height,
+            $color_depth,
+            $chain_length,
+            $per_frame_denominator
+            $(, ghost_reduction: $ghost_reduction)?
+            $(, address_transition: $address_transition)?
+        );
+        static mut $name: [$crate::dma::esp32s3::__DmaDescriptor; DESCRIPTOR_COUNT] =
+            [$crate::dma::esp32s3::__DmaDescriptor::EMPTY; DESCRIPTOR_COUNT];
+        // Safety: each expansion of this macro declares its own static, and it is only borrowed
+        // here.
+        let descriptors: &'static mut [$crate::dma::esp32s3::__DmaDescriptor] =
+            unsafe { &mut *core::ptr::addr_of_mut!($name) };
+        descriptors
+    }};
 }
 
 impl<
@@ -479,13 +713,15 @@ where
         // the LCD_PCLK divider must be at least 2. To make up for this the user
         // provided frequency is doubled to match.
 
+        let source_frequencies = [
+            clocks.xtal_clock.to_Hz() as _,
+            clocks.cpu_clock.to_Hz() as _,
+            clocks.crypto_pwm_clock.to_Hz() as _,
+        ];
         let (i, divider) = calculate_clkm(
             (frequency.to_Hz() * 2) as _,
-            &[
-                clocks.xtal_clock.to_Hz() as _,
-                clocks.cpu_clock.to_Hz() as _,
-                clocks.crypto_pwm_clock.to_Hz() as _,
-            ],
+            &source_frequencies,
+            config.clock_source().map(|source| source as usize),
         );
 
         lcd.lcd_cam.lcd_clock().write(|w| {
@@ -531,9 +767,9 @@ where
                 // Don't change the bit order, part 2
                 .lcd_bit_order()
                 .clear_bit()
-                // Don't change the byte order
+                // Swap the bytes of each word if the word layout needs it
                 .lcd_byte_order()
-                .clear_bit()
+                .bit(lcd_byte_order_bit(config.byte_order()))
                 // We're clocking out 2 bytes at a time
                 .lcd_2byte_en()
                 .set_bit()
@@ -542,39 +778,13 @@ where
                 .set_bit()
                 // We need 2 dummy cycles
                 .lcd_dummy_cyclelen()
-                .variant(2)
+                .variant(LCD_DUMMY_CYCLES)
         });
 
-        lcd.lcd_cam.lcd_misc().write(|w| {
-            // Set the threshold for Async Tx FIFO full event. (5 bits)
-            w.lcd_afifo_threshold_num()
-                .variant(0)
-                // Total of two setup cycles (this value + 1)
-                // Configure the setup cycles in LCD non-RGB mode. Setup cycles
-                // expected = this value + 1. (6 bit)
-                .lcd_vfk_cyclelen()
-                .variant(1)
-                // Same as setup time, 2 hold cycles (this value + 1)
-                .lcd_vbk_cyclelen()
-                .variant(1)
-                // Do not auto-frame data.
-                .lcd_next_frame_en()
-                .clear_bit()
-                // Enable blank region when LCD sends data out.
-                // Needed to work around a design flaw in the ESP32-S3:
-                // https://esp32.com/viewtopic.php?t=24459&start=60#p91835
-                .lcd_bk_en()
-                .set_bit()
-                // We don't need any of the clock edge congiurations
-                .lcd_cd_data_set()
-                .clear_bit()
-                .lcd_cd_dummy_set()
-                .clear_bit()
-                .lcd_cd_cmd_set()
-                .clear_bit()
-                .lcd_cd_idle_edge()
-                .clear_bit()
-        });
+        // Safety: `lcd_misc_bits()` only sets bits within the fields of LCD_MISC.
+        lcd.lcd_cam
+            .lcd_misc()
+            .write(|w| unsafe { w.bits(lcd_misc_bits(&config)) });
 
         lcd.lcd_cam
             .lcd_dly_mode()
@@ -617,6 +827,34 @@ where
                 .variant(0)
         });
 
+        #[cfg(feature = "log-registers")]
+        {
+            log::debug!(
+                "LCD_CLOCK register: {:#034b}",
+                lcd.lcd_cam.lcd_clock().read().bits()
+            );
+            log::debug!(
+                "LCD_USER register: {:#034b}",
+                lcd.lcd_cam.lcd_user().read().bits()
+            );
+            log::debug!(
+                "LCD_MISC register: {:#034b}",
+                lcd.lcd_cam.lcd_misc().read().bits()
+            );
+            log::debug!(
+                "LCD_CLK source {}, {}",
+                i,
+                divider.describe(source_frequencies[i])
+            );
+            // LCD_PCLK = LCD_CLK / 2
+            log::debug!(
+                "Effective pixel clock: {}Hz (requested {}Hz)",
+                crate::clock_divider::calculate_output_frequency(source_frequencies[i], &divider)
+                    / 2,
+                frequency.to_Hz()
+            );
+        }
+
         pins.configure();
 
         let channel = channel_creator.configure_lcd_channel(tx_descriptors);
@@ -626,9 +864,105 @@ where
             lcd,
             channel: channel.tx,
             config,
-            _pins: pins,
+            buffer_placement: BufferPlacement::Internal,
+            clock_source: i,
+            clock_divider: divider,
+            pins,
         }
     }
+
+    /// Reset the LCD_CAM peripheral and the DMA channel, without reconfiguring the clock or pins.
+    ///
+    /// This is for recovering from a wedged peripheral (for example a stuck async FIFO) after
+    /// `stop()`. The LCD and its FIFO are reset and the DMA channel is reinitialized, leaving this
+    /// ready for `start()` again. The panel stays blanked until then, and the clock, timing and pin
+    /// configuration from `create()` is kept.
+    pub fn reset_peripheral(&mut self) {
+        log::debug!("Resetting LCD_CAM peripheral");
+        lcd_cam::reset(self);
+    }
+}
+
+impl<
+        'd,
+        T,
+        R,
+        P,
+        const WIDTH: usize,
+        const HEIGHT: usize,
+        const CHAIN_LENGTH: usize,
+        const COLOR_DEPTH: usize,
+        const PER_FRAME_DENOMINATOR: u8,
+        const WORDS_PER_PLANE: usize,
+        const SCANLINES_PER_FRAME: usize,
+    > LcdControl
+    for Esp32s3Dma<
+        'd,
+        ChannelTx<'d, T, R>,
+        P,
+        WIDTH,
+        HEIGHT,
+        CHAIN_LENGTH,
+        COLOR_DEPTH,
+        PER_FRAME_DENOMINATOR,
+        WORDS_PER_PLANE,
+        SCANLINES_PER_FRAME,
+    >
+where
+    T: TxChannel<R>,
+    R: ChannelTypes + RegisterAccess,
+    R::P: LcdCamPeripheral,
+    P: MatrixPins,
+{
+    fn set_start(&mut self, start: bool) {
+        self.lcd.lcd_cam.lcd_user().modify(|_, w| {
+            if start {
+                w.lcd_update().set_bit().lcd_start().set_bit()
+            } else {
+                w.lcd_start().clear_bit()
+            }
+        });
+    }
+
+    fn take_trans_done(&mut self) -> bool {
+        let done = self
+            .lcd
+            .lcd_cam
+            .lc_dma_int_raw()
+            .read()
+            .lcd_trans_done_int_raw()
+            .bit_is_set();
+        if done {
+            self.lcd
+                .lcd_cam
+                .lc_dma_int_clr()
+                .write(|w| w.lcd_trans_done_int_clr().set_bit());
+        }
+        done
+    }
+
+    fn set_blanked(&mut self, blanked: bool) {
+        self.pins.set_blanked(blanked);
+    }
+
+    fn reset(&mut self) {
+        let lcd_cam = &self.lcd.lcd_cam;
+        // Pulse the reset bits for the LCD and its async FIFO.
+        lcd_cam.lcd_user().modify(|_, w| w.lcd_reset().set_bit());
+        lcd_cam.lcd_user().modify(|_, w| w.lcd_reset().clear_bit());
+        lcd_cam
+            .lcd_misc()
+            .modify(|_, w| w.lcd_afifo_reset().set_bit());
+        lcd_cam
+            .lcd_misc()
+            .modify(|_, w| w.lcd_afifo_reset().clear_bit());
+        lcd_cam
+            .lc_dma_int_clr()
+            .write(|w| w.lcd_trans_done_int_clr().set_bit());
+
+        R::init_channel();
+        R::clear_out_interrupts();
+    }
 }
 
 impl<
@@ -671,11 +1005,13 @@ where
     R::P: LcdCamPeripheral,
     P: MatrixPins,
 {
-    type Error = DmaError;
+    type Error = Esp32s3DmaError;
 
     /// Start a continuous DMA transfer to the RGB matrix.
     ///
-    /// Safety: The memory referred to by the `frame_buffer`argument cannot be written to while the
+    /// # Safety
+    ///
+    /// The memory referred to by the `frame_buffer`argument cannot be written to while the
     /// DMA transfer is in progress. If the lifetime of the `frame_buffer` argument is `\`static`,
     /// this is guaranteed; but if it is any other lifetime it is possible to `core::mem::forget()`
     /// the `Transfer`, which would skip the normal stop of the ongoing transfer.
@@ -703,7 +1039,7 @@ where
             SCANLINES_PER_FRAME,
         >,
         (
-            DmaError,
+            Esp32s3DmaError,
             Self,
             &'a mut FrameBuffer<
                 WIDTH,
@@ -716,6 +1052,25 @@ where
             >,
         ),
     > {
+        frame_buffer.flush_control_bits();
+        let in_placement = frame_buffer
+            .buffer_ptr_iter()
+            .all(|(ptr, len)| self.buffer_placement.contains(ptr as usize, len));
+        if !in_placement {
+            return Err((DmaError::UnsupportedMemoryRegion.into(), self, frame_buffer));
+        }
+        let aligned = frame_buffer
+            .buffer_ptr_iter()
+            .all(|(ptr, len)| self.buffer_placement.is_aligned(ptr as usize, len));
+        if !aligned {
+            return Err((Esp32s3DmaError::Unaligned, self, frame_buffer));
+        }
+        if self.buffer_placement == BufferPlacement::Psram {
+            // The block size has to match the alignment checked above.
+            R::set_out_ext_mem_block_size(DmaExtMemBKSize::Size16);
+        }
+        self.write_back_cache(frame_buffer);
+
         // Reset operating registers to known state
         self.lcd.lcd_cam.lcd_user().modify(|_, w| {
             w.lcd_reset()
@@ -774,24 +1129,40 @@ where
                     .lcd_cam
                     .lc_dma_int_clr()
                     .write(|w| w.lcd_trans_done_int_clr().set_bit());
-
-                self.lcd
-                    .lcd_cam
-                    .lcd_user()
-                    .modify(|_, w| w.lcd_update().set_bit().lcd_start().set_bit());
                 Ok(())
             });
         match maybe_err {
-            Ok(_) => Ok(Transfer {
-                matrix_dma: self,
-                frame_buffer: frame_buffer,
-            }),
-            Err(err) => Err((err, self, frame_buffer)),
+            Ok(_) => {
+                // This also releases the panel if it was blanked by stopping a transfer.
+                lcd_cam::start(&mut self);
+                Ok(Transfer::new(self, frame_buffer))
+            }
+            Err(err) => Err((err.into(), self, frame_buffer)),
+        }
+    }
+
+    fn poll_frame_done(&mut self) -> bool {
+        // The EOF interrupt is raised when the last descriptor of the frame has been sent.
+        if R::is_out_done() {
+            R::clear_out_interrupts();
+            true
+        } else {
+            false
         }
     }
 
+    fn pause(&mut self) {
+        if !lcd_cam::stop(self) {
+            log::warn!("LCD_CAM did not finish sending before pausing");
+        }
+    }
+
+    fn resume(&mut self) {
+        lcd_cam::start(self);
+    }
+
     fn stop<'a>(
-        transfer: Transfer<
+        mut transfer: Transfer<
             'a,
             Self,
             WIDTH,
@@ -831,35 +1202,25 @@ where
     > {
         // TODO Maybe add the interrupt handler stuff ESP32-HUB75-MatrixPanel-I2S is doing?
         log::debug!("Stopping RGB matrix DMA transfer");
-        transfer.matrix_dma.lcd.lcd_cam.lcd_user().modify(|_, w| {
-            w
-                //.lcd_reset()
-                //.set_bit()
-                //.lcd_update()
-                //.set_bit()
-                .lcd_start()
-                .clear_bit()
-        });
+        if !lcd_cam::stop(&mut transfer.matrix_dma) {
+            return Err((
+                Esp32s3DmaError::StopTimeout,
+                transfer.matrix_dma,
+                transfer.frame_buffer,
+            ));
+        }
         log::trace!(
             "LCD_USER register: {:#034b}",
             transfer.matrix_dma.lcd.lcd_cam.lcd_user().read().bits()
         );
-        transfer
-            .matrix_dma
-            .lcd
-            .lcd_cam
-            .lc_dma_int_clr()
-            .write(|w| w.lcd_trans_done_int_clr().clear_bit());
-        // Wait for the DMA transfer to end.
-        // TODO: This may not actually work...
-        let dma_int_raw = transfer.matrix_dma.lcd.lcd_cam.lc_dma_int_raw();
-        while dma_int_raw.read().lcd_trans_done_int_raw().bit_is_clear() {
-            log::trace!("RGB matrix DMA transfer still in progress");
-        }
 
         if transfer.matrix_dma.channel.has_error() {
+            let fault = descriptor_fault(
+                transfer.matrix_dma.channel.descriptors,
+                R::last_out_dscr_address(),
+            );
             Err((
-                DmaError::DescriptorError,
+                Esp32s3DmaError::Descriptor(fault),
                 transfer.matrix_dma,
                 transfer.frame_buffer,
             ))
@@ -897,6 +1258,9 @@ impl<
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         f.debug_struct("Esp32s3Dma")
             .field("config", &self.config)
+            .field("buffer_placement", &self.buffer_placement)
+            .field("clock_source", &self.clock_source)
+            .field("clock_divider", &self.clock_divider)
             .finish()
     }
 }
@@ -0,0 +1,613 @@
+// The parts of driving the ESP32-S3 LCD_CAM peripheral that don't need the HAL, kept apart from
+// `esp32s3` so that they are built (and tested) for every target.
+
+use super::DescriptorFault;
+use crate::config::{ByteOrder, MatrixConfig};
+
+/// The most bytes a single DMA descriptor can point to.
+pub(crate) const MAX_DESCRIPTOR_BYTES: usize = 4092;
+
+/// How many times the LCD is polled for the end of a transfer before giving up.
+///
+/// Clearing LCD_START takes effect once the word being sent is finished, so this is far longer
+/// than stopping should ever take.
+pub(crate) const TRANS_DONE_POLL_LIMIT: u32 = 100_000;
+
+/// The register and pin accesses needed to stop and restart the LCD peripheral.
+///
+/// `Esp32s3Dma` implements this on the hardware, so that the sequences using it can be tested
+/// on the host.
+pub(crate) trait LcdControl {
+    /// Set LCD_START (along with LCD_UPDATE), or clear it.
+    fn set_start(&mut self, start: bool);
+
+    /// Check if LCD_TRANS_DONE_INT_RAW is set, clearing it if it is.
+    fn take_trans_done(&mut self) -> bool;
+
+    /// Hold the OE pin high, blanking the panel whatever the LCD is sending, or give the pin back
+    /// to the LCD.
+    fn set_blanked(&mut self, blanked: bool);
+
+    /// Reset the LCD and its async FIFO, clear LCD_TRANS_DONE_INT_RAW, and reinitialize the DMA
+    /// channel.
+    fn reset(&mut self);
+}
+
+/// Poll `done` until it returns true, giving up after `limit` polls.
+fn wait_until(limit: u32, mut done: impl FnMut() -> bool) -> bool {
+    (0..limit).any(|_| done())
+}
+
+/// Stop the LCD sending data, leaving the panel blanked.
+///
+/// The LCD stops after the word it is sending, which can be in the middle of a lit column, so
+/// the OE pin is taken over first. Returns `false` if the LCD didn't report that it stopped
+/// within `TRANS_DONE_POLL_LIMIT` polls.
+pub(crate) fn stop(lcd: &mut impl LcdControl) -> bool {
+    lcd.set_blanked(true);
+    lcd.set_start(false);
+    wait_until(TRANS_DONE_POLL_LIMIT, || lcd.take_trans_done())
+}
+
+/// Start the LCD sending data, then give the OE pin back to it.
+pub(crate) fn start(lcd: &mut impl LcdControl) {
+    lcd.set_start(true);
+    lcd.set_blanked(false);
+}
+
+/// Reset a stopped (or wedged) LCD so that it can be started again, leaving the panel blanked.
+pub(crate) fn reset(lcd: &mut impl LcdControl) {
+    lcd.set_blanked(true);
+    // Make sure nothing is being sent while the peripheral is reset.
+    lcd.set_start(false);
+    lcd.reset();
+}
+
+/// The value of the LCD_BYTE_ORDER bit in LCD_USER for a byte order.
+///
+/// When the bit is set, the two bytes of each word are swapped before being sent out.
+pub const fn lcd_byte_order_bit(byte_order: ByteOrder) -> bool {
+    match byte_order {
+        ByteOrder::LittleEndian => false,
+        ByteOrder::BigEndian => true,
+    }
+}
+
+// The offsets of the fields set in LCD_MISC.
+const LCD_AFIFO_THRESHOLD_NUM_SHIFT: u32 = 1;
+const LCD_VFK_CYCLELEN_SHIFT: u32 = 6;
+const LCD_VBK_CYCLELEN_SHIFT: u32 = 12;
+const LCD_BK_EN_SHIFT: u32 = 26;
+
+/// The value of the LCD_MISC register for a configuration.
+///
+/// This sets the threshold for the async TX FIFO full event (5 bits), the setup and hold cycles
+/// (6 and 13 bits, each stored as one less than the number of cycles), and the blank region
+/// when data is sent out. The blank region works around a design flaw in the ESP32-S3:
+/// <https://esp32.com/viewtopic.php?t=24459&start=60#p91835>
+///
+/// Everything else is left cleared: data isn't automatically framed, the async FIFO isn't reset,
+/// and none of the clock edge options are used.
+pub fn lcd_misc_bits<
+    const WIDTH: usize,
+    const HEIGHT: usize,
+    const CHAIN_LENGTH: usize,
+    const COLOR_DEPTH: usize,
+    const PER_FRAME_DENOMINATOR: u8,
+>(
+    config: &MatrixConfig<WIDTH, HEIGHT, CHAIN_LENGTH, COLOR_DEPTH, PER_FRAME_DENOMINATOR>,
+) -> u32 {
+    ((config.afifo_threshold() as u32) << LCD_AFIFO_THRESHOLD_NUM_SHIFT)
+        | ((config.setup_cycles() as u32 - 1) << LCD_VFK_CYCLELEN_SHIFT)
+        | ((config.hold_cycles() as u32 - 1) << LCD_VBK_CYCLELEN_SHIFT)
+        | ((config.blank_region_workaround() as u32) << LCD_BK_EN_SHIFT)
+}
+
+/// Where the frame buffer given to `start()` is placed in memory.
+///
+/// Large frame buffers may not fit in internal RAM, but can be put in PSRAM instead. The DMA
+/// controller reads PSRAM directly, bypassing the cache, so the cache needs to be written back to
+/// PSRAM before the DMA will see any changes.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum BufferPlacement {
+    /// Internal SRAM, which is always visible to the DMA controller.
+    #[default]
+    Internal,
+
+    /// External PSRAM, accessed through the data cache.
+    ///
+    /// Every segment of the frame buffer has to start and end on a `PSRAM_BLOCK_SIZE` boundary.
+    Psram,
+}
+
+impl BufferPlacement {
+    /// The range of internal SRAM addresses on the data bus that the DMA controller can reach.
+    const INTERNAL_RANGE: core::ops::Range<usize> = 0x3FC8_8000..0x3FD0_0000;
+
+    /// The range of addresses PSRAM is mapped to on the data bus.
+    const PSRAM_RANGE: core::ops::Range<usize> = 0x3C00_0000..0x3E00_0000;
+
+    /// The size in bytes of the blocks the DMA controller reads from PSRAM in.
+    ///
+    /// This is the EDMA external memory block size set when a transfer from PSRAM is started.
+    pub const PSRAM_BLOCK_SIZE: usize = 16;
+
+    fn address_range(&self) -> core::ops::Range<usize> {
+        match self {
+            Self::Internal => Self::INTERNAL_RANGE,
+            Self::Psram => Self::PSRAM_RANGE,
+        }
+    }
+
+    /// Find the placement for a given address, or `None` if the DMA controller can't reach it.
+    pub fn of_address(address: usize) -> Option<Self> {
+        [Self::Internal, Self::Psram]
+            .into_iter()
+            .find(|placement| placement.address_range().contains(&address))
+    }
+
+    /// Check if the `len` bytes starting at `address` are entirely within this placement.
+    pub fn contains(&self, address: usize, len: usize) -> bool {
+        let range = self.address_range();
+        address >= range.start && address.saturating_add(len) <= range.end
+    }
+
+    /// Check if the `len` bytes starting at `address` are aligned well enough for the DMA
+    /// controller to read them from this placement.
+    ///
+    /// Internal RAM has no requirements, but PSRAM is read in whole blocks, so both the start and
+    /// the length need to be a multiple of `PSRAM_BLOCK_SIZE`.
+    pub fn is_aligned(&self, address: usize, len: usize) -> bool {
+        match self {
+            Self::Internal => true,
+            Self::Psram => {
+                address.is_multiple_of(Self::PSRAM_BLOCK_SIZE)
+                    && len.is_multiple_of(Self::PSRAM_BLOCK_SIZE)
+            }
+        }
+    }
+}
+
+/// The number of DMA descriptors needed to send a full frame.
+///
+/// Each descriptor can point to at most 4092 bytes, and every plane is sent `2^plane` times for
+/// each scanline.
+pub const fn min_descriptor_count(
+    words_per_plane: usize,
+    color_depth: usize,
+    scanlines_per_frame: usize,
+) -> usize {
+    ((words_per_plane * core::mem::size_of::<u16>()).div_ceil(MAX_DESCRIPTOR_BYTES))
+        * ((1 << color_depth) - 1)
+        * scanlines_per_frame
+}
+
+/// The number of DMA descriptors needed to send a full frame with ghost reduction enabled.
+///
+/// This is `min_descriptor_count()` plus one descriptor for the blank segment after every segment.
+pub const fn ghost_reduction_descriptor_count(
+    words_per_plane: usize,
+    color_depth: usize,
+    scanlines_per_frame: usize,
+) -> usize {
+    min_descriptor_count(words_per_plane, color_depth, scanlines_per_frame)
+        + ((1 << color_depth) - 1) * scanlines_per_frame
+}
+
+/// The number of DMA descriptors needed to send a full frame with address transitions enabled.
+///
+/// This is `min_descriptor_count()` plus one descriptor for the blank segment before each
+/// scanline.
+pub const fn address_transition_descriptor_count(
+    words_per_plane: usize,
+    color_depth: usize,
+    scanlines_per_frame: usize,
+) -> usize {
+    min_descriptor_count(words_per_plane, color_depth, scanlines_per_frame) + scanlines_per_frame
+}
+
+/// The number of DMA descriptors needed to send a full frame, with or without ghost reduction
+/// and address transitions.
+pub const fn frame_descriptor_count(
+    words_per_plane: usize,
+    color_depth: usize,
+    scanlines_per_frame: usize,
+    ghost_reduction: bool,
+    address_transition: bool,
+) -> usize {
+    let mut count = if ghost_reduction {
+        ghost_reduction_descriptor_count(words_per_plane, color_depth, scanlines_per_frame)
+    } else {
+        min_descriptor_count(words_per_plane, color_depth, scanlines_per_frame)
+    };
+    if address_transition {
+        count += scanlines_per_frame;
+    }
+    count
+}
+
+/// The number of DMA descriptors needed to send the given segments, with each segment split into
+/// as few descriptors as possible.
+pub fn descriptors_for_segments<'a>(segments: impl Iterator<Item = &'a [u16]>) -> usize {
+    segments
+        .map(|segment| core::mem::size_of_val(segment).div_ceil(MAX_DESCRIPTOR_BYTES))
+        .sum()
+}
+
+/// Map the descriptor address reported by the DMA channel to one of the transfer's descriptors.
+pub fn descriptor_fault<D>(descriptors: &[D], address: usize) -> DescriptorFault {
+    DescriptorFault::locate(
+        descriptors.as_ptr() as usize,
+        core::mem::size_of::<D>(),
+        descriptors.len(),
+        address,
+    )
+}
+
+/// The number of DMA descriptors needed for a matrix geometry, as a constant.
+///
+/// The arguments are the same as for `declare_frame_buffer!`: width, height, color depth, chain
+/// length, and the per-frame denominator, optionally followed by `ghost_reduction: true` and
+/// `address_transition: true` if those will be enabled on the frame buffer. This is the length of
+/// the array declared by `static_descriptors!`.
+#[macro_export]
+macro_rules! descriptor_count {
+    (@or ; $default:expr) => {
+        $default
+    };
+    (@or $value:expr ; $default:expr) => {
+        $value
+    };
+    (
+        $width:literal,
+        $height:literal,
+        $color_depth:literal,
+        $chain_length:literal,
+        $per_frame_denominator:literal
+        $(, ghost_reduction: $ghost_reduction:expr)?
+        $(, address_transition: $address_transition:expr)?
+        $(,)?
+    ) => {
+        $crate::dma::lcd_cam::frame_descriptor_count(
+            // NOTE: the "2" here is the value of PIXELS_PER_CLOCK
+            $width * $chain_length * $height / $per_frame_denominator / 2,
+            $color_depth,
+            $height / ($height / $per_frame_denominator),
+            $crate::descriptor_count!(@or $($ghost_reduction)? ; false),
+            $crate::descriptor_count!(@or $($address_transition)? ; false),
+        )
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::buffer::FrameBuffer;
+    use crate::matrix_word::{control_word, MatrixWord};
+
+    type TestFrameBuffer = FrameBuffer<64, 32, 1, 8, 16, { 64 * 32 / 16 / 2 }, 16>;
+
+    #[test]
+    fn descriptors_needed_small_panel() {
+        let frame_buffer = TestFrameBuffer::new();
+        // Every plane fits in a single descriptor.
+        let segment_count = frame_buffer.buffer_iter().count();
+        assert_eq!(
+            descriptors_for_segments(frame_buffer.buffer_iter()),
+            segment_count
+        );
+        assert_eq!(segment_count, min_descriptor_count(64, 8, 16));
+    }
+
+    #[test]
+    fn descriptors_needed_ghost_reduction() {
+        let mut frame_buffer = TestFrameBuffer::new();
+        frame_buffer.set_ghost_reduction(true);
+        assert_eq!(
+            descriptors_for_segments(frame_buffer.buffer_iter()),
+            ghost_reduction_descriptor_count(64, 8, 16)
+        );
+        assert_eq!(
+            ghost_reduction_descriptor_count(64, 8, 16),
+            2 * min_descriptor_count(64, 8, 16)
+        );
+    }
+
+    #[test]
+    fn descriptors_needed_address_transition() {
+        let mut frame_buffer = TestFrameBuffer::new();
+        frame_buffer.set_address_transition(true);
+        assert_eq!(
+            descriptors_for_segments(frame_buffer.buffer_iter()),
+            address_transition_descriptor_count(64, 8, 16)
+        );
+        assert_eq!(
+            address_transition_descriptor_count(64, 8, 16),
+            min_descriptor_count(64, 8, 16) + 16
+        );
+    }
+
+    #[test]
+    fn descriptors_for_large_segments() {
+        // A chain of 16 64x64 panels at 1/16 scan has 2048 words (4096 bytes) per plane, just
+        // over the limit of a single descriptor.
+        let frame_buffer = FrameBuffer::<64, 64, 16, 2, 16, 2048, 16>::new();
+        assert_eq!(
+            descriptors_for_segments(frame_buffer.buffer_iter()),
+            2 * 3 * 16
+        );
+        assert_eq!(min_descriptor_count(2048, 2, 16), 2 * 3 * 16);
+        // Exactly 4092 bytes still fits in one.
+        let plane = [0u16; 2046];
+        assert_eq!(descriptors_for_segments(core::iter::once(&plane[..])), 1);
+    }
+
+    #[test]
+    fn descriptor_count_macro() {
+        const COUNT: usize = crate::descriptor_count!(64, 32, 8, 1, 16);
+        assert_eq!(COUNT, min_descriptor_count(64, 8, 16));
+        let frame_buffer = TestFrameBuffer::new();
+        assert_eq!(COUNT, descriptors_for_segments(frame_buffer.buffer_iter()));
+        // Chained panels at 1/8 scan.
+        assert_eq!(
+            crate::descriptor_count!(64, 32, 6, 4, 8),
+            min_descriptor_count(64 * 4 * 32 / 8 / 2, 6, 8)
+        );
+    }
+
+    #[test]
+    fn descriptor_count_macro_ghost_reduction() {
+        const COUNT: usize = crate::descriptor_count!(64, 32, 8, 1, 16, ghost_reduction: true);
+        let mut frame_buffer = TestFrameBuffer::new();
+        frame_buffer.set_ghost_reduction(true);
+        assert_eq!(COUNT, descriptors_for_segments(frame_buffer.buffer_iter()));
+        assert_eq!(
+            crate::descriptor_count!(64, 32, 8, 1, 16, ghost_reduction: false),
+            crate::descriptor_count!(64, 32, 8, 1, 16)
+        );
+    }
+
+    #[test]
+    fn descriptor_count_macro_address_transition() {
+        const COUNT: usize = crate::descriptor_count!(64, 32, 8, 1, 16, address_transition: true);
+        let mut frame_buffer = TestFrameBuffer::new();
+        frame_buffer.set_address_transition(true);
+        assert_eq!(COUNT, descriptors_for_segments(frame_buffer.buffer_iter()));
+        assert_eq!(COUNT, address_transition_descriptor_count(64, 8, 16));
+
+        const BOTH: usize = crate::descriptor_count!(
+            64,
+            32,
+            8,
+            1,
+            16,
+            ghost_reduction: true,
+            address_transition: true,
+        );
+        frame_buffer.set_ghost_reduction(true);
+        assert_eq!(BOTH, descriptors_for_segments(frame_buffer.buffer_iter()));
+    }
+
+    type TestConfig = MatrixConfig<64, 32, 1, 8, 16>;
+
+    /// An LCD sending `words`, which finishes after `stop_polls` polls once LCD_START is cleared
+    /// (or never, for `None`).
+    struct FakeLcd<'a> {
+        words: &'a [u16],
+        position: usize,
+        running: bool,
+        blanked: bool,
+        stop_polls: Option<u32>,
+        polls: u32,
+    }
+
+    impl<'a> FakeLcd<'a> {
+        fn new(words: &'a [u16], position: usize, stop_polls: Option<u32>) -> Self {
+            Self {
+                words,
+                position,
+                running: true,
+                blanked: false,
+                stop_polls,
+                polls: 0,
+            }
+        }
+
+        /// The word seen by the panel: the last word sent, with OE forced high while blanked.
+        fn output(&self) -> u16 {
+            let word = self.words[self.position];
+            if self.blanked {
+                word | control_word(0, false, true)
+            } else {
+                word
+            }
+        }
+    }
+
+    impl LcdControl for FakeLcd<'_> {
+        fn set_start(&mut self, start: bool) {
+            self.running = start;
+            self.polls = 0;
+        }
+
+        fn take_trans_done(&mut self) -> bool {
+            if self.running {
+                return false;
+            }
+            self.polls += 1;
+            self.stop_polls
+                .is_some_and(|stop_polls| self.polls == stop_polls)
+        }
+
+        fn set_blanked(&mut self, blanked: bool) {
+            self.blanked = blanked;
+        }
+
+        fn reset(&mut self) {
+            // Resetting clears whatever kept the LCD from finishing.
+            self.stop_polls = Some(1);
+            self.position = 0;
+            self.polls = 0;
+        }
+    }
+
+    /// The first plane of a fully lit frame buffer, and the index of a lit word in it.
+    fn lit_plane(frame_buffer: &mut TestFrameBuffer) -> (&[u16], usize) {
+        let config = TestConfig::default();
+        frame_buffer.configure(config.latch_blanking_count(), &config.lit_columns(255));
+        let plane = frame_buffer.plane(0, 0).unwrap();
+        let lit = plane.iter().position(|word| !word.output_enable()).unwrap();
+        (plane, lit)
+    }
+
+    #[test]
+    fn stop_mid_column_blanks() {
+        let mut frame_buffer = TestFrameBuffer::new();
+        let (plane, lit) = lit_plane(&mut frame_buffer);
+        let mut lcd = FakeLcd::new(plane, lit, Some(3));
+        assert!(!lcd.output().output_enable());
+        assert!(stop(&mut lcd));
+        // The LCD was stopped on a lit word, but the panel is still blanked.
+        assert!(!lcd.running);
+        assert!(lcd.output().output_enable());
+        assert_eq!(lcd.output() & !control_word(0, false, true), plane[lit]);
+        start(&mut lcd);
+        assert!(lcd.running);
+        assert_eq!(lcd.output(), plane[lit]);
+    }
+
+    #[test]
+    fn stop_gives_up() {
+        let mut frame_buffer = TestFrameBuffer::new();
+        let (plane, lit) = lit_plane(&mut frame_buffer);
+        let mut lcd = FakeLcd::new(plane, lit, None);
+        assert!(!stop(&mut lcd));
+        assert_eq!(lcd.polls, TRANS_DONE_POLL_LIMIT);
+        assert!(lcd.output().output_enable());
+    }
+
+    #[test]
+    fn reset_after_stop_timeout() {
+        let mut frame_buffer = TestFrameBuffer::new();
+        let (plane, lit) = lit_plane(&mut frame_buffer);
+        let mut lcd = FakeLcd::new(plane, lit, None);
+        assert!(!stop(&mut lcd));
+        reset(&mut lcd);
+        assert!(!lcd.running);
+        assert!(lcd.output().output_enable());
+        // The LCD can be started and stopped normally again.
+        start(&mut lcd);
+        assert!(lcd.running);
+        assert!(!lcd.blanked);
+        lcd.position = lit;
+        assert!(stop(&mut lcd));
+        assert!(lcd.output().output_enable());
+    }
+
+    #[test]
+    fn lcd_misc_blank_region() {
+        // With the defaults only the blank region, and the setup and hold cycles are set.
+        let config = TestConfig::default();
+        assert_eq!(lcd_misc_bits(&config), 1 << 26 | 1 << 12 | 1 << 6);
+        let config = config.with_blank_region_workaround(false);
+        assert_eq!(lcd_misc_bits(&config), 1 << 12 | 1 << 6);
+    }
+
+    #[test]
+    fn lcd_misc_setup_hold_cycles() {
+        let config = TestConfig::default()
+            .with_blank_region_workaround(false)
+            .with_setup_cycles(5)
+            .with_hold_cycles(300);
+        assert_eq!(lcd_misc_bits(&config), 299 << 12 | 4 << 6);
+        // The largest values fill their fields without spilling into their neighbours.
+        let config = config.with_setup_cycles(64).with_hold_cycles(8192);
+        assert_eq!(lcd_misc_bits(&config), 0x1FFF << 12 | 0x3F << 6);
+    }
+
+    #[test]
+    fn lcd_misc_afifo_threshold() {
+        let config = TestConfig::default()
+            .with_blank_region_workaround(false)
+            .with_setup_cycles(1)
+            .with_hold_cycles(1);
+        assert_eq!(lcd_misc_bits(&config), 0);
+        let config = config.with_afifo_threshold(11);
+        assert_eq!(lcd_misc_bits(&config), 11 << 1);
+        // The threshold is clamped to the 5 bits of its field, and bit 0 stays clear.
+        let config = config.with_afifo_threshold(u8::MAX);
+        assert_eq!(lcd_misc_bits(&config), 0x1F << 1);
+    }
+
+    #[test]
+    fn buffer_placement_of_address() {
+        assert_eq!(
+            BufferPlacement::of_address(0x3FC9_0000),
+            Some(BufferPlacement::Internal)
+        );
+        assert_eq!(
+            BufferPlacement::of_address(0x3C10_0000),
+            Some(BufferPlacement::Psram)
+        );
+        // Instruction bus addresses aren't reachable by DMA.
+        assert_eq!(BufferPlacement::of_address(0x4037_0000), None);
+    }
+
+    #[test]
+    fn buffer_placement_contains() {
+        assert!(BufferPlacement::Internal.contains(0x3FC9_0000, 0x1000));
+        assert!(!BufferPlacement::Internal.contains(0x3FCF_F000, 0x2000));
+        assert!(!BufferPlacement::Psram.contains(0x3FC9_0000, 0x1000));
+        assert!(BufferPlacement::Psram.contains(0x3D00_0000, 0x10_0000));
+        // A length that would wrap around the address space isn't contained.
+        assert!(!BufferPlacement::Psram.contains(0x3D00_0000, usize::MAX));
+    }
+
+    #[test]
+    fn buffer_placement_alignment() {
+        assert!(BufferPlacement::Internal.is_aligned(0x3FC9_0002, 6));
+        assert!(BufferPlacement::Psram.is_aligned(0x3D00_0000, 128));
+        assert!(BufferPlacement::Psram.is_aligned(0x3D00_0010, 32));
+        assert!(!BufferPlacement::Psram.is_aligned(0x3D00_0008, 32));
+        assert!(!BufferPlacement::Psram.is_aligned(0x3D00_0000, 24));
+    }
+
+    #[test]
+    fn frame_buffer_segments_fill_psram_blocks() {
+        // Every segment of a frame buffer is a whole number of blocks long, so a frame buffer
+        // placed on a block boundary in PSRAM only needs its segments to start aligned.
+        let mut frame_buffer = TestFrameBuffer::new();
+        frame_buffer.set_ghost_reduction(true);
+        frame_buffer.set_address_transition(true);
+        assert!(frame_buffer.buffer_iter().all(|segment| {
+            BufferPlacement::Psram.is_aligned(0x3D00_0000, core::mem::size_of_val(segment))
+        }));
+    }
+
+    #[test]
+    fn descriptor_fault_from_address() {
+        // The same size as the ESP32-S3 DMA descriptors.
+        let descriptors = [[0u32; 3]; 4];
+        let address = &descriptors[3] as *const [u32; 3] as usize;
+        assert_eq!(
+            descriptor_fault(&descriptors, address),
+            DescriptorFault {
+                address,
+                index: Some(3)
+            }
+        );
+        // Addresses inside a descriptor or past the end don't match one.
+        assert_eq!(descriptor_fault(&descriptors, address + 4).index, None);
+        assert_eq!(descriptor_fault(&descriptors, address + 12).index, None);
+        assert_eq!(descriptor_fault(&descriptors, 0).index, None);
+    }
+
+    #[test]
+    fn byte_order_register_bit() {
+        let mut config = MatrixConfig::<64, 32, 1, 8, 16>::default();
+        assert!(!lcd_byte_order_bit(config.byte_order()));
+        config.set_byte_order(ByteOrder::BigEndian);
+        assert!(lcd_byte_order_bit(config.byte_order()));
+    }
+}
@@ -2,8 +2,11 @@ use super::buffer::FrameBuffer;
 
 #[cfg(feature = "esp32s3")]
 pub mod esp32s3;
+// Parts of this are only used by `esp32s3` outside of the tests.
+#[cfg_attr(not(feature = "esp32s3"), allow(dead_code))]
+pub mod lcd_cam;
+pub mod null;
 
-#[derive(Debug)]
 pub struct Transfer<
     'a,
     M,
@@ -36,6 +39,27 @@ pub struct Transfer<
         WORDS_PER_PLANE,
         SCANLINES_PER_FRAME,
     >,
+
+    // The number of words sent for each frame, as the DMA was set up when the transfer started.
+    frame_words: usize,
+
+    paused: bool,
+
+    overrun_detector: OverrunDetector,
+
+    frame_hook: Option<
+        &'a mut dyn FnMut(
+            &mut FrameBuffer<
+                WIDTH,
+                HEIGHT,
+                CHAIN_LENGTH,
+                COLOR_DEPTH,
+                PER_FRAME_DENOMINATOR,
+                WORDS_PER_PLANE,
+                SCANLINES_PER_FRAME,
+            >,
+        ),
+    >,
 }
 
 impl<
@@ -71,6 +95,157 @@ where
         SCANLINES_PER_FRAME,
     >,
 {
+    pub(crate) fn new(
+        matrix_dma: M,
+        frame_buffer: &'a mut FrameBuffer<
+            WIDTH,
+            HEIGHT,
+            CHAIN_LENGTH,
+            COLOR_DEPTH,
+            PER_FRAME_DENOMINATOR,
+            WORDS_PER_PLANE,
+            SCANLINES_PER_FRAME,
+        >,
+    ) -> Self {
+        let frame_words = frame_buffer
+            .buffer_iter()
+            .map(|segment| segment.len())
+            .sum();
+        Self {
+            matrix_dma,
+            frame_buffer,
+            frame_words,
+            paused: false,
+            overrun_detector: OverrunDetector::new(),
+            frame_hook: None,
+        }
+    }
+
+    /// The frame buffer being sent, for changes that have to be made while the transfer runs.
+    pub(crate) fn frame_buffer_mut(
+        &mut self,
+    ) -> &mut FrameBuffer<
+        WIDTH,
+        HEIGHT,
+        CHAIN_LENGTH,
+        COLOR_DEPTH,
+        PER_FRAME_DENOMINATOR,
+        WORDS_PER_PLANE,
+        SCANLINES_PER_FRAME,
+    > {
+        self.frame_buffer
+    }
+
+    /// Split a stopped transfer back into the DMA and the frame buffer, for `MatrixDma::stop()`
+    /// implementations.
+    pub(crate) fn into_parts(
+        self,
+    ) -> (
+        M,
+        &'a mut FrameBuffer<
+            WIDTH,
+            HEIGHT,
+            CHAIN_LENGTH,
+            COLOR_DEPTH,
+            PER_FRAME_DENOMINATOR,
+            WORDS_PER_PLANE,
+            SCANLINES_PER_FRAME,
+        >,
+    ) {
+        (self.matrix_dma, self.frame_buffer)
+    }
+
+    /// The number of words sent to the matrix for each frame.
+    ///
+    /// This is the length the DMA was set up with when the transfer was started.
+    pub fn current_frame_words(&self) -> usize {
+        self.frame_words
+    }
+
+    /// Stop sending data to the matrix, without tearing down the DMA transfer.
+    ///
+    /// The panel is blanked while paused. This is cheaper than `stop()` followed by `start()`, as
+    /// the DMA stays set up and no registers need to be reconfigured.
+    pub fn pause(&mut self) {
+        if !self.paused {
+            self.matrix_dma.pause();
+            self.paused = true;
+            // No frames are sent while paused, so the next frame can't be timed.
+            self.overrun_detector.restart();
+        }
+    }
+
+    /// Resume sending data to the matrix after `pause()`.
+    pub fn resume(&mut self) {
+        if self.paused {
+            self.matrix_dma.resume();
+            self.paused = false;
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Set a function to be called with the frame buffer every time a frame has been sent.
+    ///
+    /// The hook is called from `poll()`, so `poll()` needs to be called at least once a frame for
+    /// the hook to be run once per frame. The DMA transfer is still running while the hook runs, so
+    /// any changes it makes to the frame buffer may be partially visible for one frame. Keep the
+    /// changes small, or only change parts of the frame buffer that have already been sent.
+    pub fn set_frame_hook(
+        &mut self,
+        frame_hook: &'a mut dyn FnMut(
+            &mut FrameBuffer<
+                WIDTH,
+                HEIGHT,
+                CHAIN_LENGTH,
+                COLOR_DEPTH,
+                PER_FRAME_DENOMINATOR,
+                WORDS_PER_PLANE,
+                SCANLINES_PER_FRAME,
+            >,
+        ),
+    ) {
+        self.frame_hook = Some(frame_hook);
+    }
+
+    pub fn clear_frame_hook(&mut self) {
+        self.frame_hook = None;
+    }
+
+    /// Remove the frame hook, returning it so that it can be set on another transfer.
+    #[allow(clippy::type_complexity)]
+    pub fn take_frame_hook(
+        &mut self,
+    ) -> Option<
+        &'a mut dyn FnMut(
+            &mut FrameBuffer<
+                WIDTH,
+                HEIGHT,
+                CHAIN_LENGTH,
+                COLOR_DEPTH,
+                PER_FRAME_DENOMINATOR,
+                WORDS_PER_PLANE,
+                SCANLINES_PER_FRAME,
+            >,
+        ),
+    > {
+        self.frame_hook.take()
+    }
+
+    /// Check if a frame has been sent since the last time this was called, running the frame hook
+    /// if it has.
+    pub fn poll(&mut self) -> bool {
+        let frame_done = self.matrix_dma.poll_frame_done();
+        if frame_done {
+            if let Some(frame_hook) = self.frame_hook.as_mut() {
+                frame_hook(self.frame_buffer);
+            }
+        }
+        frame_done
+    }
+
     pub fn stop(
         self,
     ) -> Result<
@@ -102,6 +277,692 @@ where
     > {
         M::stop(self)
     }
+
+    /// Set the longest a frame should take to send, in microseconds, to detect DMA underruns.
+    ///
+    /// Frames are only timed by `poll_timed()`.
+    pub fn set_frame_time_limit(&mut self, limit_micros: u64) {
+        self.overrun_detector.set_limit(Some(limit_micros));
+    }
+
+    pub fn clear_frame_time_limit(&mut self) {
+        self.overrun_detector.set_limit(None);
+    }
+
+    /// The same as `poll()`, but also times the frame that was sent with `now_micros`, the current
+    /// time from a free running timer in microseconds.
+    ///
+    /// To be accurate this needs to be called as soon as a frame has been sent, such as from the
+    /// DMA's done interrupt.
+    pub fn poll_timed(&mut self, now_micros: u64) -> bool {
+        let frame_done = self.poll();
+        if frame_done {
+            self.overrun_detector.frame_done(now_micros);
+        }
+        frame_done
+    }
+
+    /// Check if the last frame timed by `poll_timed()` took longer than the limit set with
+    /// `set_frame_time_limit()`.
+    ///
+    /// A frame that overran means the DMA couldn't keep the FIFO fed, which shows up as tearing or
+    /// flickering.
+    pub fn last_frame_overran(&self) -> bool {
+        self.overrun_detector.last_frame_overran()
+    }
+
+    /// Stop the DMA transfer and immediately start it again with the same frame buffer.
+    ///
+    /// The DMA descriptors stay with the `MatrixDma` implementation, so they are reused for the
+    /// new transfer.
+    pub fn restart(
+        mut self,
+    ) -> Result<
+        Self,
+        (
+            M::Error,
+            M,
+            &'a mut FrameBuffer<
+                WIDTH,
+                HEIGHT,
+                CHAIN_LENGTH,
+                COLOR_DEPTH,
+                PER_FRAME_DENOMINATOR,
+                WORDS_PER_PLANE,
+                SCANLINES_PER_FRAME,
+            >,
+        ),
+    > {
+        let frame_hook = self.take_frame_hook();
+        let mut overrun_detector = self.overrun_detector;
+        overrun_detector.restart();
+        let (matrix_dma, frame_buffer) = self.stop()?;
+        // Safety: the frame buffer was already borrowed for 'a by the transfer being restarted, so
+        // the caller has upheld the requirements of `start_reference()` for this lifetime.
+        let mut transfer = unsafe { matrix_dma.start_reference(frame_buffer) }?;
+        transfer.frame_hook = frame_hook;
+        transfer.overrun_detector = overrun_detector;
+        Ok(transfer)
+    }
+}
+
+impl<
+        'a,
+        M,
+        const WIDTH: usize,
+        const HEIGHT: usize,
+        const CHAIN_LENGTH: usize,
+        const COLOR_DEPTH: usize,
+        const PER_FRAME_DENOMINATOR: u8,
+        const WORDS_PER_PLANE: usize,
+        const SCANLINES_PER_FRAME: usize,
+    > core::fmt::Debug
+    for Transfer<
+        'a,
+        M,
+        WIDTH,
+        HEIGHT,
+        CHAIN_LENGTH,
+        COLOR_DEPTH,
+        PER_FRAME_DENOMINATOR,
+        WORDS_PER_PLANE,
+        SCANLINES_PER_FRAME,
+    >
+where
+    M: MatrixDma<
+            WIDTH,
+            HEIGHT,
+            CHAIN_LENGTH,
+            COLOR_DEPTH,
+            PER_FRAME_DENOMINATOR,
+            WORDS_PER_PLANE,
+            SCANLINES_PER_FRAME,
+        > + core::fmt::Debug,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Transfer")
+            .field("matrix_dma", &self.matrix_dma)
+            .field("frame_buffer", &self.frame_buffer)
+            .field("frame_words", &self.frame_words)
+            .field("paused", &self.paused)
+            .field("overrun_detector", &self.overrun_detector)
+            .field("frame_hook", &self.frame_hook.is_some())
+            .finish()
+    }
+}
+
+/// Detects frames that took longer than expected to send, from the times each frame finished.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct OverrunDetector {
+    limit_micros: Option<u64>,
+
+    last_done: Option<u64>,
+
+    overran: bool,
+}
+
+impl OverrunDetector {
+    pub const fn new() -> Self {
+        Self {
+            limit_micros: None,
+            last_done: None,
+            overran: false,
+        }
+    }
+
+    /// Set the longest a frame should take, or `None` to stop detecting overruns.
+    pub fn set_limit(&mut self, limit_micros: Option<u64>) {
+        self.limit_micros = limit_micros;
+        self.overran = false;
+    }
+
+    /// Record that a frame finished at `now_micros`.
+    ///
+    /// The first frame after creating or restarting the detector can't be timed, as there is no
+    /// earlier frame to time it from. The timer is allowed to wrap around.
+    pub fn frame_done(&mut self, now_micros: u64) {
+        if let (Some(limit), Some(last_done)) = (self.limit_micros, self.last_done) {
+            self.overran = now_micros.wrapping_sub(last_done) > limit;
+        }
+        self.last_done = Some(now_micros);
+    }
+
+    /// Forget when the last frame finished, for when frames stop being sent for a while.
+    pub fn restart(&mut self) {
+        self.last_done = None;
+        self.overran = false;
+    }
+
+    pub fn last_frame_overran(&self) -> bool {
+        self.overran
+    }
+}
+
+/// Details of a DMA descriptor error.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DescriptorFault {
+    /// The descriptor address reported by the DMA channel.
+    pub address: usize,
+
+    /// The index of the descriptor at `address`, or `None` if the address isn't one of the
+    /// descriptors used for the transfer.
+    pub index: Option<usize>,
+}
+
+impl DescriptorFault {
+    /// Find which of `count` descriptors (each `descriptor_size` bytes, starting at `base`) is at
+    /// `address`.
+    pub fn locate(base: usize, descriptor_size: usize, count: usize, address: usize) -> Self {
+        let index = address
+            .checked_sub(base)
+            .filter(|offset| offset % descriptor_size == 0)
+            .map(|offset| offset / descriptor_size)
+            .filter(|index| *index < count);
+        Self { address, index }
+    }
+}
+
+/// Two signals assigned to the same GPIO.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PinCollision {
+    /// The GPIO number assigned to both signals.
+    pub pin: u8,
+
+    /// The name of the first signal assigned to `pin`.
+    pub first: &'static str,
+
+    /// The name of the second signal assigned to `pin`.
+    pub second: &'static str,
+}
+
+impl PinCollision {
+    /// Find the first GPIO assigned to more than one signal.
+    ///
+    /// `pins` has the name of each signal and the GPIO number assigned to it, or `None` if the
+    /// signal isn't used.
+    pub fn find(pins: &[(&'static str, Option<u8>)]) -> Option<Self> {
+        pins.iter()
+            .enumerate()
+            .filter_map(|(index, (name, pin))| Some((index, *name, (*pin)?)))
+            .find_map(|(index, first, pin)| {
+                pins[index + 1..]
+                    .iter()
+                    .find(|(_, other)| *other == Some(pin))
+                    .map(|(second, _)| Self { pin, first, second })
+            })
+    }
+}
+
+/// Which of the two transfers in a `DualTransfer` an error came from.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DualError<E1, E2> {
+    First(E1),
+    Second(E2),
+}
+
+/// Two transfers to separate chains of panels, started and stopped together so that both chains
+/// stay aligned to the same frame.
+pub struct DualTransfer<
+    'a,
+    M1,
+    M2,
+    const WIDTH: usize,
+    const HEIGHT: usize,
+    const CHAIN_LENGTH: usize,
+    const COLOR_DEPTH: usize,
+    const PER_FRAME_DENOMINATOR: u8,
+    const WORDS_PER_PLANE: usize,
+    const SCANLINES_PER_FRAME: usize,
+> where
+    M1: MatrixDma<
+        WIDTH,
+        HEIGHT,
+        CHAIN_LENGTH,
+        COLOR_DEPTH,
+        PER_FRAME_DENOMINATOR,
+        WORDS_PER_PLANE,
+        SCANLINES_PER_FRAME,
+    >,
+    M2: MatrixDma<
+        WIDTH,
+        HEIGHT,
+        CHAIN_LENGTH,
+        COLOR_DEPTH,
+        PER_FRAME_DENOMINATOR,
+        WORDS_PER_PLANE,
+        SCANLINES_PER_FRAME,
+    >,
+{
+    first: Transfer<
+        'a,
+        M1,
+        WIDTH,
+        HEIGHT,
+        CHAIN_LENGTH,
+        COLOR_DEPTH,
+        PER_FRAME_DENOMINATOR,
+        WORDS_PER_PLANE,
+        SCANLINES_PER_FRAME,
+    >,
+
+    second: Transfer<
+        'a,
+        M2,
+        WIDTH,
+        HEIGHT,
+        CHAIN_LENGTH,
+        COLOR_DEPTH,
+        PER_FRAME_DENOMINATOR,
+        WORDS_PER_PLANE,
+        SCANLINES_PER_FRAME,
+    >,
+
+    // Frames that have been reported as done by one transfer, but not the other yet.
+    first_frame_done: bool,
+    second_frame_done: bool,
+}
+
+impl<
+        'a,
+        M1,
+        M2,
+        const WIDTH: usize,
+        const HEIGHT: usize,
+        const CHAIN_LENGTH: usize,
+        const COLOR_DEPTH: usize,
+        const PER_FRAME_DENOMINATOR: u8,
+        const WORDS_PER_PLANE: usize,
+        const SCANLINES_PER_FRAME: usize,
+    >
+    DualTransfer<
+        'a,
+        M1,
+        M2,
+        WIDTH,
+        HEIGHT,
+        CHAIN_LENGTH,
+        COLOR_DEPTH,
+        PER_FRAME_DENOMINATOR,
+        WORDS_PER_PLANE,
+        SCANLINES_PER_FRAME,
+    >
+where
+    M1: MatrixDma<
+        WIDTH,
+        HEIGHT,
+        CHAIN_LENGTH,
+        COLOR_DEPTH,
+        PER_FRAME_DENOMINATOR,
+        WORDS_PER_PLANE,
+        SCANLINES_PER_FRAME,
+    >,
+    M2: MatrixDma<
+        WIDTH,
+        HEIGHT,
+        CHAIN_LENGTH,
+        COLOR_DEPTH,
+        PER_FRAME_DENOMINATOR,
+        WORDS_PER_PLANE,
+        SCANLINES_PER_FRAME,
+    >,
+{
+    /// Start both transfers, the first immediately followed by the second.
+    ///
+    /// If the second transfer fails to start, the first is stopped again so that neither is
+    /// running.
+    ///
+    /// # Safety
+    ///
+    /// The same requirements as `MatrixDma::start_reference()` apply to both frame buffers.
+    #[allow(clippy::type_complexity)]
+    pub unsafe fn start_reference(
+        first_dma: M1,
+        first_frame_buffer: &'a mut FrameBuffer<
+            WIDTH,
+            HEIGHT,
+            CHAIN_LENGTH,
+            COLOR_DEPTH,
+            PER_FRAME_DENOMINATOR,
+            WORDS_PER_PLANE,
+            SCANLINES_PER_FRAME,
+        >,
+        second_dma: M2,
+        second_frame_buffer: &'a mut FrameBuffer<
+            WIDTH,
+            HEIGHT,
+            CHAIN_LENGTH,
+            COLOR_DEPTH,
+            PER_FRAME_DENOMINATOR,
+            WORDS_PER_PLANE,
+            SCANLINES_PER_FRAME,
+        >,
+    ) -> Result<
+        Self,
+        (
+            DualError<M1::Error, M2::Error>,
+            (
+                M1,
+                &'a mut FrameBuffer<
+                    WIDTH,
+                    HEIGHT,
+                    CHAIN_LENGTH,
+                    COLOR_DEPTH,
+                    PER_FRAME_DENOMINATOR,
+                    WORDS_PER_PLANE,
+                    SCANLINES_PER_FRAME,
+                >,
+            ),
+            (
+                M2,
+                &'a mut FrameBuffer<
+                    WIDTH,
+                    HEIGHT,
+                    CHAIN_LENGTH,
+                    COLOR_DEPTH,
+                    PER_FRAME_DENOMINATOR,
+                    WORDS_PER_PLANE,
+                    SCANLINES_PER_FRAME,
+                >,
+            ),
+        ),
+    > {
+        let first = match first_dma.start_reference(first_frame_buffer) {
+            Ok(first) => first,
+            Err((err, first_dma, first_frame_buffer)) => {
+                return Err((
+                    DualError::First(err),
+                    (first_dma, first_frame_buffer),
+                    (second_dma, second_frame_buffer),
+                ))
+            }
+        };
+        match second_dma.start_reference(second_frame_buffer) {
+            Ok(second) => Ok(Self {
+                first,
+                second,
+                first_frame_done: false,
+                second_frame_done: false,
+            }),
+            Err((err, second_dma, second_frame_buffer)) => {
+                let first = match first.stop() {
+                    Ok(first) => first,
+                    Err((_, first_dma, first_frame_buffer)) => (first_dma, first_frame_buffer),
+                };
+                Err((
+                    DualError::Second(err),
+                    first,
+                    (second_dma, second_frame_buffer),
+                ))
+            }
+        }
+    }
+
+    pub fn first(
+        &mut self,
+    ) -> &mut Transfer<
+        'a,
+        M1,
+        WIDTH,
+        HEIGHT,
+        CHAIN_LENGTH,
+        COLOR_DEPTH,
+        PER_FRAME_DENOMINATOR,
+        WORDS_PER_PLANE,
+        SCANLINES_PER_FRAME,
+    > {
+        &mut self.first
+    }
+
+    pub fn second(
+        &mut self,
+    ) -> &mut Transfer<
+        'a,
+        M2,
+        WIDTH,
+        HEIGHT,
+        CHAIN_LENGTH,
+        COLOR_DEPTH,
+        PER_FRAME_DENOMINATOR,
+        WORDS_PER_PLANE,
+        SCANLINES_PER_FRAME,
+    > {
+        &mut self.second
+    }
+
+    /// Check if both transfers have sent a frame since the last time this returned `true`.
+    ///
+    /// Each transfer's frame hook is run as soon as that transfer finishes its frame.
+    pub fn poll(&mut self) -> bool {
+        self.first_frame_done |= self.first.poll();
+        self.second_frame_done |= self.second.poll();
+        if self.first_frame_done && self.second_frame_done {
+            self.first_frame_done = false;
+            self.second_frame_done = false;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Show new frame buffers on both chains, stopping both transfers and starting them again
+    /// together so that they stay aligned to the same frame.
+    ///
+    /// The frame buffers that were being shown are returned, and the frame hooks of both transfers
+    /// are kept. If either transfer fails to stop or start, both are left stopped, and the error
+    /// is returned with each DMA and the frame buffer it was last given, followed by the two frame
+    /// buffers neither DMA has (the new ones if stopping failed, the old ones if starting failed).
+    ///
+    /// # Safety
+    ///
+    /// The same requirements as `MatrixDma::start_reference()` apply to both new frame buffers.
+    /// In particular, the returned `DualTransfer` cannot be `core::mem::forget()`-ten while it is
+    /// running, unless the frame buffers are borrowed for `'static`.
+    #[allow(clippy::type_complexity)]
+    pub unsafe fn swap(
+        mut self,
+        first_frame_buffer: &'a mut FrameBuffer<
+            WIDTH,
+            HEIGHT,
+            CHAIN_LENGTH,
+            COLOR_DEPTH,
+            PER_FRAME_DENOMINATOR,
+            WORDS_PER_PLANE,
+            SCANLINES_PER_FRAME,
+        >,
+        second_frame_buffer: &'a mut FrameBuffer<
+            WIDTH,
+            HEIGHT,
+            CHAIN_LENGTH,
+            COLOR_DEPTH,
+            PER_FRAME_DENOMINATOR,
+            WORDS_PER_PLANE,
+            SCANLINES_PER_FRAME,
+        >,
+    ) -> Result<
+        (
+            Self,
+            (
+                &'a mut FrameBuffer<
+                    WIDTH,
+                    HEIGHT,
+                    CHAIN_LENGTH,
+                    COLOR_DEPTH,
+                    PER_FRAME_DENOMINATOR,
+                    WORDS_PER_PLANE,
+                    SCANLINES_PER_FRAME,
+                >,
+                &'a mut FrameBuffer<
+                    WIDTH,
+                    HEIGHT,
+                    CHAIN_LENGTH,
+                    COLOR_DEPTH,
+                    PER_FRAME_DENOMINATOR,
+                    WORDS_PER_PLANE,
+                    SCANLINES_PER_FRAME,
+                >,
+            ),
+        ),
+        (
+            DualError<M1::Error, M2::Error>,
+            (
+                M1,
+                &'a mut FrameBuffer<
+                    WIDTH,
+                    HEIGHT,
+                    CHAIN_LENGTH,
+                    COLOR_DEPTH,
+                    PER_FRAME_DENOMINATOR,
+                    WORDS_PER_PLANE,
+                    SCANLINES_PER_FRAME,
+                >,
+            ),
+            (
+                M2,
+                &'a mut FrameBuffer<
+                    WIDTH,
+                    HEIGHT,
+                    CHAIN_LENGTH,
+                    COLOR_DEPTH,
+                    PER_FRAME_DENOMINATOR,
+                    WORDS_PER_PLANE,
+                    SCANLINES_PER_FRAME,
+                >,
+            ),
+            (
+                &'a mut FrameBuffer<
+                    WIDTH,
+                    HEIGHT,
+                    CHAIN_LENGTH,
+                    COLOR_DEPTH,
+                    PER_FRAME_DENOMINATOR,
+                    WORDS_PER_PLANE,
+                    SCANLINES_PER_FRAME,
+                >,
+                &'a mut FrameBuffer<
+                    WIDTH,
+                    HEIGHT,
+                    CHAIN_LENGTH,
+                    COLOR_DEPTH,
+                    PER_FRAME_DENOMINATOR,
+                    WORDS_PER_PLANE,
+                    SCANLINES_PER_FRAME,
+                >,
+            ),
+        ),
+    > {
+        let first_hook = self.first.take_frame_hook();
+        let second_hook = self.second.take_frame_hook();
+        let new_frame_buffers = (first_frame_buffer, second_frame_buffer);
+        let ((first_dma, first_shown), (second_dma, second_shown)) = match self.stop() {
+            (Ok(first), Ok(second)) => (first, second),
+            (Err((err, first_dma, first_frame_buffer)), second) => {
+                let second = second.unwrap_or_else(|(_, dma, frame_buffer)| (dma, frame_buffer));
+                return Err((
+                    DualError::First(err),
+                    (first_dma, first_frame_buffer),
+                    second,
+                    new_frame_buffers,
+                ));
+            }
+            (Ok(first), Err((err, second_dma, second_frame_buffer))) => {
+                return Err((
+                    DualError::Second(err),
+                    first,
+                    (second_dma, second_frame_buffer),
+                    new_frame_buffers,
+                ));
+            }
+        };
+        // Safety: the caller has upheld the requirements of `start_reference()` for the new frame
+        // buffers.
+        let started = unsafe {
+            Self::start_reference(
+                first_dma,
+                new_frame_buffers.0,
+                second_dma,
+                new_frame_buffers.1,
+            )
+        };
+        match started {
+            Ok(mut dual) => {
+                if let Some(first_hook) = first_hook {
+                    dual.first.set_frame_hook(first_hook);
+                }
+                if let Some(second_hook) = second_hook {
+                    dual.second.set_frame_hook(second_hook);
+                }
+                Ok((dual, (first_shown, second_shown)))
+            }
+            Err((err, first, second)) => Err((err, first, second, (first_shown, second_shown))),
+        }
+    }
+
+    /// Stop both transfers, the second first, returning the result of stopping each of them.
+    #[allow(clippy::type_complexity)]
+    pub fn stop(
+        self,
+    ) -> (
+        Result<
+            (
+                M1,
+                &'a mut FrameBuffer<
+                    WIDTH,
+                    HEIGHT,
+                    CHAIN_LENGTH,
+                    COLOR_DEPTH,
+                    PER_FRAME_DENOMINATOR,
+                    WORDS_PER_PLANE,
+                    SCANLINES_PER_FRAME,
+                >,
+            ),
+            (
+                M1::Error,
+                M1,
+                &'a mut FrameBuffer<
+                    WIDTH,
+                    HEIGHT,
+                    CHAIN_LENGTH,
+                    COLOR_DEPTH,
+                    PER_FRAME_DENOMINATOR,
+                    WORDS_PER_PLANE,
+                    SCANLINES_PER_FRAME,
+                >,
+            ),
+        >,
+        Result<
+            (
+                M2,
+                &'a mut FrameBuffer<
+                    WIDTH,
+                    HEIGHT,
+                    CHAIN_LENGTH,
+                    COLOR_DEPTH,
+                    PER_FRAME_DENOMINATOR,
+                    WORDS_PER_PLANE,
+                    SCANLINES_PER_FRAME,
+                >,
+            ),
+            (
+                M2::Error,
+                M2,
+                &'a mut FrameBuffer<
+                    WIDTH,
+                    HEIGHT,
+                    CHAIN_LENGTH,
+                    COLOR_DEPTH,
+                    PER_FRAME_DENOMINATOR,
+                    WORDS_PER_PLANE,
+                    SCANLINES_PER_FRAME,
+                >,
+            ),
+        >,
+    ) {
+        let second = self.second.stop();
+        let first = self.first.stop();
+        (first, second)
+    }
 }
 
 pub trait MatrixDma<
@@ -118,7 +979,12 @@ pub trait MatrixDma<
 
     /// Start a continuous DMA transfer to the RGB matrix.
     ///
-    /// Safety: The memory referred to by the `frame_buffer`argument cannot be written to while the
+    /// Implementations must call `frame_buffer.flush_control_bits()` before sending anything, as
+    /// frame buffers with lazy control bits may have scanlines with no control bits written yet.
+    ///
+    /// # Safety
+    ///
+    /// The memory referred to by the `frame_buffer`argument cannot be written to while the
     /// DMA transfer is in progress. If the lifetime of the `frame_buffer` argument is `\`static`,
     /// this is guaranteed; but if it is any other lifetime it is possible to `core::mem::forget()`
     /// the `Transfer`, which would skip the normal stop of the ongoing transfer.
@@ -160,6 +1026,9 @@ pub trait MatrixDma<
         ),
     >;
 
+    /// Start a continuous DMA transfer to the RGB matrix from a `'static` frame buffer.
+    ///
+    /// This goes through `start_reference()`, which flushes any deferred control bits first.
     fn start(
         self,
         frame_buffer: &'static mut FrameBuffer<
@@ -202,6 +1071,16 @@ pub trait MatrixDma<
         unsafe { self.start_reference(frame_buffer) }
     }
 
+    /// Check if the DMA has finished sending a frame since the last time this was called.
+    fn poll_frame_done(&mut self) -> bool;
+
+    /// Stop sending data to the matrix, leaving the panel blanked, but keep the DMA transfer set
+    /// up so that it can be resumed.
+    fn pause(&mut self);
+
+    /// Resume sending data to the matrix after a `pause()`.
+    fn resume(&mut self);
+
     fn stop<'a>(
         transfer: Transfer<
             'a,
@@ -242,3 +1121,447 @@ pub trait MatrixDma<
         ),
     >;
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::declare_frame_buffer;
+
+    // Test cases are using std
+    extern crate std;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::vec::Vec;
+
+    type TestFrameBuffer = FrameBuffer<4, 4, 1, 3, 2, 4, 2>;
+
+    /// A DMA "peripheral" that only records how many frames have been sent.
+    #[derive(Debug, Default)]
+    struct MockDma {
+        frames_sent: usize,
+        // Stand-in for the peripheral's start bit.
+        sending: bool,
+        starts: usize,
+        // Stand-in for the DMA descriptors owned by a real implementation.
+        descriptors: [u32; 4],
+    }
+
+    impl MatrixDma<4, 4, 1, 3, 2, 4, 2> for MockDma {
+        type Error = ();
+
+        unsafe fn start_reference<'a>(
+            self,
+            frame_buffer: &'a mut TestFrameBuffer,
+        ) -> Result<
+            Transfer<'a, Self, 4, 4, 1, 3, 2, 4, 2>,
+            (Self::Error, Self, &'a mut TestFrameBuffer),
+        > {
+            let mut matrix_dma = self;
+            matrix_dma.starts += 1;
+            matrix_dma.sending = true;
+            Ok(Transfer::new(matrix_dma, frame_buffer))
+        }
+
+        fn poll_frame_done(&mut self) -> bool {
+            if self.frames_sent > 0 {
+                self.frames_sent -= 1;
+                true
+            } else {
+                false
+            }
+        }
+
+        fn pause(&mut self) {
+            self.sending = false;
+        }
+
+        fn resume(&mut self) {
+            self.sending = true;
+        }
+
+        fn stop<'a>(
+            transfer: Transfer<'a, Self, 4, 4, 1, 3, 2, 4, 2>,
+        ) -> Result<(Self, &'a mut TestFrameBuffer), (Self::Error, Self, &'a mut TestFrameBuffer)>
+        {
+            let mut matrix_dma = transfer.matrix_dma;
+            matrix_dma.sending = false;
+            Ok((matrix_dma, transfer.frame_buffer))
+        }
+    }
+
+    /// A DMA "peripheral" that records when it is started and stopped in a shared log.
+    struct LoggingDma {
+        id: u8,
+        fail_start: bool,
+        frames_sent: usize,
+        log: Rc<RefCell<Vec<(&'static str, u8)>>>,
+    }
+
+    impl LoggingDma {
+        fn new(id: u8, log: &Rc<RefCell<Vec<(&'static str, u8)>>>) -> Self {
+            Self {
+                id,
+                fail_start: false,
+                frames_sent: 0,
+                log: log.clone(),
+            }
+        }
+    }
+
+    impl MatrixDma<4, 4, 1, 3, 2, 4, 2> for LoggingDma {
+        type Error = u8;
+
+        unsafe fn start_reference<'a>(
+            self,
+            frame_buffer: &'a mut TestFrameBuffer,
+        ) -> Result<
+            Transfer<'a, Self, 4, 4, 1, 3, 2, 4, 2>,
+            (Self::Error, Self, &'a mut TestFrameBuffer),
+        > {
+            if self.fail_start {
+                Err((self.id, self, frame_buffer))
+            } else {
+                self.log.borrow_mut().push(("start", self.id));
+                Ok(Transfer::new(self, frame_buffer))
+            }
+        }
+
+        fn poll_frame_done(&mut self) -> bool {
+            if self.frames_sent > 0 {
+                self.frames_sent -= 1;
+                true
+            } else {
+                false
+            }
+        }
+
+        fn pause(&mut self) {
+            self.log.borrow_mut().push(("pause", self.id));
+        }
+
+        fn resume(&mut self) {
+            self.log.borrow_mut().push(("resume", self.id));
+        }
+
+        fn stop<'a>(
+            transfer: Transfer<'a, Self, 4, 4, 1, 3, 2, 4, 2>,
+        ) -> Result<(Self, &'a mut TestFrameBuffer), (Self::Error, Self, &'a mut TestFrameBuffer)>
+        {
+            let matrix_dma = transfer.matrix_dma;
+            matrix_dma.log.borrow_mut().push(("stop", matrix_dma.id));
+            Ok((matrix_dma, transfer.frame_buffer))
+        }
+    }
+
+    #[test]
+    fn pin_collision() {
+        let mut pins = [
+            ("red_1", Some(1)),
+            ("green_1", Some(2)),
+            ("address_d", None),
+            ("address_e", None),
+            ("output_enable", Some(12)),
+            ("latch", Some(13)),
+        ];
+        assert_eq!(PinCollision::find(&pins), None);
+        pins[5].1 = Some(12);
+        assert_eq!(
+            PinCollision::find(&pins),
+            Some(PinCollision {
+                pin: 12,
+                first: "output_enable",
+                second: "latch",
+            })
+        );
+        // Unused pins never collide.
+        pins[5].1 = None;
+        assert_eq!(PinCollision::find(&pins), None);
+        pins[3].1 = Some(1);
+        assert_eq!(
+            PinCollision::find(&pins),
+            Some(PinCollision {
+                pin: 1,
+                first: "red_1",
+                second: "address_e",
+            })
+        );
+    }
+
+    #[test]
+    fn dual_transfer_start_stop_order() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut first_fb: TestFrameBuffer = declare_frame_buffer!(4, 4, 3, 1, 2);
+        let mut second_fb: TestFrameBuffer = declare_frame_buffer!(4, 4, 3, 1, 2);
+        let dual = unsafe {
+            DualTransfer::start_reference(
+                LoggingDma::new(1, &log),
+                &mut first_fb,
+                LoggingDma::new(2, &log),
+                &mut second_fb,
+            )
+        }
+        .ok()
+        .unwrap();
+        assert_eq!(*log.borrow(), [("start", 1), ("start", 2)]);
+        let (first, second) = dual.stop();
+        assert!(first.is_ok());
+        assert!(second.is_ok());
+        assert_eq!(
+            *log.borrow(),
+            [("start", 1), ("start", 2), ("stop", 2), ("stop", 1)]
+        );
+    }
+
+    #[test]
+    fn dual_transfer_second_start_fails() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut first_fb: TestFrameBuffer = declare_frame_buffer!(4, 4, 3, 1, 2);
+        let mut second_fb: TestFrameBuffer = declare_frame_buffer!(4, 4, 3, 1, 2);
+        let mut second_dma = LoggingDma::new(2, &log);
+        second_dma.fail_start = true;
+        let result = unsafe {
+            DualTransfer::start_reference(
+                LoggingDma::new(1, &log),
+                &mut first_fb,
+                second_dma,
+                &mut second_fb,
+            )
+        };
+        match result {
+            Err((err, _, _)) => assert_eq!(err, DualError::Second(2)),
+            Ok(_) => panic!("The second transfer should have failed to start"),
+        }
+        // The first transfer was stopped again.
+        assert_eq!(*log.borrow(), [("start", 1), ("stop", 1)]);
+    }
+
+    #[test]
+    fn dual_transfer_swap() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut first_fb: TestFrameBuffer = declare_frame_buffer!(4, 4, 3, 1, 2);
+        let mut second_fb: TestFrameBuffer = declare_frame_buffer!(4, 4, 3, 1, 2);
+        let mut first_next: TestFrameBuffer = declare_frame_buffer!(4, 4, 3, 1, 2);
+        let mut second_next: TestFrameBuffer = declare_frame_buffer!(4, 4, 3, 1, 2);
+        first_next.set_control_bits(1);
+        second_next.set_control_bits(2);
+        let (first_expected, second_expected) = (first_next.clone(), second_next.clone());
+        let mut hook_calls = 0;
+        let mut hook = |_: &mut TestFrameBuffer| hook_calls += 1;
+        let mut dual = unsafe {
+            DualTransfer::start_reference(
+                LoggingDma::new(1, &log),
+                &mut first_fb,
+                LoggingDma::new(2, &log),
+                &mut second_fb,
+            )
+        }
+        .ok()
+        .unwrap();
+        dual.first().set_frame_hook(&mut hook);
+        // A frame only half done before the swap isn't counted after it.
+        dual.first().matrix_dma.frames_sent += 1;
+        assert!(!dual.poll());
+
+        let (mut dual, (first_shown, second_shown)) =
+            unsafe { dual.swap(&mut first_next, &mut second_next) }
+                .ok()
+                .unwrap();
+        assert_eq!(
+            *log.borrow(),
+            [
+                ("start", 1),
+                ("start", 2),
+                ("stop", 2),
+                ("stop", 1),
+                ("start", 1),
+                ("start", 2)
+            ]
+        );
+        // The old frame buffers are handed back, and the new ones are being sent.
+        assert!(!first_shown.is_configured());
+        assert!(!second_shown.is_configured());
+        assert_eq!(*dual.first().frame_buffer_mut(), first_expected);
+        assert_eq!(*dual.second().frame_buffer_mut(), second_expected);
+        dual.second().matrix_dma.frames_sent += 1;
+        assert!(!dual.poll());
+        dual.first().matrix_dma.frames_sent += 1;
+        assert!(dual.poll());
+        let _ = dual.stop();
+        // The hook was kept through the swap.
+        assert_eq!(hook_calls, 2);
+    }
+
+    #[test]
+    fn dual_transfer_poll_waits_for_both() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut first_fb: TestFrameBuffer = declare_frame_buffer!(4, 4, 3, 1, 2);
+        let mut second_fb: TestFrameBuffer = declare_frame_buffer!(4, 4, 3, 1, 2);
+        let mut dual = unsafe {
+            DualTransfer::start_reference(
+                LoggingDma::new(1, &log),
+                &mut first_fb,
+                LoggingDma::new(2, &log),
+                &mut second_fb,
+            )
+        }
+        .ok()
+        .unwrap();
+        dual.first().matrix_dma.frames_sent += 1;
+        assert!(!dual.poll());
+        dual.second().matrix_dma.frames_sent += 1;
+        assert!(dual.poll());
+        assert!(!dual.poll());
+        let _ = dual.stop();
+    }
+
+    #[test]
+    fn current_frame_words() {
+        let mut fb: TestFrameBuffer = declare_frame_buffer!(4, 4, 3, 1, 2);
+        let expected: usize = fb.buffer_iter().map(|segment| segment.len()).sum();
+        let transfer = unsafe { MockDma::default().start_reference(&mut fb) }.unwrap();
+        // 4 words per plane, (1 + 2 + 4) plane repetitions, and 2 scanlines
+        assert_eq!(expected, 4 * 7 * 2);
+        assert_eq!(transfer.current_frame_words(), expected);
+        let _ = transfer.stop();
+    }
+
+    #[test]
+    fn pause_resume() {
+        let mut fb: TestFrameBuffer = declare_frame_buffer!(4, 4, 3, 1, 2);
+        let mut transfer = unsafe { MockDma::default().start_reference(&mut fb) }.unwrap();
+        assert!(transfer.matrix_dma.sending);
+        assert!(!transfer.is_paused());
+        transfer.pause();
+        assert!(!transfer.matrix_dma.sending);
+        assert!(transfer.is_paused());
+        transfer.resume();
+        assert!(transfer.matrix_dma.sending);
+        assert!(!transfer.is_paused());
+        // Pausing or resuming twice does nothing extra.
+        transfer.resume();
+        assert!(transfer.matrix_dma.sending);
+        let (matrix_dma, _) = transfer.stop().unwrap();
+        assert_eq!(matrix_dma.starts, 1);
+    }
+
+    #[test]
+    fn frame_hook_once_per_frame() {
+        let mut fb: TestFrameBuffer = declare_frame_buffer!(4, 4, 3, 1, 2);
+        let mut hook_calls = 0;
+        let mut hook = |_: &mut TestFrameBuffer| hook_calls += 1;
+        let mut transfer = unsafe { MockDma::default().start_reference(&mut fb) }.unwrap();
+        transfer.set_frame_hook(&mut hook);
+        // Nothing has been sent yet
+        assert!(!transfer.poll());
+        for _ in 0..3 {
+            transfer.matrix_dma.frames_sent += 1;
+            assert!(transfer.poll());
+            assert!(!transfer.poll());
+        }
+        let _ = transfer.stop();
+        assert_eq!(hook_calls, 3);
+    }
+
+    #[test]
+    fn descriptors_reused_across_stop_start() {
+        let mut fb: TestFrameBuffer = declare_frame_buffer!(4, 4, 3, 1, 2);
+        let matrix_dma = MockDma {
+            descriptors: [1, 2, 3, 4],
+            ..MockDma::default()
+        };
+        let transfer = unsafe { matrix_dma.start_reference(&mut fb) }.unwrap();
+        let (matrix_dma, fb) = transfer.stop().unwrap();
+        assert_eq!(matrix_dma.descriptors, [1, 2, 3, 4]);
+        let transfer = unsafe { matrix_dma.start_reference(fb) }.unwrap();
+        let transfer = transfer.restart().unwrap();
+        let (matrix_dma, _) = transfer.stop().unwrap();
+        assert_eq!(matrix_dma.starts, 3);
+        assert_eq!(matrix_dma.descriptors, [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn restart_keeps_frame_hook() {
+        let mut fb: TestFrameBuffer = declare_frame_buffer!(4, 4, 3, 1, 2);
+        let mut hook_calls = 0;
+        let mut hook = |_: &mut TestFrameBuffer| hook_calls += 1;
+        let mut transfer = unsafe { MockDma::default().start_reference(&mut fb) }.unwrap();
+        transfer.set_frame_hook(&mut hook);
+        let mut transfer = transfer.restart().unwrap();
+        transfer.matrix_dma.frames_sent += 1;
+        assert!(transfer.poll());
+        let _ = transfer.stop();
+        assert_eq!(hook_calls, 1);
+    }
+
+    #[test]
+    fn overrun_detector() {
+        let mut detector = OverrunDetector::new();
+        // Nothing is detected without a limit.
+        detector.frame_done(0);
+        detector.frame_done(1_000_000);
+        assert!(!detector.last_frame_overran());
+        detector.set_limit(Some(10_000));
+        detector.frame_done(1_009_000);
+        assert!(!detector.last_frame_overran());
+        detector.frame_done(1_020_000);
+        assert!(detector.last_frame_overran());
+        // Exactly the limit is fine.
+        detector.frame_done(1_030_000);
+        assert!(!detector.last_frame_overran());
+        // The timer wrapping around isn't an overrun.
+        let mut detector = OverrunDetector::new();
+        detector.set_limit(Some(10_000));
+        detector.frame_done(u64::MAX - 4_000);
+        detector.frame_done(5_000);
+        assert!(!detector.last_frame_overran());
+        // The first frame after a restart isn't timed.
+        detector.restart();
+        detector.frame_done(50_000);
+        assert!(!detector.last_frame_overran());
+    }
+
+    #[test]
+    fn transfer_last_frame_overran() {
+        let mut fb: TestFrameBuffer = declare_frame_buffer!(4, 4, 3, 1, 2);
+        let mut transfer = unsafe { MockDma::default().start_reference(&mut fb) }.unwrap();
+        transfer.set_frame_time_limit(1_000);
+        for (now, overran) in [(100, false), (1_000, false), (2_500, true), (3_000, false)] {
+            transfer.matrix_dma.frames_sent += 1;
+            assert!(transfer.poll_timed(now));
+            assert_eq!(transfer.last_frame_overran(), overran, "{now}");
+        }
+        // Polling without a frame being sent doesn't change anything.
+        assert!(!transfer.poll_timed(10_000));
+        assert!(!transfer.last_frame_overran());
+        // Pausing means the next frame can't be timed.
+        transfer.pause();
+        transfer.resume();
+        transfer.matrix_dma.frames_sent += 1;
+        assert!(transfer.poll_timed(20_000));
+        assert!(!transfer.last_frame_overran());
+        let _ = transfer.stop();
+    }
+
+    #[test]
+    fn descriptor_fault_index() {
+        let matrix_dma = MockDma::default();
+        let descriptors = &matrix_dma.descriptors;
+        let base = descriptors.as_ptr() as usize;
+        let size = core::mem::size_of::<u32>();
+        let locate = |address| DescriptorFault::locate(base, size, descriptors.len(), address);
+        // The channel reports the address of the third descriptor.
+        let address = &descriptors[2] as *const u32 as usize;
+        assert_eq!(
+            locate(address),
+            DescriptorFault {
+                address,
+                index: Some(2)
+            }
+        );
+        // Addresses that aren't the start of one of the descriptors have no index.
+        assert_eq!(locate(address + 1).index, None);
+        assert_eq!(locate(base - size).index, None);
+        assert_eq!(locate(base + size * 4).index, None);
+    }
+}
@@ -0,0 +1,205 @@
+use super::{MatrixDma, Transfer};
+use crate::buffer::FrameBuffer;
+
+/// A `MatrixDma` that doesn't send anything, for running the rest of the pipeline without
+/// hardware.
+///
+/// When a transfer is started the words of every segment of the frame buffer are copied into the
+/// recording, in the order a DMA peripheral would send them. Words past the end of the recording
+/// are dropped, and `is_truncated()` reports it. Every call to `poll_frame_done()` while not paused
+/// counts as one frame being sent.
+#[derive(Debug)]
+pub struct NullDma<'r> {
+    recording: &'r mut [u16],
+
+    recorded_words: usize,
+
+    recorded_segments: usize,
+
+    truncated: bool,
+
+    starts: usize,
+
+    frames_sent: usize,
+
+    paused: bool,
+}
+
+impl<'r> NullDma<'r> {
+    pub fn new(recording: &'r mut [u16]) -> Self {
+        Self {
+            recording,
+            recorded_words: 0,
+            recorded_segments: 0,
+            truncated: false,
+            starts: 0,
+            frames_sent: 0,
+            paused: false,
+        }
+    }
+
+    /// The words sent for a single frame by the last transfer started.
+    pub fn recorded(&self) -> &[u16] {
+        &self.recording[..self.recorded_words]
+    }
+
+    /// How many segments were sent for a single frame by the last transfer started.
+    pub fn recorded_segments(&self) -> usize {
+        self.recorded_segments
+    }
+
+    /// Check if the last frame started didn't fit in the recording.
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// How many times a transfer has been started.
+    pub fn starts(&self) -> usize {
+        self.starts
+    }
+
+    /// How many frames have been sent, across every transfer.
+    pub fn frames_sent(&self) -> usize {
+        self.frames_sent
+    }
+
+    fn record<'a>(&mut self, segments: impl Iterator<Item = &'a [u16]>) {
+        self.recorded_words = 0;
+        self.recorded_segments = 0;
+        self.truncated = false;
+        for segment in segments {
+            let available = &mut self.recording[self.recorded_words..];
+            let length = segment.len().min(available.len());
+            available[..length].copy_from_slice(&segment[..length]);
+            self.recorded_words += length;
+            self.recorded_segments += 1;
+            self.truncated |= length < segment.len();
+        }
+    }
+}
+
+impl<
+        'r,
+        const WIDTH: usize,
+        const HEIGHT: usize,
+        const CHAIN_LENGTH: usize,
+        const COLOR_DEPTH: usize,
+        const PER_FRAME_DENOMINATOR: u8,
+        const WORDS_PER_PLANE: usize,
+        const SCANLINES_PER_FRAME: usize,
+    >
+    MatrixDma<
+        WIDTH,
+        HEIGHT,
+        CHAIN_LENGTH,
+        COLOR_DEPTH,
+        PER_FRAME_DENOMINATOR,
+        WORDS_PER_PLANE,
+        SCANLINES_PER_FRAME,
+    > for NullDma<'r>
+{
+    type Error = core::convert::Infallible;
+
+    unsafe fn start_reference<'a>(
+        mut self,
+        frame_buffer: &'a mut FrameBuffer<
+            WIDTH,
+            HEIGHT,
+            CHAIN_LENGTH,
+            COLOR_DEPTH,
+            PER_FRAME_DENOMINATOR,
+            WORDS_PER_PLANE,
+            SCANLINES_PER_FRAME,
+        >,
+    ) -> Result<
+        Transfer<
+            'a,
+            Self,
+            WIDTH,
+            HEIGHT,
+            CHAIN_LENGTH,
+            COLOR_DEPTH,
+            PER_FRAME_DENOMINATOR,
+            WORDS_PER_PLANE,
+            SCANLINES_PER_FRAME,
+        >,
+        (
+            Self::Error,
+            Self,
+            &'a mut FrameBuffer<
+                WIDTH,
+                HEIGHT,
+                CHAIN_LENGTH,
+                COLOR_DEPTH,
+                PER_FRAME_DENOMINATOR,
+                WORDS_PER_PLANE,
+                SCANLINES_PER_FRAME,
+            >,
+        ),
+    > {
+        frame_buffer.flush_control_bits();
+        self.record(frame_buffer.buffer_iter());
+        self.starts += 1;
+        self.paused = false;
+        Ok(Transfer::new(self, frame_buffer))
+    }
+
+    fn poll_frame_done(&mut self) -> bool {
+        if self.paused {
+            false
+        } else {
+            self.frames_sent += 1;
+            true
+        }
+    }
+
+    fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    fn stop<'a>(
+        transfer: Transfer<
+            'a,
+            Self,
+            WIDTH,
+            HEIGHT,
+            CHAIN_LENGTH,
+            COLOR_DEPTH,
+            PER_FRAME_DENOMINATOR,
+            WORDS_PER_PLANE,
+            SCANLINES_PER_FRAME,
+        >,
+    ) -> Result<
+        (
+            Self,
+            &'a mut FrameBuffer<
+                WIDTH,
+                HEIGHT,
+                CHAIN_LENGTH,
+                COLOR_DEPTH,
+                PER_FRAME_DENOMINATOR,
+                WORDS_PER_PLANE,
+                SCANLINES_PER_FRAME,
+            >,
+        ),
+        (
+            Self::Error,
+            Self,
+            &'a mut FrameBuffer<
+                WIDTH,
+                HEIGHT,
+                CHAIN_LENGTH,
+                COLOR_DEPTH,
+                PER_FRAME_DENOMINATOR,
+                WORDS_PER_PLANE,
+                SCANLINES_PER_FRAME,
+            >,
+        ),
+    > {
+        Ok(transfer.into_parts())
+    }
+}
@@ -1,13 +1,16 @@
 #![no_std]
 
+pub mod animator;
+pub mod any_matrix;
 pub mod buffer;
-mod clock_divider;
+pub mod clock_divider;
 pub mod color;
 pub mod config;
 pub mod dma;
 pub mod matrix_word;
 pub mod rgb_matrix;
-mod util;
+pub mod stream;
+pub mod streaming_matrix;
 
 #[macro_export]
 macro_rules! const_check {
@@ -23,6 +26,6 @@ macro_rules! const_check {
 #[macro_export]
 macro_rules! const_not_zero {
     ($id:ident, $ty:ty) => {
-        const $id: $ty = crate::const_check!($id, $id > 0, "Cannot be 0");
+        const $id: $ty = $crate::const_check!($id, $id > 0, "Cannot be 0");
     };
 }
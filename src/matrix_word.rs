@@ -25,12 +25,30 @@ impl BitOffsets {
             | Self::AddressE.bit_for()
     };
 
+    const CONTROL_MASK: u16 =
+        { Self::ADDRESS_MASK | Self::Latch.bit_for() | Self::OutputEnable.bit_for() };
+
     #[inline]
     const fn bit_for(&self) -> u16 {
         1 << (*self as u8)
     }
 }
 
+/// A word with only the control bits (address, latch, and output enable) set.
+///
+/// Like `MatrixWordMut::set_address()`, an address greater than 31 is truncated to 31.
+pub const fn control_word(address: u8, latch: bool, output_enable: bool) -> u16 {
+    let address = if address > 31 { 31 } else { address };
+    let mut word = (address as u16) << BitOffsets::AddressA as u16;
+    if latch {
+        word |= BitOffsets::Latch.bit_for();
+    }
+    if output_enable {
+        word |= BitOffsets::OutputEnable.bit_for();
+    }
+    word
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 #[repr(u8)]
 pub enum MatrixPixel {
@@ -38,6 +56,14 @@ pub enum MatrixPixel {
     Two,
 }
 
+/// An address that is past the last scanline, from `MatrixWordMut::try_set_address()`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AddressOutOfRange {
+    pub address: u8,
+    pub scanline_count: usize,
+}
+
 pub trait MatrixWord {
     /// Is the red bit for the given pixel set?
     fn red(&self, pixel: MatrixPixel) -> bool;
@@ -130,128 +156,174 @@ pub trait MatrixWordMut: MatrixWord {
     ///
     /// Any value greater than 31 is truncated to 31.
     fn set_address(&mut self, address: u8);
-}
-
-impl MatrixWord for u16 {
-    fn red(&self, pixel: MatrixPixel) -> bool {
-        let mask = match pixel {
-            MatrixPixel::One => BitOffsets::Red1,
-            MatrixPixel::Two => BitOffsets::Red2,
-        }
-        .bit_for();
-        self & mask != 0
-    }
 
-    fn green(&self, pixel: MatrixPixel) -> bool {
-        let mask = match pixel {
-            MatrixPixel::One => BitOffsets::Green1,
-            MatrixPixel::Two => BitOffsets::Green2,
-        }
-        .bit_for();
-        self & mask != 0
-    }
-
-    fn blue(&self, pixel: MatrixPixel) -> bool {
-        let mask = match pixel {
-            MatrixPixel::One => BitOffsets::Blue1,
-            MatrixPixel::Two => BitOffsets::Blue2,
+    /// Set the address bits to the given value, if it is a valid scanline index.
+    ///
+    /// Unlike `set_address()`, an address greater than or equal to `scanline_count` is an error
+    /// instead of being truncated, and the word is not changed.
+    fn try_set_address(
+        &mut self,
+        address: u8,
+        scanline_count: usize,
+    ) -> Result<(), AddressOutOfRange> {
+        if (address as usize) < scanline_count.min(32) {
+            self.set_address(address);
+            Ok(())
+        } else {
+            Err(AddressOutOfRange {
+                address,
+                scanline_count,
+            })
         }
-        .bit_for();
-        self & mask != 0
-    }
-
-    fn latch(&self) -> bool {
-        self & BitOffsets::Latch.bit_for() != 0
     }
+}
 
-    fn output_enable(&self) -> bool {
-        self & BitOffsets::OutputEnable.bit_for() != 0
-    }
+/// A type that can be used for the words of a `FrameBuffer`.
+pub trait BufferWord: MatrixWordMut + Copy + core::fmt::Debug + Eq {
+    /// A word with every bit cleared.
+    const ZERO: Self;
 
-    fn address(&self) -> u8 {
-        ((self & BitOffsets::ADDRESS_MASK) >> BitOffsets::AddressA as u16) as u8
-    }
+    /// Replace the address, latch, and output enable bits with those from a `control_word()`,
+    /// leaving the color bits unchanged.
+    fn replace_control_bits(&mut self, control: u16);
 }
 
-impl MatrixWordMut for u16 {
-    fn set_red_to(&mut self, pixel: MatrixPixel, value: bool) {
-        let mask = match pixel {
-            MatrixPixel::One => BitOffsets::Red1,
-            MatrixPixel::Two => BitOffsets::Red2,
-        }
-        .bit_for();
-        if value {
-            *self |= mask;
-        } else {
-            *self &= !mask;
-        }
-    }
-
-    fn set_green_to(&mut self, pixel: MatrixPixel, value: bool) {
-        let mask = match pixel {
-            MatrixPixel::One => BitOffsets::Green1,
-            MatrixPixel::Two => BitOffsets::Green2,
-        }
-        .bit_for();
-        if value {
-            *self |= mask;
-        } else {
-            *self &= !mask;
-        }
-    }
+macro_rules! impl_matrix_word {
+    ($word:ty) => {
+        impl BufferWord for $word {
+            const ZERO: Self = 0;
 
-    fn set_blue_to(&mut self, pixel: MatrixPixel, value: bool) {
-        let mask = match pixel {
-            MatrixPixel::One => BitOffsets::Blue1,
-            MatrixPixel::Two => BitOffsets::Blue2,
-        }
-        .bit_for();
-        if value {
-            *self |= mask;
-        } else {
-            *self &= !mask;
+            fn replace_control_bits(&mut self, control: u16) {
+                let mask = BitOffsets::CONTROL_MASK as $word;
+                *self = (*self & !mask) | (control as $word & mask);
+            }
         }
-    }
 
-    fn set_latch_to(&mut self, value: bool) {
-        if value {
-            self.set_latch()
-        } else {
-            self.clear_latch()
+        impl MatrixWord for $word {
+            fn red(&self, pixel: MatrixPixel) -> bool {
+                let mask = match pixel {
+                    MatrixPixel::One => BitOffsets::Red1,
+                    MatrixPixel::Two => BitOffsets::Red2,
+                }
+                .bit_for() as $word;
+                self & mask != 0
+            }
+
+            fn green(&self, pixel: MatrixPixel) -> bool {
+                let mask = match pixel {
+                    MatrixPixel::One => BitOffsets::Green1,
+                    MatrixPixel::Two => BitOffsets::Green2,
+                }
+                .bit_for() as $word;
+                self & mask != 0
+            }
+
+            fn blue(&self, pixel: MatrixPixel) -> bool {
+                let mask = match pixel {
+                    MatrixPixel::One => BitOffsets::Blue1,
+                    MatrixPixel::Two => BitOffsets::Blue2,
+                }
+                .bit_for() as $word;
+                self & mask != 0
+            }
+
+            fn latch(&self) -> bool {
+                self & (BitOffsets::Latch.bit_for() as $word) != 0
+            }
+
+            fn output_enable(&self) -> bool {
+                self & (BitOffsets::OutputEnable.bit_for() as $word) != 0
+            }
+
+            fn address(&self) -> u8 {
+                ((self & BitOffsets::ADDRESS_MASK as $word) >> BitOffsets::AddressA as $word) as u8
+            }
         }
-    }
-
-    fn set_latch(&mut self) {
-        *self |= BitOffsets::Latch.bit_for()
-    }
-
-    fn clear_latch(&mut self) {
-        *self &= !BitOffsets::Latch.bit_for()
-    }
 
-    fn set_output_enable_to(&mut self, value: bool) {
-        if value {
-            self.set_output_enable()
-        } else {
-            self.clear_output_enable()
+        impl MatrixWordMut for $word {
+            fn set_red_to(&mut self, pixel: MatrixPixel, value: bool) {
+                let mask = match pixel {
+                    MatrixPixel::One => BitOffsets::Red1,
+                    MatrixPixel::Two => BitOffsets::Red2,
+                }
+                .bit_for() as $word;
+                if value {
+                    *self |= mask;
+                } else {
+                    *self &= !mask;
+                }
+            }
+
+            fn set_green_to(&mut self, pixel: MatrixPixel, value: bool) {
+                let mask = match pixel {
+                    MatrixPixel::One => BitOffsets::Green1,
+                    MatrixPixel::Two => BitOffsets::Green2,
+                }
+                .bit_for() as $word;
+                if value {
+                    *self |= mask;
+                } else {
+                    *self &= !mask;
+                }
+            }
+
+            fn set_blue_to(&mut self, pixel: MatrixPixel, value: bool) {
+                let mask = match pixel {
+                    MatrixPixel::One => BitOffsets::Blue1,
+                    MatrixPixel::Two => BitOffsets::Blue2,
+                }
+                .bit_for() as $word;
+                if value {
+                    *self |= mask;
+                } else {
+                    *self &= !mask;
+                }
+            }
+
+            fn set_latch_to(&mut self, value: bool) {
+                if value {
+                    self.set_latch()
+                } else {
+                    self.clear_latch()
+                }
+            }
+
+            fn set_latch(&mut self) {
+                *self |= BitOffsets::Latch.bit_for() as $word
+            }
+
+            fn clear_latch(&mut self) {
+                *self &= !(BitOffsets::Latch.bit_for() as $word)
+            }
+
+            fn set_output_enable_to(&mut self, value: bool) {
+                if value {
+                    self.set_output_enable()
+                } else {
+                    self.clear_output_enable()
+                }
+            }
+
+            fn set_output_enable(&mut self) {
+                *self |= BitOffsets::OutputEnable.bit_for() as $word
+            }
+
+            fn clear_output_enable(&mut self) {
+                *self &= !(BitOffsets::OutputEnable.bit_for() as $word)
+            }
+
+            fn set_address(&mut self, address: u8) {
+                let address = address.min(31) as $word;
+                *self &= !(BitOffsets::ADDRESS_MASK as $word);
+                *self |= address << BitOffsets::AddressA as $word;
+            }
         }
-    }
-
-    fn set_output_enable(&mut self) {
-        *self |= BitOffsets::OutputEnable.bit_for()
-    }
-
-    fn clear_output_enable(&mut self) {
-        *self &= !BitOffsets::OutputEnable.bit_for()
-    }
-
-    fn set_address(&mut self, address: u8) {
-        let address = address.min(31) as u16;
-        *self &= !BitOffsets::ADDRESS_MASK;
-        *self |= address << BitOffsets::AddressA as u16;
-    }
+    };
 }
 
+impl_matrix_word!(u16);
+impl_matrix_word!(u32);
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -469,4 +541,294 @@ mod test {
         assert_ne!(val, 0, "value did not change");
         assert_eq!(val, expected);
     }
+
+    #[test]
+    fn try_set_address_in_range() {
+        let mut val = 0u16;
+        assert_eq!(val.try_set_address(15, 16), Ok(()));
+        assert_eq!(val.address(), 15);
+    }
+
+    #[test]
+    fn try_set_address_out_of_range() {
+        let mut val = 0u16;
+        val.set_address(3);
+        assert_eq!(
+            val.try_set_address(16, 16),
+            Err(AddressOutOfRange {
+                address: 16,
+                scanline_count: 16
+            })
+        );
+        assert_eq!(val.address(), 3);
+        // Addresses past the 5 address lines are never valid
+        assert!(val.try_set_address(32, 64).is_err());
+    }
+
+    #[test]
+    fn control_word_matches_setters() {
+        let mut expected = 0u16;
+        expected.set_address(5);
+        expected.set_latch();
+        expected.set_output_enable();
+        assert_eq!(control_word(5, true, true), expected);
+        assert_eq!(control_word(40, false, false), 31 << 8);
+    }
+
+    #[test]
+    fn replace_control_bits() {
+        let mut word = 0u16;
+        word.set_red(MatrixPixel::One);
+        word.set_blue(MatrixPixel::Two);
+        word.set_address(3);
+        word.set_latch();
+        word.replace_control_bits(control_word(7, false, true));
+        assert!(word.red(MatrixPixel::One));
+        assert!(word.blue(MatrixPixel::Two));
+        assert_eq!(word.address(), 7);
+        assert!(!word.latch());
+        assert!(word.output_enable());
+    }
+
+    /// The same tests as above, but for `u32` words.
+    mod u32_word {
+        use super::*;
+
+        #[test]
+        fn red_1_not_set() {
+            assert!(!0u32.red(MatrixPixel::One))
+        }
+
+        #[test]
+        fn red_1_set() {
+            assert!(1u32.red(MatrixPixel::One))
+        }
+
+        #[test]
+        fn set_red_1() {
+            let mut val = 0u32;
+            assert!(!val.red(MatrixPixel::One));
+            val.set_red_to(MatrixPixel::One, false);
+            assert_eq!(val, 0, "Bit changed when set to false when already unset");
+            val.set_red_to(MatrixPixel::One, true);
+            assert_eq!(val, 1u32, "Bit not changed to set after set method");
+            val.set_red_to(MatrixPixel::One, false);
+            assert_eq!(val, 0, "Bit not reset when set to false");
+        }
+
+        #[test]
+        fn green_1_not_set() {
+            assert!(!0u32.green(MatrixPixel::One))
+        }
+
+        #[test]
+        fn green_1_set() {
+            assert!(2u32.green(MatrixPixel::One))
+        }
+
+        #[test]
+        fn set_green_1() {
+            let mut val = 0u32;
+            assert!(!val.green(MatrixPixel::One));
+            val.set_green_to(MatrixPixel::One, false);
+            assert_eq!(val, 0, "Bit changed when set to false when already unset");
+            val.set_green_to(MatrixPixel::One, true);
+            assert_eq!(val, 2u32, "Bit not changed to set after set method");
+            val.set_green_to(MatrixPixel::One, false);
+            assert_eq!(val, 0, "Bit not reset when set to false");
+        }
+
+        #[test]
+        fn blue_1_not_set() {
+            assert!(!0u32.blue(MatrixPixel::One))
+        }
+
+        #[test]
+        fn blue_1_set() {
+            assert!(4u32.blue(MatrixPixel::One))
+        }
+
+        #[test]
+        fn set_blue_1() {
+            let mut val = 0u32;
+            assert!(!val.blue(MatrixPixel::One));
+            val.set_blue_to(MatrixPixel::One, false);
+            assert_eq!(val, 0, "Bit changed when set to false when already unset");
+            val.set_blue_to(MatrixPixel::One, true);
+            assert_eq!(val, 4u32, "Bit not changed to set after set method");
+            val.set_blue_to(MatrixPixel::One, false);
+            assert_eq!(val, 0, "Bit not reset when set to false");
+        }
+
+        #[test]
+        fn red_2_not_set() {
+            assert!(!0u32.red(MatrixPixel::Two))
+        }
+
+        #[test]
+        fn red_2_set() {
+            assert!(8u32.red(MatrixPixel::Two))
+        }
+
+        #[test]
+        fn set_red_2() {
+            let mut val = 0u32;
+            assert!(!val.red(MatrixPixel::Two));
+            val.set_red_to(MatrixPixel::Two, false);
+            assert_eq!(val, 0, "Bit changed when set to false when already unset");
+            val.set_red_to(MatrixPixel::Two, true);
+            assert_eq!(val, 8u32, "Bit not changed to set after set method");
+            val.set_red_to(MatrixPixel::Two, false);
+            assert_eq!(val, 0, "Bit not reset when set to false");
+        }
+
+        #[test]
+        fn green_2_not_set() {
+            assert!(!0u32.green(MatrixPixel::Two))
+        }
+
+        #[test]
+        fn green_2_set() {
+            assert!(16u32.green(MatrixPixel::Two))
+        }
+
+        #[test]
+        fn set_green_2() {
+            let mut val = 0u32;
+            assert!(!val.green(MatrixPixel::Two));
+            val.set_green_to(MatrixPixel::Two, false);
+            assert_eq!(val, 0, "Bit changed when set to false when already unset");
+            val.set_green_to(MatrixPixel::Two, true);
+            assert_eq!(val, 16u32, "Bit not changed to set after set method");
+            val.set_green_to(MatrixPixel::Two, false);
+            assert_eq!(val, 0, "Bit not reset when set to false");
+        }
+
+        #[test]
+        fn blue_2_not_set() {
+            assert!(!0u32.blue(MatrixPixel::Two))
+        }
+
+        #[test]
+        fn blue_2_set() {
+            assert!(32u32.blue(MatrixPixel::Two))
+        }
+
+        #[test]
+        fn set_blue_2() {
+            let mut val = 0u32;
+            assert!(!val.blue(MatrixPixel::Two));
+            val.set_blue_to(MatrixPixel::Two, false);
+            assert_eq!(val, 0, "Bit changed when set to false when already unset");
+            val.set_blue_to(MatrixPixel::Two, true);
+            assert_eq!(val, 32u32, "Bit not changed to set after set method");
+            val.set_blue_to(MatrixPixel::Two, false);
+            assert_eq!(val, 0, "Bit not reset when set to false");
+        }
+
+        #[test]
+        fn latch_not_set() {
+            assert!(!0u32.latch())
+        }
+
+        #[test]
+        fn latch_set() {
+            assert!(64u32.latch())
+        }
+
+        #[test]
+        fn set_latch() {
+            let mut val = 0u32;
+            assert!(!val.latch());
+            val.set_latch_to(false);
+            assert_eq!(val, 0, "Bit changed when set to false when already unset");
+            val.set_latch_to(true);
+            assert_eq!(val, 64u32, "Bit not changed to set after set method");
+            val.set_latch_to(false);
+            assert_eq!(val, 0, "Bit not reset when set to false");
+        }
+
+        #[test]
+        fn output_enable_not_set() {
+            assert!(!0u32.output_enable())
+        }
+
+        #[test]
+        fn output_enable_set() {
+            assert!(128u32.output_enable())
+        }
+
+        #[test]
+        fn set_output_enable() {
+            let mut val = 0u32;
+            assert!(!val.output_enable());
+            val.set_output_enable_to(false);
+            assert_eq!(val, 0, "Bit changed when set to false when already unset");
+            val.set_output_enable_to(true);
+            assert_eq!(val, 128u32, "Bit not changed to set after set method");
+            val.set_output_enable_to(false);
+            assert_eq!(val, 0, "Bit not reset when set to false");
+        }
+
+        #[test]
+        fn address_all() {
+            for addr in 0u32..31 {
+                let shifted = addr << 8;
+                assert_eq!(
+                    shifted.address(),
+                    addr as u8,
+                    "Address {} not found for {:#06X}",
+                    addr,
+                    shifted
+                );
+            }
+        }
+
+        #[test]
+        fn set_address_all() {
+            for addr in 0u32..31 {
+                let expected = addr << 8;
+                let mut val = 0u32;
+                val.set_address(addr as u8);
+                assert_eq!(
+                    val, expected,
+                    "Newly set address is incorrect for address {}",
+                    addr
+                );
+            }
+        }
+
+        #[test]
+        fn set_address_truncate() {
+            let mut val = 0u32;
+            val.set_address(100);
+            let mut expected = 0u32;
+            expected.set_address(31);
+            assert_ne!(val, 0, "value did not change");
+            assert_eq!(val, expected);
+        }
+
+        #[test]
+        fn try_set_address_in_range() {
+            let mut val = 0u32;
+            assert_eq!(val.try_set_address(15, 16), Ok(()));
+            assert_eq!(val.address(), 15);
+        }
+
+        #[test]
+        fn try_set_address_out_of_range() {
+            let mut val = 0u32;
+            val.set_address(3);
+            assert_eq!(
+                val.try_set_address(16, 16),
+                Err(AddressOutOfRange {
+                    address: 16,
+                    scanline_count: 16
+                })
+            );
+            assert_eq!(val.address(), 3);
+            // Addresses past the 5 address lines are never valid
+            assert!(val.try_set_address(32, 64).is_err());
+        }
+    }
 }
@@ -1,19 +1,125 @@
-use core::iter;
-use core::ops::{Deref, DerefMut};
 use embedded_graphics_core::draw_target::DrawTarget;
-use embedded_graphics_core::geometry::{OriginDimensions, Size};
-use embedded_graphics_core::pixelcolor::PixelColor;
+use embedded_graphics_core::geometry::{OriginDimensions, Point, Size};
+use embedded_graphics_core::pixelcolor::{PixelColor, Rgb888, RgbColor};
+use embedded_graphics_core::primitives::Rectangle;
 use embedded_graphics_core::Pixel;
 
 use crate::{const_check, const_not_zero};
 
-use super::buffer::FrameBuffer;
-use super::color::Color;
+use super::buffer::{plane_bits, ColorStorage, FrameBuffer, LitColumns};
+use super::color::{blend, depth_components, saturating_add, Color, GammaTable};
 use super::config::MatrixConfig;
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum MatrixError {
     OutOfBounds,
+    BrightnessBelowMinimum,
+    LengthMismatch,
+}
+
+/// A rewrite of part of every word in a frame buffer, from `RgbMatrix`'s full rewrite hook.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FullRewrite {
+    /// The address, latch, and blanking bits were rewritten.
+    ControlBits,
+
+    /// The brightness (output enable) bits were rewritten.
+    BrightnessBits,
+}
+
+/// What to show before the first frame buffer is passed to `RgbMatrix::set_pending`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum IdlePattern<ColorType> {
+    /// Every pixel is off.
+    #[default]
+    Blank,
+
+    /// Every pixel is the same color.
+    Solid(ColorType),
+
+    /// Vertical bars of black, red, green, yellow, blue, magenta, cyan, and white, spread across
+    /// the entire chain.
+    ColorBars,
+}
+
+const IDLE_COLOR_BARS: usize = 8;
+
+/// Scale a color component by a panel brightness trim, where 255 leaves it unchanged.
+fn trimmed<CS: ColorStorage<COLOR_DEPTH>, const COLOR_DEPTH: usize>(value: CS, trim: u8) -> CS {
+    match trim {
+        u8::MAX => value,
+        _ => CS::from_u16((value.to_u16() as u32 * trim as u32 / u8::MAX as u32) as u16),
+    }
+}
+
+/// Split a color into its color plane bits, applying the gamma correction (if there is one) and
+/// then the panel brightness trim.
+pub(crate) fn corrected_plane_bits<ColorType, const COLOR_DEPTH: usize>(
+    color: &ColorType,
+    gamma: Option<&GammaTable>,
+    trim: u8,
+) -> [u8; COLOR_DEPTH]
+where
+    ColorType: Color<COLOR_DEPTH>,
+{
+    let (red, green, blue) = depth_components(color);
+    let (red, green, blue) = match gamma {
+        Some(gamma) => (
+            gamma.correct(red),
+            gamma.correct(green),
+            gamma.correct(blue),
+        ),
+        None => (red, green, blue),
+    };
+    plane_bits(
+        trimmed(red, trim),
+        trimmed(green, trim),
+        trimmed(blue, trim),
+    )
+}
+
+/// Remembers the color plane bits of the most recently used color.
+///
+/// Large areas of a single color are common, and splitting a color into its planes for every pixel
+/// in those areas is wasted work.
+struct ColorCache<ColorType, const COLOR_DEPTH: usize> {
+    last: Option<(ColorType, u8, [u8; COLOR_DEPTH])>,
+
+    decompositions: usize,
+}
+
+impl<ColorType, const COLOR_DEPTH: usize> ColorCache<ColorType, COLOR_DEPTH>
+where
+    ColorType: PartialEq + Copy + Color<COLOR_DEPTH>,
+{
+    fn new() -> Self {
+        Self {
+            last: None,
+            decompositions: 0,
+        }
+    }
+
+    fn plane_bits(
+        &mut self,
+        color: ColorType,
+        gamma: Option<&GammaTable>,
+        trim: u8,
+    ) -> &[u8; COLOR_DEPTH] {
+        let is_hit = matches!(
+            &self.last,
+            Some((last_color, last_trim, _)) if *last_color == color && *last_trim == trim
+        );
+        if !is_hit {
+            let bits = corrected_plane_bits(&color, gamma, trim);
+            self.last = Some((color, trim, bits));
+            self.decompositions += 1;
+        }
+        // The cache was just filled if it was empty.
+        &self.last.as_ref().unwrap().2
+    }
 }
 
 pub struct RgbMatrix<
@@ -36,8 +142,14 @@ pub struct RgbMatrix<
 
     dirty_bitmap: [u32; BITMAP_ELEMENTS],
 
+    // The top left and bottom right corners of the dirty pixels.
+    dirty_bounds: Option<(Point, Point)>,
+
     brightness: u8,
 
+    // The brightness of each scanline, relative to the overall brightness.
+    scanline_brightness: [u8; SCANLINES_PER_FRAME],
+
     brightness_dirty: bool,
 
     pending_frame_buffer: Option<
@@ -51,6 +163,41 @@ pub struct RgbMatrix<
             SCANLINES_PER_FRAME,
         >,
     >,
+
+    idle_pattern: IdlePattern<ColorType>,
+
+    gamma: Option<GammaTable>,
+
+    // The pixels changed since `begin_frame`, which are only written to the pending frame buffer by
+    // `end_frame`. `None` outside of a frame.
+    frame_bitmap: Option<[u32; BITMAP_ELEMENTS]>,
+
+    // A frame buffer passed to `set_pending` during a frame, swapped in by `end_frame`.
+    deferred_pending: Option<
+        &'a mut FrameBuffer<
+            WIDTH,
+            HEIGHT,
+            CHAIN_LENGTH,
+            COLOR_DEPTH,
+            PER_FRAME_DENOMINATOR,
+            WORDS_PER_PLANE,
+            SCANLINES_PER_FRAME,
+        >,
+    >,
+
+    on_full_rewrite: Option<&'a dyn Fn(FullRewrite)>,
+
+    // Set by the first swap that hands a frame buffer back to be shown. From then on the frame
+    // buffers passed to `set_pending` alternate, instead of each being new to the matrix.
+    double_buffered: bool,
+
+    // How many pixels have been drawn outside of the matrix since the count was last reset.
+    dropped_pixels: usize,
+
+    // How many runs of pixels have been written by `set_span`, to check that `draw_iter` batches
+    // them.
+    #[cfg(test)]
+    span_writes: usize,
 }
 
 impl<
@@ -101,10 +248,23 @@ impl<
 
     const BITMAP_ELEMENTS: usize = const_check!(
         BITMAP_ELEMENTS,
-        BITMAP_ELEMENTS == (HEIGHT * WIDTH * CHAIN_LENGTH / (u32::BITS as usize)),
-        "BITMAP_ELEMENTS must be HEIGHT * WIDTH * CHAIN_LENGTH / 32"
+        BITMAP_ELEMENTS == (HEIGHT * WIDTH * CHAIN_LENGTH).div_ceil(u32::BITS as usize),
+        "BITMAP_ELEMENTS must be HEIGHT * WIDTH * CHAIN_LENGTH / 32, rounded up"
     );
 
+    /// The dirty bitmap with every pixel marked dirty.
+    ///
+    /// When the number of pixels isn't a multiple of 32, the bits of the last element past the last
+    /// pixel are left clear.
+    const ALL_DIRTY: [u32; BITMAP_ELEMENTS] = {
+        let mut bitmap = [u32::MAX; BITMAP_ELEMENTS];
+        match (HEIGHT * WIDTH * CHAIN_LENGTH) % (u32::BITS as usize) {
+            0 => (),
+            tail_bits => bitmap[BITMAP_ELEMENTS - 1] = (1 << tail_bits) - 1,
+        }
+        bitmap
+    };
+
     const CHAIN_WIDTH: usize = Self::WIDTH * Self::CHAIN_LENGTH;
 
     const MAX_WIDTH: usize = Self::CHAIN_WIDTH - 1;
@@ -117,24 +277,235 @@ impl<
         Self::HEIGHT
     }
 
-    const fn panel_width(&self) -> usize {
-        Self::WIDTH
-    }
-
     const fn chain_width(&self) -> usize {
         Self::CHAIN_WIDTH
     }
 
+    /// The width and height seen by `set_pixel` and `DrawTarget`, after rotation.
+    fn logical_size(&self) -> (usize, usize) {
+        self.config
+            .rotation()
+            .logical_size(self.chain_width(), self.height())
+    }
+
+    /// Map rotated coordinates to the coordinates of the pixel on the chain of panels.
+    fn to_physical(&self, x: usize, y: usize) -> Result<(usize, usize), MatrixError> {
+        self.config
+            .rotation()
+            .to_physical(Self::CHAIN_WIDTH, Self::HEIGHT, x, y)
+            .ok_or(MatrixError::OutOfBounds)
+    }
+
+    /// Find which panel of the chain a pixel is on, as `(panel_index, panel_x, panel_y)`.
+    ///
+    /// `x` and `y` are the (rotated) coordinates used by `set_pixel`, and panel 0 is the first
+    /// panel in the chain.
+    pub fn map_logical(&self, x: usize, y: usize) -> Result<(usize, usize, usize), MatrixError> {
+        let (x, y) = self.to_physical(x, y)?;
+        Ok((x / Self::WIDTH, x % Self::WIDTH, y))
+    }
+
+    /// Find the (rotated) coordinates of a pixel on a single panel of the chain, the reverse of
+    /// `map_logical`.
+    pub fn map_physical(
+        &self,
+        panel_index: usize,
+        panel_x: usize,
+        panel_y: usize,
+    ) -> Result<(usize, usize), MatrixError> {
+        if panel_index >= Self::CHAIN_LENGTH || panel_x >= Self::WIDTH {
+            return Err(MatrixError::OutOfBounds);
+        }
+        self.config
+            .rotation()
+            .to_logical(
+                Self::CHAIN_WIDTH,
+                Self::HEIGHT,
+                panel_index * Self::WIDTH + panel_x,
+                panel_y,
+            )
+            .ok_or(MatrixError::OutOfBounds)
+    }
+
+    /// The smallest rectangle containing every pixel changed since the last time the dirty
+    /// pixels were written to a frame buffer, or `None` if no pixels have changed.
+    ///
+    /// The bounds are in the (unrotated) coordinates of the chain of panels.
+    pub fn dirty_bounds(&self) -> Option<Rectangle> {
+        self.dirty_bounds
+            .map(|(top_left, bottom_right)| Rectangle::with_corners(top_left, bottom_right))
+    }
+
+    /// Check if anything has changed since the last call to `set_pending`.
+    ///
+    /// If nothing has, the pending frame buffer shows the same thing as the frame buffer that was
+    /// passed to `set_pending`, and swapping them can be skipped. Changes made between
+    /// `begin_frame` and `end_frame` count as soon as they are made.
+    pub fn pending_differs(&self) -> bool {
+        self.brightness_dirty || self.dirty_bitmap.iter().any(|element| *element != 0)
+    }
+
+    /// The number of pixels drawn outside of the matrix (and so dropped) by `set_pixel` or
+    /// `draw_iter` since the count was last reset.
+    ///
+    /// Drawing off the edge is not an error for `DrawTarget`, so this is a way of catching layout
+    /// mistakes while debugging. The count stops at `usize::MAX`.
+    pub fn dropped_pixels(&self) -> usize {
+        self.dropped_pixels
+    }
+
+    pub fn reset_dropped_pixels(&mut self) {
+        self.dropped_pixels = 0;
+    }
+
+    fn grow_dirty_bounds(&mut self, point: Point) {
+        self.dirty_bounds = Some(match self.dirty_bounds {
+            Some((top_left, bottom_right)) => (
+                top_left.component_min(point),
+                bottom_right.component_max(point),
+            ),
+            None => (point, point),
+        });
+    }
+
+    /// Mark every pixel in a rectangle as dirty, so they're all redrawn by the next update.
+    ///
+    /// This is a scoped version of `assume_desynced` for when only part of a frame buffer is known
+    /// to be wrong. The parts of the rectangle outside the matrix are ignored.
+    pub fn refresh_region(&mut self, area: Rectangle) {
+        let (logical_width, logical_height) = self.logical_size();
+        let area = area.intersection(&Rectangle::new(
+            Point::zero(),
+            Size::new(logical_width as u32, logical_height as u32),
+        ));
+        let Some(bottom_right) = area.bottom_right() else {
+            return;
+        };
+        // Rotate the corners (which are both in bounds now) to get the physical area.
+        let corner = |point: Point| {
+            let (x, y) = self
+                .to_physical(point.x as usize, point.y as usize)
+                .unwrap();
+            Point::new(x as i32, y as i32)
+        };
+        let area = Rectangle::with_corners(corner(area.top_left), corner(bottom_right));
+        let bottom_right = area.bottom_right().unwrap();
+        for y in area.top_left.y..=bottom_right.y {
+            for x in area.top_left.x..=bottom_right.x {
+                let overall_bit_index = y as usize * Self::CHAIN_WIDTH + x as usize;
+                let element_index = overall_bit_index / u32::BITS as usize;
+                let bit_index = overall_bit_index % u32::BITS as usize;
+                self.dirty_bitmap[element_index] |= 1 << bit_index;
+            }
+        }
+        self.grow_dirty_bounds(area.top_left);
+        self.grow_dirty_bounds(bottom_right);
+    }
+
     pub fn brightness(&self) -> u8 {
         self.brightness
     }
 
+    /// Set the overall brightness.
+    ///
+    /// Like pixels, the new brightness is written to the pending frame buffer (or by `end_frame`
+    /// when inside a frame) and copied to the next frame buffer passed to `set_pending`. The frame
+    /// buffer being shown is never changed, so the whole frame switches brightness at once when the
+    /// frame buffers are swapped.
     pub fn set_brightness(&mut self, new_brightness: u8) {
-        self.brightness_dirty = new_brightness != self.brightness;
-        self.brightness = new_brightness;
+        if new_brightness != self.brightness {
+            self.brightness = new_brightness;
+            self.brightness_dirty = true;
+            self.write_pending_brightness();
+        }
     }
 
-    pub fn configure_frame_buffer(
+    pub fn scanline_brightness(&self) -> &[u8] {
+        &self.scanline_brightness
+    }
+
+    /// Set the brightness of each scanline, relative to the overall brightness.
+    ///
+    /// There must be one value for each scanline, with 255 being the full overall brightness and 0
+    /// being off. Each scanline covers every row with the same address, so on a 1/16 scan panel
+    /// scanline 0 is both row 0 and row 16. `MatrixError::LengthMismatch` is returned for any
+    /// other number of values.
+    pub fn set_scanline_brightness(&mut self, brightness: &[u8]) -> Result<(), MatrixError> {
+        if brightness.len() != SCANLINES_PER_FRAME {
+            return Err(MatrixError::LengthMismatch);
+        }
+        if brightness != self.scanline_brightness {
+            self.scanline_brightness.copy_from_slice(brightness);
+            self.brightness_dirty = true;
+            self.write_pending_brightness();
+        }
+        Ok(())
+    }
+
+    /// Write a brightness change to the pending frame buffer, unless it's left for `end_frame`.
+    fn write_pending_brightness(&mut self) {
+        if self.frame_bitmap.is_some() {
+            return;
+        }
+        if let Some(frame_buffer) = self.pending_frame_buffer.take() {
+            self.write_brightness(frame_buffer);
+            self.pending_frame_buffer = Some(frame_buffer);
+        }
+    }
+
+    /// The lit columns for each scanline, combining the overall and scanline brightness.
+    fn scanline_lit_columns(&self) -> [LitColumns; SCANLINES_PER_FRAME] {
+        self.scanline_lit_columns_at(self.brightness)
+    }
+
+    /// The lit columns for each scanline at an overall brightness other than the current one.
+    fn scanline_lit_columns_at(&self, overall: u8) -> [LitColumns; SCANLINES_PER_FRAME] {
+        core::array::from_fn(|scanline| {
+            let relative = self.scanline_brightness[scanline] as usize;
+            let brightness = overall as usize * relative / u8::MAX as usize;
+            self.config.lit_columns(brightness as u8)
+        })
+    }
+
+    /// The percentage of the time the LEDs are actually on at the current brightness.
+    ///
+    /// This is the share of columns with output enabled, weighted by how many times each color
+    /// plane is sent, and averaged across the scanlines. The blanking columns around the latch are
+    /// never lit, so this is below 100 even at full brightness.
+    pub fn effective_brightness_percent(&self) -> u8 {
+        let plane_weights = (0..COLOR_DEPTH).map(|plane| 1u64 << plane);
+        let mut lit_weight = 0u64;
+        for lit_columns in self.scanline_lit_columns().iter() {
+            for (plane, weight) in plane_weights.clone().enumerate() {
+                lit_weight += lit_columns.for_plane(plane).len() as u64 * weight;
+            }
+        }
+        let total_weight =
+            plane_weights.sum::<u64>() * WORDS_PER_PLANE as u64 * SCANLINES_PER_FRAME as u64;
+        (lit_weight * 100 / total_weight) as u8
+    }
+
+    /// Set a function to call whenever the control or brightness bits of an entire frame buffer
+    /// are rewritten.
+    ///
+    /// These rewrites are much more expensive than updating the changed pixels, so this can be used
+    /// to find out when (and how often) they happen.
+    pub fn set_full_rewrite_hook(&mut self, on_full_rewrite: &'a dyn Fn(FullRewrite)) {
+        self.on_full_rewrite = Some(on_full_rewrite);
+    }
+
+    pub fn clear_full_rewrite_hook(&mut self) {
+        self.on_full_rewrite = None;
+    }
+
+    fn notify_full_rewrite(&self, rewrite: FullRewrite) {
+        if let Some(on_full_rewrite) = self.on_full_rewrite {
+            on_full_rewrite(rewrite);
+        }
+    }
+
+    /// Set the brightness bits of every scanline in a frame buffer, and tell the full rewrite hook.
+    fn write_brightness(
         &self,
         frame_buffer: &mut FrameBuffer<
             WIDTH,
@@ -146,7 +517,49 @@ impl<
             SCANLINES_PER_FRAME,
         >,
     ) {
-        frame_buffer.configure(self.config.latch_blanking_count(), self.brightness);
+        for (scanline, lit_columns) in self.scanline_lit_columns().iter().enumerate() {
+            frame_buffer.set_scanline_brightness_bits(scanline, lit_columns);
+        }
+        self.notify_full_rewrite(FullRewrite::BrightnessBits);
+    }
+
+    /// Write the brightness bits for a temporary overall brightness into a frame buffer, such as
+    /// for the startup ramp.
+    ///
+    /// The brightness of every frame buffer is rewritten by the next `set_pending` afterwards, so
+    /// the frame buffer being replaced goes back to the current brightness.
+    pub(crate) fn write_temporary_brightness(
+        &mut self,
+        frame_buffer: &mut FrameBuffer<
+            WIDTH,
+            HEIGHT,
+            CHAIN_LENGTH,
+            COLOR_DEPTH,
+            PER_FRAME_DENOMINATOR,
+            WORDS_PER_PLANE,
+            SCANLINES_PER_FRAME,
+        >,
+        brightness: u8,
+    ) {
+        for (scanline, lit_columns) in self.scanline_lit_columns_at(brightness).iter().enumerate() {
+            frame_buffer.set_scanline_brightness_bits(scanline, lit_columns);
+        }
+        self.notify_full_rewrite(FullRewrite::BrightnessBits);
+        self.brightness_dirty = true;
+    }
+
+    /// Set the brightness, but no lower than the configured minimum brightness.
+    ///
+    /// If the new brightness is below the minimum, the brightness is set to the minimum and
+    /// `MatrixError::BrightnessBelowMinimum` is returned.
+    pub fn set_brightness_checked(&mut self, new_brightness: u8) -> Result<(), MatrixError> {
+        let min_brightness = self.config.min_brightness();
+        self.set_brightness(new_brightness.max(min_brightness));
+        if new_brightness < min_brightness {
+            Err(MatrixError::BrightnessBelowMinimum)
+        } else {
+            Ok(())
+        }
     }
 }
 
@@ -183,31 +596,95 @@ where
         y: usize,
         new_color: ColorType,
     ) -> Result<(), MatrixError> {
-        // Discard early out of bounds coordinates. We also can't use pattern matching here
-        // because we're using const generics for all the bounds.
-        if x >= Self::CHAIN_WIDTH {
-            return Err(MatrixError::OutOfBounds);
+        // Discard early out of bounds coordinates.
+        let (panel_index, panel_x, y) = match self.map_logical(x, y) {
+            Ok(mapped) => mapped,
+            Err(error) => {
+                self.dropped_pixels = self.dropped_pixels.saturating_add(1);
+                return Err(error);
+            }
+        };
+        if self.store_pixel(panel_index, panel_x, y, new_color, corrected_plane_bits) {
+            let x = panel_index * Self::WIDTH + panel_x;
+            self.grow_dirty_bounds(Point::new(x as i32, y as i32));
         }
-        if y >= Self::HEIGHT {
-            return Err(MatrixError::OutOfBounds);
+        Ok(())
+    }
+
+    /// Change the color of a pixel on a panel (as found by `map_logical`), marking it dirty and
+    /// writing it to the pending frame buffer unless it's left for `end_frame`.
+    ///
+    /// `plane_bits` splits the color into its color planes, given the gamma correction and the
+    /// brightness trim of its panel. The dirty bounds are left to the caller. Returns `false` if
+    /// the pixel was already that color.
+    fn store_pixel(
+        &mut self,
+        panel_index: usize,
+        panel_x: usize,
+        y: usize,
+        new_color: ColorType,
+        plane_bits: impl FnOnce(&ColorType, Option<&GammaTable>, u8) -> [u8; COLOR_DEPTH],
+    ) -> bool {
+        if self.pixel_buffer[y][panel_index][panel_x] == new_color {
+            return false;
+        }
+        let x = panel_index * Self::WIDTH + panel_x;
+        let overall_bit_index = y * Self::CHAIN_WIDTH + x;
+        let element_index = overall_bit_index / u32::BITS as usize;
+        let bit_index = overall_bit_index % u32::BITS as usize;
+        self.dirty_bitmap[element_index] |= 1 << bit_index;
+        if let Some(frame_bitmap) = &mut self.frame_bitmap {
+            frame_bitmap[element_index] |= 1 << bit_index;
+        } else if let Some(frame_buffer) = &mut self.pending_frame_buffer {
+            let bits = plane_bits(
+                &new_color,
+                self.gamma.as_ref(),
+                self.config.panel_brightness()[panel_index],
+            );
+            frame_buffer.set_pixel_planes(x, y, &bits, None);
+        }
+        self.pixel_buffer[y][panel_index][panel_x] = new_color;
+        true
+    }
+
+    /// Set a run of pixels in a single row of a panel to the same color.
+    ///
+    /// This is the same as calling `set_pixel` for each pixel, but the color is only split into
+    /// its color planes once. Pixels past the right edge of the panel are ignored.
+    fn set_span(
+        &mut self,
+        panel_index: usize,
+        panel_x: usize,
+        y: usize,
+        len: usize,
+        new_color: ColorType,
+    ) where
+        ColorType: Copy,
+    {
+        if panel_index >= Self::CHAIN_LENGTH || panel_x >= Self::WIDTH || y >= Self::HEIGHT {
+            return;
+        }
+        #[cfg(test)]
+        {
+            self.span_writes += 1;
         }
-        // Calculate which panel in the chain this x coordinate refers to
-        let panel_index = x as usize / Self::WIDTH;
-        let panel_x = x as usize % Self::WIDTH;
-        let y = y as usize;
-        // Set the dirty flag before changing the color
-        let old_color = &self.pixel_buffer[y][panel_index][panel_x];
-        if old_color != &new_color {
-            let overall_bit_index = y * Self::CHAIN_WIDTH + x;
-            let element_index = overall_bit_index / u32::BITS as usize;
-            let bit_index = overall_bit_index % u32::BITS as usize;
-            self.dirty_bitmap[element_index] |= 1 << bit_index;
-            if let Some(frame_buffer) = &mut self.pending_frame_buffer {
-                frame_buffer.set_pixel(x, y, new_color.red(), new_color.green(), new_color.blue());
+        let end = (panel_x + len).min(Self::WIDTH);
+        let mut color_cache = ColorCache::new();
+        let mut changed: Option<(usize, usize)> = None;
+        for panel_x in panel_x..end {
+            let is_changed =
+                self.store_pixel(panel_index, panel_x, y, new_color, |color, gamma, trim| {
+                    *color_cache.plane_bits(*color, gamma, trim)
+                });
+            if is_changed {
+                let x = panel_index * Self::WIDTH + panel_x;
+                changed = Some(changed.map_or((x, x), |(first, _)| (first, x)));
             }
-            self.pixel_buffer[y][panel_index][panel_x] = new_color;
         }
-        Ok(())
+        if let Some((first, last)) = changed {
+            self.grow_dirty_bounds(Point::new(first as i32, y as i32));
+            self.grow_dirty_bounds(Point::new(last as i32, y as i32));
+        }
     }
 }
 
@@ -236,7 +713,7 @@ impl<
         BITMAP_ELEMENTS,
     >
 where
-    ColorType: Default + Copy + Color<COLOR_DEPTH>,
+    ColorType: Default + PartialEq + Copy + Color<COLOR_DEPTH>,
 {
     pub fn new(
         config: MatrixConfig<WIDTH, HEIGHT, CHAIN_LENGTH, COLOR_DEPTH, PER_FRAME_DENOMINATOR>,
@@ -255,15 +732,264 @@ where
             config,
             pixel_buffer: [[[ColorType::default(); WIDTH]; CHAIN_LENGTH]; HEIGHT],
             dirty_bitmap: [0u32; BITMAP_ELEMENTS],
+            dirty_bounds: None,
             brightness: Self::DEFAULT_BRIGHTNESS,
+            scanline_brightness: [u8::MAX; SCANLINES_PER_FRAME],
             brightness_dirty: false,
             pending_frame_buffer: None,
+            idle_pattern: IdlePattern::Blank,
+            gamma: None,
+            frame_bitmap: None,
+            deferred_pending: None,
+            on_full_rewrite: None,
+            double_buffered: false,
+            dropped_pixels: 0,
+            #[cfg(test)]
+            span_writes: 0,
+        }
+    }
+
+    pub fn idle_pattern(&self) -> IdlePattern<ColorType> {
+        self.idle_pattern
+    }
+
+    pub fn gamma(&self) -> Option<&GammaTable> {
+        self.gamma.as_ref()
+    }
+
+    /// Set the gamma correction applied to every color drawn through the matrix.
+    ///
+    /// The correction is applied as colors are packed into frame buffers by the matrix (by
+    /// `set_pixel`, drawing, and `set_pending`), but writing directly to a frame buffer (such as
+    /// with `FrameBuffer::set_pixel_raw`) is never corrected. Only one of the two should be used
+    /// for content that has already been corrected, or the correction is applied twice.
+    ///
+    /// Changing the correction redraws the pending frame buffer (or leaves that to `end_frame`
+    /// inside a frame), and every pixel is redrawn in the next frame buffer passed to
+    /// `set_pending`.
+    pub fn set_gamma(&mut self, gamma: Option<GammaTable>) {
+        self.gamma = gamma;
+        self.mark_all_dirty();
+        if let Some(frame_bitmap) = self.frame_bitmap.as_mut() {
+            *frame_bitmap = Self::ALL_DIRTY;
+            return;
+        }
+        if let Some(frame_buffer) = self.pending_frame_buffer.take() {
+            self.render_all(frame_buffer);
+            self.pending_frame_buffer = Some(frame_buffer);
+        }
+    }
+
+    /// Set what is shown by frame buffers configured before the first call to `set_pending`.
+    ///
+    /// The pattern is only drawn into frame buffers by `configure_frame_buffer`, and does not
+    /// change the pixels of the matrix. Once a frame buffer is passed to `set_pending` every pixel
+    /// is redrawn into it, replacing the idle pattern.
+    pub fn set_idle_pattern(&mut self, idle_pattern: IdlePattern<ColorType>) {
+        self.idle_pattern = idle_pattern;
+    }
+
+    pub fn configure_frame_buffer(
+        &self,
+        frame_buffer: &mut FrameBuffer<
+            WIDTH,
+            HEIGHT,
+            CHAIN_LENGTH,
+            COLOR_DEPTH,
+            PER_FRAME_DENOMINATOR,
+            WORDS_PER_PLANE,
+            SCANLINES_PER_FRAME,
+        >,
+    ) {
+        frame_buffer.configure(
+            self.config.latch_blanking_count(),
+            &self.config.lit_columns(self.brightness),
+        );
+        self.notify_full_rewrite(FullRewrite::ControlBits);
+        if self.scanline_brightness.iter().any(|b| *b != u8::MAX) {
+            self.write_brightness(frame_buffer);
+        } else {
+            self.notify_full_rewrite(FullRewrite::BrightnessBits);
+        }
+        frame_buffer.apply_config(&self.config);
+        if self.pending_frame_buffer.is_none() {
+            self.render_idle_pattern(frame_buffer);
+        }
+    }
+
+    /// Draw the idle pattern into a frame buffer, leaving the pixel buffer untouched.
+    fn render_idle_pattern(
+        &self,
+        frame_buffer: &mut FrameBuffer<
+            WIDTH,
+            HEIGHT,
+            CHAIN_LENGTH,
+            COLOR_DEPTH,
+            PER_FRAME_DENOMINATOR,
+            WORDS_PER_PLANE,
+            SCANLINES_PER_FRAME,
+        >,
+    ) {
+        let solid_color = match self.idle_pattern {
+            IdlePattern::Blank => return,
+            IdlePattern::Solid(color) => Some(color),
+            IdlePattern::ColorBars => None,
+        };
+        let mut color_cache = ColorCache::new();
+        for y in 0..Self::HEIGHT {
+            for x in 0..Self::CHAIN_WIDTH {
+                let bits = match solid_color {
+                    Some(color) => *color_cache.plane_bits(
+                        color,
+                        self.gamma.as_ref(),
+                        self.config.panel_brightness()[x / Self::WIDTH],
+                    ),
+                    None => {
+                        // Each bar uses the bar index as the red, green, and blue plane bits,
+                        // giving black, red, green, yellow, blue, magenta, cyan, and white at
+                        // full intensity.
+                        let bar = (x * IDLE_COLOR_BARS / Self::CHAIN_WIDTH) as u8;
+                        [bar; COLOR_DEPTH]
+                    }
+                };
+                frame_buffer.set_pixel_planes(x, y, &bits, None);
+            }
+        }
+        frame_buffer.set_shows_idle_pattern(true);
+    }
+
+    /// The color of a pixel, as last set by `set_pixel` (or drawn through `DrawTarget`).
+    pub fn get_pixel(&self, x: usize, y: usize) -> Result<ColorType, MatrixError> {
+        let (panel_index, panel_x, y) = self.map_logical(x, y)?;
+        Ok(self.pixel_buffer[y][panel_index][panel_x])
+    }
+
+    /// Pack pixels straight into the pending frame buffer, without remembering them.
+    ///
+    /// This is for streaming whole frames (like video) where every pixel is redrawn each frame, so
+    /// there's no need to copy them into the next frame buffer. Pixels are rotated, gamma corrected
+    /// and trimmed the same as `set_pixel`, but neither the pixels of the matrix (as returned by
+    /// `get_pixel`) nor the dirty tracking are changed. Any dirty pixels are still copied into the
+    /// next frame buffer by `set_pending` (or `end_frame`), overwriting pixels drawn with this.
+    ///
+    /// Pixels outside of the matrix are skipped, as is everything if there is no pending frame
+    /// buffer. Everything is also skipped between `begin_frame` and `end_frame`, as these pixels
+    /// can't be held back until the frame ends, and nothing drawn in a frame may reach the pending
+    /// frame buffer early. Returns how many pixels were written.
+    pub fn draw_direct(&mut self, pixels: impl Iterator<Item = (Point, ColorType)>) -> usize {
+        if self.frame_bitmap.is_some() {
+            return 0;
         }
+        let Some(frame_buffer) = self.pending_frame_buffer.as_deref_mut() else {
+            return 0;
+        };
+        let mut color_cache = ColorCache::new();
+        let mut written = 0;
+        for (point, color) in pixels {
+            if point.x < 0 || point.y < 0 {
+                continue;
+            }
+            let Some((x, y)) = self.config.rotation().to_physical(
+                Self::CHAIN_WIDTH,
+                Self::HEIGHT,
+                point.x as usize,
+                point.y as usize,
+            ) else {
+                continue;
+            };
+            let bits = color_cache.plane_bits(
+                color,
+                self.gamma.as_ref(),
+                self.config.panel_brightness()[x / Self::WIDTH],
+            );
+            frame_buffer.set_pixel_planes(x, y, bits, None);
+            written += 1;
+        }
+        written
+    }
+
+    pub(crate) fn has_pending_frame_buffer(&self) -> bool {
+        self.pending_frame_buffer.is_some()
+    }
+
+    /// Whether a swap has handed a frame buffer back to be shown, so that the matrix is drawing
+    /// into one frame buffer while another is shown.
+    ///
+    /// Before the first swap there is at most the pending frame buffer, so the frame buffer coming
+    /// in at the first swap has never had any pixels drawn into it, and is fully redrawn. After
+    /// that, each frame buffer coming in was pending until the swap before, so only the changes
+    /// since then are written.
+    pub fn is_double_buffered(&self) -> bool {
+        self.double_buffered
+    }
+
+    /// Note a swap that hands the pending frame buffer back to be shown.
+    fn swapped(&mut self) {
+        if !self.double_buffered {
+            // The frame buffer coming in has only ever been shown, so it has none of the pixels.
+            self.double_buffered = true;
+            self.assume_desynced();
+        }
+    }
+
+    /// The frame buffer currently being drawn into, if `set_pending` has been called.
+    ///
+    /// Drawn pixels are written into it as they are set (or by `end_frame` when inside a frame).
+    pub fn pending_buffer(
+        &self,
+    ) -> Option<
+        &FrameBuffer<
+            WIDTH,
+            HEIGHT,
+            CHAIN_LENGTH,
+            COLOR_DEPTH,
+            PER_FRAME_DENOMINATOR,
+            WORDS_PER_PLANE,
+            SCANLINES_PER_FRAME,
+        >,
+    > {
+        self.pending_frame_buffer.as_deref()
+    }
+
+    /// The frame buffer currently being drawn into, for changing it directly.
+    ///
+    /// Changes made through this are not known to the dirty tracking, so they are not copied into
+    /// the next frame buffer passed to `set_pending`, and pixels drawn later overwrite them. Call
+    /// `assume_desynced` after changing it to redraw every pixel into the next frame buffer.
+    pub fn pending_buffer_mut(
+        &mut self,
+    ) -> Option<
+        &mut FrameBuffer<
+            WIDTH,
+            HEIGHT,
+            CHAIN_LENGTH,
+            COLOR_DEPTH,
+            PER_FRAME_DENOMINATOR,
+            WORDS_PER_PLANE,
+            SCANLINES_PER_FRAME,
+        >,
+    > {
+        self.pending_frame_buffer.as_deref_mut()
     }
 
+    /// Make `new_frame_buffer` the frame buffer being drawn into, returning the previous one to be
+    /// shown.
+    ///
+    /// Pixels are written to the pending frame buffer as they are drawn, so only the changes made
+    /// since the last call need to be copied into `new_frame_buffer`. When two frame buffers are
+    /// swapped back and forth this keeps both of them in sync, as the one coming back was the
+    /// pending frame buffer up until the last swap. The first frame buffer swapped in (see
+    /// `is_double_buffered`) and frame buffers showing the idle pattern have every pixel redrawn
+    /// the first time they are passed in, so they stay in sync as well. Any
+    /// other frame buffer (a third one in a rotation, or one changed directly) needs
+    /// `assume_desynced` to be called first so that every pixel is redrawn.
+    ///
+    /// Between `begin_frame` and `end_frame` the swap is left for `end_frame`, which returns the
+    /// frame buffer to show instead, and this returns `None`. Calling it again in the same frame
+    /// hands back the frame buffer from the earlier call untouched.
     pub fn set_pending(
         &mut self,
-        mut new_frame_buffer: &'a mut FrameBuffer<
+        new_frame_buffer: &'a mut FrameBuffer<
             WIDTH,
             HEIGHT,
             CHAIN_LENGTH,
@@ -283,11 +1009,56 @@ where
             SCANLINES_PER_FRAME,
         >,
     > {
-        self.update_dirty(&mut new_frame_buffer);
+        if self.frame_bitmap.is_some() {
+            return self.deferred_pending.replace(new_frame_buffer);
+        }
+        if self.pending_frame_buffer.is_some() {
+            self.swapped();
+        }
+        self.catch_up(new_frame_buffer);
         self.pending_frame_buffer.replace(new_frame_buffer)
     }
 
-    fn update_dirty(
+    /// Swap `back_buffer` with the pending frame buffer, but only if anything has changed since
+    /// the last swap.
+    ///
+    /// Returns `true` if they were swapped, in which case `back_buffer` now holds the frame buffer
+    /// to show. If nothing has changed (or there is no pending frame buffer yet), `back_buffer` is
+    /// left alone and the frame buffer already being shown can keep being shown, skipping the work
+    /// of updating `back_buffer` and restarting the transfer.
+    pub fn maybe_present(
+        &mut self,
+        back_buffer: &mut &'a mut FrameBuffer<
+            WIDTH,
+            HEIGHT,
+            CHAIN_LENGTH,
+            COLOR_DEPTH,
+            PER_FRAME_DENOMINATOR,
+            WORDS_PER_PLANE,
+            SCANLINES_PER_FRAME,
+        >,
+    ) -> bool {
+        if !self.pending_differs() {
+            return false;
+        }
+        let Some(pending) = self.pending_frame_buffer.take() else {
+            return false;
+        };
+        let new_frame_buffer = core::mem::replace(back_buffer, pending);
+        self.swapped();
+        self.catch_up(new_frame_buffer);
+        self.pending_frame_buffer = Some(new_frame_buffer);
+        true
+    }
+
+    /// Write every change since the last swap (including brightness changes) into a frame buffer,
+    /// without making it the pending frame buffer.
+    ///
+    /// This is the same update `set_pending` makes to the frame buffer passed to it, so a back
+    /// buffer can be prepared while the pending frame buffer is out of reach (for example, while it
+    /// is being sent). The changes are still dirty afterwards, as the next frame buffer passed to
+    /// `set_pending` may be a different one that needs them as well.
+    pub fn flush_into(
         &mut self,
         frame_buffer: &mut FrameBuffer<
             WIDTH,
@@ -299,35 +1070,432 @@ where
             SCANLINES_PER_FRAME,
         >,
     ) {
-        if self.brightness_dirty {
-            frame_buffer.set_brightness_bits(self.config.latch_blanking_count(), self.brightness);
-            self.brightness_dirty = false;
-        }
-        for (element_index, element) in self
-            .dirty_bitmap
-            .iter_mut()
-            .enumerate()
-            .filter(|(_, e)| **e != 0)
-        {
-            for bit_index in 0..u32::BITS {
-                let masked = *element & (1 << bit_index);
-                if masked != 0 {
-                    let overall_bit_index = (element_index as u32 * u32::BITS + bit_index) as usize;
-                    let y = overall_bit_index / Self::CHAIN_WIDTH;
-                    let x = overall_bit_index % Self::CHAIN_WIDTH;
-                    let panel_index = x / Self::WIDTH;
-                    let panel_x = x % Self::WIDTH;
-                    let color = self.pixel_buffer[y][panel_index][panel_x];
-                    frame_buffer.set_pixel(x, y, color.red(), color.green(), color.blue());
-                    *element &= !masked;
-                }
-            }
-        }
+        let dirty_bitmap = self.dirty_bitmap;
+        let dirty_bounds = self.dirty_bounds;
+        let brightness_dirty = self.brightness_dirty;
+        self.catch_up(frame_buffer);
+        self.dirty_bitmap = dirty_bitmap;
+        self.dirty_bounds = dirty_bounds;
+        self.brightness_dirty = brightness_dirty;
     }
-}
 
-impl<
-        'a,
+    /// Copy the changes made since the last swap into the frame buffer about to become pending.
+    fn catch_up(
+        &mut self,
+        new_frame_buffer: &mut FrameBuffer<
+            WIDTH,
+            HEIGHT,
+            CHAIN_LENGTH,
+            COLOR_DEPTH,
+            PER_FRAME_DENOMINATOR,
+            WORDS_PER_PLANE,
+            SCANLINES_PER_FRAME,
+        >,
+    ) {
+        // A frame buffer showing the idle pattern has none of the pixels, so they're all redrawn.
+        if new_frame_buffer.shows_idle_pattern() {
+            self.mark_all_dirty();
+            new_frame_buffer.set_shows_idle_pattern(false);
+        }
+        match self.frame_bitmap {
+            Some(frame_bitmap) => {
+                // Changes made during a frame are left for `end_frame`, but stay dirty for the
+                // frame buffer being replaced.
+                for (dirty, changed) in self.dirty_bitmap.iter_mut().zip(frame_bitmap.iter()) {
+                    *dirty &= !changed;
+                }
+                self.update_dirty(new_frame_buffer);
+                for (element_index, changed) in frame_bitmap.iter().enumerate() {
+                    self.dirty_bitmap[element_index] |= changed;
+                    for bit_index in (0..u32::BITS).filter(|bit| changed & (1 << bit) != 0) {
+                        let overall_bit_index =
+                            (element_index as u32 * u32::BITS + bit_index) as usize;
+                        self.grow_dirty_bounds(Point::new(
+                            (overall_bit_index % Self::CHAIN_WIDTH) as i32,
+                            (overall_bit_index / Self::CHAIN_WIDTH) as i32,
+                        ));
+                    }
+                }
+            }
+            None => {
+                self.update_dirty(new_frame_buffer);
+            }
+        }
+    }
+
+    /// Start collecting changes to pixels without writing them to the pending frame buffer.
+    ///
+    /// Pixels set until the matching `end_frame` only change the pixels of the matrix and the
+    /// dirty tracking, so a scene that takes longer than a frame to draw is never shown half
+    /// finished. Calling this again before `end_frame` has no effect.
+    pub fn begin_frame(&mut self) {
+        if self.frame_bitmap.is_none() {
+            self.frame_bitmap = Some([0u32; BITMAP_ELEMENTS]);
+        }
+    }
+
+    /// Write every pixel changed since `begin_frame` to the pending frame buffer at once.
+    ///
+    /// If `set_pending` was called during the frame, that frame buffer becomes the pending frame
+    /// buffer now and the previous one is returned to be shown, as `set_pending` would have.
+    /// Calling this without a matching `begin_frame` has no effect.
+    pub fn end_frame(
+        &mut self,
+    ) -> Option<
+        &'a mut FrameBuffer<
+            WIDTH,
+            HEIGHT,
+            CHAIN_LENGTH,
+            COLOR_DEPTH,
+            PER_FRAME_DENOMINATOR,
+            WORDS_PER_PLANE,
+            SCANLINES_PER_FRAME,
+        >,
+    > {
+        self.write_frame();
+        let new_frame_buffer = self.deferred_pending.take()?;
+        self.set_pending(new_frame_buffer)
+    }
+
+    /// Write the pixels changed during a frame to the pending frame buffer, ending the frame.
+    fn write_frame(&mut self) {
+        let Some(mut frame_bitmap) = self.frame_bitmap.take() else {
+            return;
+        };
+        let Some(frame_buffer) = self.pending_frame_buffer.take() else {
+            // Without a pending frame buffer, the changes are left to the dirty tracking.
+            return;
+        };
+        if self.brightness_dirty {
+            self.write_brightness(frame_buffer);
+        }
+        let mut color_cache = ColorCache::new();
+        for (element_index, element) in frame_bitmap
+            .iter_mut()
+            .enumerate()
+            .filter(|(_, e)| **e != 0)
+        {
+            while *element != 0 {
+                let bit_index = element.trailing_zeros();
+                let overall_bit_index = (element_index as u32 * u32::BITS + bit_index) as usize;
+                let y = overall_bit_index / Self::CHAIN_WIDTH;
+                let x = overall_bit_index % Self::CHAIN_WIDTH;
+                let panel_index = x / Self::WIDTH;
+                let panel_x = x % Self::WIDTH;
+                frame_buffer.set_pixel_planes(
+                    x,
+                    y,
+                    color_cache.plane_bits(
+                        self.pixel_buffer[y][panel_index][panel_x],
+                        self.gamma.as_ref(),
+                        self.config.panel_brightness()[panel_index],
+                    ),
+                    None,
+                );
+                *element &= !(1 << bit_index);
+            }
+        }
+        self.pending_frame_buffer = Some(frame_buffer);
+    }
+
+    /// Write the brightness (if it changed) and every dirty pixel to a frame buffer, returning how
+    /// many pixels were written.
+    fn update_dirty(
+        &mut self,
+        frame_buffer: &mut FrameBuffer<
+            WIDTH,
+            HEIGHT,
+            CHAIN_LENGTH,
+            COLOR_DEPTH,
+            PER_FRAME_DENOMINATOR,
+            WORDS_PER_PLANE,
+            SCANLINES_PER_FRAME,
+        >,
+    ) -> usize {
+        // If every pixel is about to be rewritten, the brightness can be applied while packing
+        // each pixel instead of taking an extra pass over the entire frame buffer.
+        let combined_pass = self.brightness_dirty && self.all_dirty();
+        let lit_columns = self.scanline_lit_columns();
+        if self.brightness_dirty && !combined_pass {
+            self.write_brightness(frame_buffer);
+        }
+        self.brightness_dirty = false;
+        let pixels_written = self.write_dirty_pixels(
+            frame_buffer,
+            usize::MAX,
+            combined_pass.then_some(&lit_columns[..]),
+        );
+        if combined_pass {
+            self.notify_full_rewrite(FullRewrite::BrightnessBits);
+        }
+        pixels_written
+    }
+
+    /// Write up to `max_pixels` dirty pixels to a frame buffer, returning if any dirty pixels are
+    /// left.
+    ///
+    /// The written pixels are no longer dirty, so a later call picks up where this one left off.
+    /// This spreads the work of updating a frame buffer across several calls, instead of doing it
+    /// all in `set_pending`. Brightness changes are still only written by `set_pending`.
+    pub fn flush_budgeted(
+        &mut self,
+        frame_buffer: &mut FrameBuffer<
+            WIDTH,
+            HEIGHT,
+            CHAIN_LENGTH,
+            COLOR_DEPTH,
+            PER_FRAME_DENOMINATOR,
+            WORDS_PER_PLANE,
+            SCANLINES_PER_FRAME,
+        >,
+        max_pixels: usize,
+    ) -> bool {
+        self.write_dirty_pixels(frame_buffer, max_pixels, None);
+        self.dirty_bitmap.iter().any(|element| *element != 0)
+    }
+
+    /// Write up to `max_pixels` dirty pixels to a frame buffer and clear their dirty bits,
+    /// returning how many were written.
+    ///
+    /// If `lit_columns` is given (one for each scanline), the brightness bits are written as well.
+    fn write_dirty_pixels(
+        &mut self,
+        frame_buffer: &mut FrameBuffer<
+            WIDTH,
+            HEIGHT,
+            CHAIN_LENGTH,
+            COLOR_DEPTH,
+            PER_FRAME_DENOMINATOR,
+            WORDS_PER_PLANE,
+            SCANLINES_PER_FRAME,
+        >,
+        max_pixels: usize,
+        lit_columns: Option<&[LitColumns]>,
+    ) -> usize {
+        let mut pixels_written = 0;
+        let mut color_cache = ColorCache::new();
+        for (element_index, element) in self
+            .dirty_bitmap
+            .iter_mut()
+            .enumerate()
+            .filter(|(_, e)| **e != 0)
+        {
+            while *element != 0 {
+                if pixels_written == max_pixels {
+                    // The bounds are left as they are, as they still cover the remaining pixels.
+                    return pixels_written;
+                }
+                let bit_index = element.trailing_zeros();
+                let overall_bit_index = (element_index as u32 * u32::BITS + bit_index) as usize;
+                let y = overall_bit_index / Self::CHAIN_WIDTH;
+                let x = overall_bit_index % Self::CHAIN_WIDTH;
+                let panel_index = x / Self::WIDTH;
+                let panel_x = x % Self::WIDTH;
+                let color = self.pixel_buffer[y][panel_index][panel_x];
+                let scanline = frame_buffer.locate(x, y).scanline;
+                frame_buffer.set_pixel_planes(
+                    x,
+                    y,
+                    color_cache.plane_bits(
+                        color,
+                        self.gamma.as_ref(),
+                        self.config.panel_brightness()[panel_index],
+                    ),
+                    lit_columns.map(|lit_columns| &lit_columns[scanline]),
+                );
+                pixels_written += 1;
+                *element &= !(1 << bit_index);
+            }
+        }
+        self.dirty_bounds = None;
+        pixels_written
+    }
+
+    fn all_dirty(&self) -> bool {
+        self.dirty_bitmap == Self::ALL_DIRTY
+    }
+
+    fn mark_all_dirty(&mut self) {
+        self.dirty_bitmap = Self::ALL_DIRTY;
+        self.dirty_bounds = Some((
+            Point::zero(),
+            Point::new(Self::MAX_WIDTH as i32, Self::MAX_HEIGHT as i32),
+        ));
+    }
+
+    /// Return the matrix to a clean state, with every pixel black and the default brightness.
+    ///
+    /// The pending frame buffer (if there is one) is redrawn immediately, and every pixel is marked
+    /// dirty so the next frame buffer passed to `set_pending` is fully redrawn as well.
+    pub fn reset(&mut self) {
+        self.pixel_buffer = [[[ColorType::default(); WIDTH]; CHAIN_LENGTH]; HEIGHT];
+        self.brightness = Self::DEFAULT_BRIGHTNESS;
+        self.scanline_brightness = [u8::MAX; SCANLINES_PER_FRAME];
+        self.brightness_dirty = true;
+        self.mark_all_dirty();
+        if let Some(frame_buffer) = self.pending_frame_buffer.take() {
+            self.render_all(frame_buffer);
+            self.pending_frame_buffer = Some(frame_buffer);
+        }
+    }
+
+    /// Declare that the frame buffers are in sync with the pixels of the matrix.
+    ///
+    /// This clears the dirty tracking (including any brightness change), so the next frame buffer
+    /// passed to `set_pending` is not updated at all. Use this after writing the same pixels into a
+    /// frame buffer directly.
+    pub fn assume_synced(&mut self) {
+        self.dirty_bitmap = [0u32; BITMAP_ELEMENTS];
+        self.dirty_bounds = None;
+        self.brightness_dirty = false;
+    }
+
+    /// Declare that the frame buffers no longer match the pixels of the matrix.
+    ///
+    /// Every pixel and the brightness are redrawn into the next frame buffer passed to
+    /// `set_pending`. Use this after changing a frame buffer directly.
+    pub fn assume_desynced(&mut self) {
+        self.mark_all_dirty();
+        self.brightness_dirty = true;
+    }
+
+    /// Configure a frame buffer and pack every pixel into it, ignoring the dirty tracking.
+    ///
+    /// The contents of the frame buffer depend only on the pixels and settings of the matrix, not
+    /// on what was in the frame buffer before, so the result can be checksummed and compared
+    /// between runs. The dirty tracking of the matrix is left unchanged.
+    pub fn render_to(
+        &self,
+        frame_buffer: &mut FrameBuffer<
+            WIDTH,
+            HEIGHT,
+            CHAIN_LENGTH,
+            COLOR_DEPTH,
+            PER_FRAME_DENOMINATOR,
+            WORDS_PER_PLANE,
+            SCANLINES_PER_FRAME,
+        >,
+    ) {
+        frame_buffer.configure(
+            self.config.latch_blanking_count(),
+            &self.config.lit_columns(self.brightness),
+        );
+        // The brightness bits are rewritten again by `render_all`, which notifies the hook.
+        self.notify_full_rewrite(FullRewrite::ControlBits);
+        frame_buffer.apply_config(&self.config);
+        self.render_all(frame_buffer);
+    }
+
+    /// Pack every pixel and the current brightness into a frame buffer, ignoring the dirty
+    /// tracking.
+    fn render_all(
+        &self,
+        frame_buffer: &mut FrameBuffer<
+            WIDTH,
+            HEIGHT,
+            CHAIN_LENGTH,
+            COLOR_DEPTH,
+            PER_FRAME_DENOMINATOR,
+            WORDS_PER_PLANE,
+            SCANLINES_PER_FRAME,
+        >,
+    ) {
+        let lit_columns = self.scanline_lit_columns();
+        for (y, row) in self.pixel_buffer.iter().enumerate() {
+            for (panel_index, panel_row) in row.iter().enumerate() {
+                for (panel_x, color) in panel_row.iter().enumerate() {
+                    let x = panel_index * Self::WIDTH + panel_x;
+                    let scanline = frame_buffer.locate(x, y).scanline;
+                    frame_buffer.set_pixel_planes(
+                        x,
+                        y,
+                        &corrected_plane_bits(
+                            color,
+                            self.gamma.as_ref(),
+                            self.config.panel_brightness()[panel_index],
+                        ),
+                        Some(&lit_columns[scanline]),
+                    );
+                }
+            }
+        }
+        self.notify_full_rewrite(FullRewrite::BrightnessBits);
+    }
+}
+
+impl<
+        'a,
+        ColorType,
+        const WIDTH: usize,
+        const HEIGHT: usize,
+        const CHAIN_LENGTH: usize,
+        const COLOR_DEPTH: usize,
+        const PER_FRAME_DENOMINATOR: u8,
+        const WORDS_PER_PLANE: usize,
+        const SCANLINES_PER_FRAME: usize,
+        const BITMAP_ELEMENTS: usize,
+    >
+    RgbMatrix<
+        'a,
+        ColorType,
+        WIDTH,
+        HEIGHT,
+        CHAIN_LENGTH,
+        COLOR_DEPTH,
+        PER_FRAME_DENOMINATOR,
+        WORDS_PER_PLANE,
+        SCANLINES_PER_FRAME,
+        BITMAP_ELEMENTS,
+    >
+where
+    ColorType:
+        Default + PartialEq + Copy + Color<COLOR_DEPTH> + RgbColor + From<Rgb888> + Into<Rgb888>,
+{
+    /// Blend a color over the existing color of a pixel.
+    ///
+    /// `coverage` is how much of the new color to use, from 0 (the pixel is unchanged) to 255 (the
+    /// pixel is entirely the new color). See `color::blend` for how the colors are combined.
+    pub fn blend_pixel(
+        &mut self,
+        x: usize,
+        y: usize,
+        color: ColorType,
+        coverage: u8,
+    ) -> Result<(), MatrixError> {
+        let existing = self.get_pixel(x, y)?;
+        self.set_pixel(x, y, blend(existing, color, coverage))
+    }
+
+    /// Add a color to the existing color of a pixel, saturating each component.
+    ///
+    /// Overlapping additive draws brighten each other, which is useful for glows and particle
+    /// effects. See `color::saturating_add` for how the colors are combined.
+    pub fn add_pixel(&mut self, x: usize, y: usize, color: ColorType) -> Result<(), MatrixError> {
+        let existing = self.get_pixel(x, y)?;
+        self.set_pixel(x, y, saturating_add(existing, color))
+    }
+
+    /// Draw antialiased glyphs (or any other coverage mask) in a single color.
+    ///
+    /// Each item is a point and how much of that pixel is covered by the glyph. Unlike drawing
+    /// antialiased text through `DrawTarget`, which blends against a fixed background color, the
+    /// coverage is blended against what is already on the matrix. Points outside of the matrix are
+    /// ignored.
+    pub fn draw_glyph_coverage<I>(&mut self, coverage: I, color: ColorType)
+    where
+        I: IntoIterator<Item = (Point, u8)>,
+    {
+        for (point, coverage) in coverage {
+            if point.x >= 0 && point.y >= 0 {
+                // Ignore any errors
+                let _ = self.blend_pixel(point.x as usize, point.y as usize, color, coverage);
+            }
+        }
+    }
+}
+
+impl<
+        'a,
         ColorType,
         const WIDTH: usize,
         const HEIGHT: usize,
@@ -352,9 +1520,10 @@ impl<
     >
 {
     fn size(&self) -> Size {
+        let (width, height) = self.logical_size();
         Size {
-            width: self.chain_width() as u32,
-            height: self.height() as u32,
+            width: width as u32,
+            height: height as u32,
         }
     }
 }
@@ -394,12 +1563,1304 @@ where
     where
         I: IntoIterator<Item = Pixel<Self::Color>>,
     {
+        // Runs of adjacent pixels in a row with the same color (as drawn by lines and filled
+        // shapes) are collected into spans, so the color only needs to be split into planes once.
+        let mut span: Option<(usize, usize, usize, usize, ColorType)> = None;
         for Pixel(coord, color) in pixels {
-            if coord.x >= 0 && coord.y >= 0 {
-                // Ignore any errors
-                let _ = self.set_pixel(coord.x as usize, coord.y as usize, color);
+            if coord.x < 0 || coord.y < 0 {
+                self.dropped_pixels = self.dropped_pixels.saturating_add(1);
+                continue;
+            }
+            // Spans are collected after rotating, so they follow the rows of the panels.
+            let Ok((panel_index, panel_x, y)) =
+                self.map_logical(coord.x as usize, coord.y as usize)
+            else {
+                self.dropped_pixels = self.dropped_pixels.saturating_add(1);
+                continue;
+            };
+            match &mut span {
+                Some((span_panel, span_x, span_y, len, span_color))
+                    if *span_panel == panel_index
+                        && *span_y == y
+                        && *span_x + *len == panel_x
+                        && *span_color == color =>
+                {
+                    *len += 1;
+                }
+                _ => {
+                    if let Some((span_panel, span_x, span_y, len, span_color)) = span {
+                        self.set_span(span_panel, span_x, span_y, len, span_color);
+                    }
+                    span = Some((panel_index, panel_x, y, 1, color));
+                }
             }
         }
+        if let Some((span_panel, span_x, span_y, len, span_color)) = span {
+            self.set_span(span_panel, span_x, span_y, len, span_color);
+        }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use embedded_graphics_core::pixelcolor::Rgb888;
+
+    use crate::config::Rotation;
+    use crate::declare_frame_buffer;
+    use crate::matrix_word::MatrixWord;
+
+    type TestMatrix<'a> = RgbMatrix<'a, Rgb888, 64, 32, 1, 8, 16, 64, 16, 64>;
+
+    fn test_color(x: usize, y: usize) -> Rgb888 {
+        Rgb888::new(x as u8 * 4, y as u8 * 8, 0xA5)
+    }
+
+    #[test]
+    fn update_dirty_combined_brightness_pass() {
+        let mut matrix = TestMatrix::new(MatrixConfig::default());
+        for y in 0..32 {
+            for x in 0..64 {
+                matrix.set_pixel(x, y, test_color(x, y)).unwrap();
+            }
+        }
+        matrix.set_brightness(200);
+        let mut fb = declare_frame_buffer!(64, 32, 8, 1, 16);
+        matrix.configure_frame_buffer(&mut fb);
+        let mut expected = fb.clone();
+        assert!(matrix.all_dirty());
+        fb.word_writes = 0;
+        assert_eq!(matrix.update_dirty(&mut fb), 64 * 32);
+        assert!(!matrix.brightness_dirty);
+
+        // Do the same work the two-pass way to compare against.
+        expected.word_writes = 0;
+        matrix.write_brightness(&mut expected);
+        matrix.mark_all_dirty();
+        assert_eq!(
+            matrix.write_dirty_pixels(&mut expected, usize::MAX, None),
+            64 * 32
+        );
+
+        assert_eq!(fb, expected, "combined pass produced a different buffer");
+        assert_eq!(lit_words(&fb, 0), 59 * 200 / 255);
+        // Each pixel writes one word in every plane, and the brightness doesn't take another pass
+        // over all 64 words * 8 planes * 16 scanlines.
+        assert_eq!(fb.word_writes, 64 * 32 * 8);
+        assert_eq!(expected.word_writes - fb.word_writes, 64 * 8 * 16);
+    }
+
+    fn lit_words(fb: &FrameBuffer<64, 32, 1, 8, 16, 64, 16>, scanline: usize) -> usize {
+        let plane = fb.plane(scanline, 7).unwrap();
+        plane.iter().filter(|word| !word.output_enable()).count()
+    }
+
+    #[test]
+    fn scanline_brightness() {
+        let mut matrix = TestMatrix::new(MatrixConfig::default());
+        matrix.set_brightness(255);
+        let mut brightness = [255; 16];
+        brightness[1] = 64;
+        matrix.set_scanline_brightness(&brightness).unwrap();
+        let mut fb = declare_frame_buffer!(64, 32, 8, 1, 16);
+        matrix.configure_frame_buffer(&mut fb);
+        // 59 columns are available after the blanking columns.
+        assert_eq!(lit_words(&fb, 0), 59);
+        assert_eq!(lit_words(&fb, 1), 59 * 64 / 255);
+
+        // Changing the scanline brightness rewrites the brightness bits on the next update.
+        brightness[1] = 255;
+        brightness[2] = 0;
+        matrix.set_scanline_brightness(&brightness).unwrap();
+        matrix.update_dirty(&mut fb);
+        assert_eq!(lit_words(&fb, 1), 59);
+        assert_eq!(lit_words(&fb, 2), 0);
+        assert_eq!(
+            matrix.set_scanline_brightness(&[255; 8]),
+            Err(MatrixError::LengthMismatch)
+        );
+    }
+
+    #[test]
+    fn brightness_applied_at_swap() {
+        let mut first = declare_frame_buffer!(64, 32, 8, 1, 16);
+        let mut second = declare_frame_buffer!(64, 32, 8, 1, 16);
+        let mut matrix = TestMatrix::new(MatrixConfig::default());
+        matrix.set_brightness(255);
+        matrix.configure_frame_buffer(&mut first);
+        matrix.configure_frame_buffer(&mut second);
+        matrix.set_pending(&mut first);
+        let live = matrix.set_pending(&mut second).unwrap();
+        let live_before = live.clone();
+
+        matrix.set_brightness(128);
+        assert_eq!(*live, live_before);
+        assert_eq!(
+            lit_words(matrix.pending_buffer().unwrap(), 0),
+            59 * 128 / 255
+        );
+
+        // Inside a frame the change waits for `end_frame`.
+        matrix.begin_frame();
+        matrix.set_brightness(64);
+        assert_eq!(
+            lit_words(matrix.pending_buffer().unwrap(), 0),
+            59 * 128 / 255
+        );
+        matrix.end_frame();
+        assert_eq!(
+            lit_words(matrix.pending_buffer().unwrap(), 0),
+            59 * 64 / 255
+        );
+        assert_eq!(*live, live_before);
+
+        // Both frame buffers end up with the new brightness as they are swapped.
+        let live = matrix.set_pending(live).unwrap();
+        assert_eq!(lit_words(live, 0), 59 * 64 / 255);
+        let live = matrix.set_pending(live).unwrap();
+        assert_eq!(lit_words(live, 0), 59 * 64 / 255);
+    }
+
+    #[test]
+    fn bitmap_rounds_up() {
+        // 100 pixels need 4 bitmap elements, with only 4 bits used in the last one.
+        let mut matrix =
+            RgbMatrix::<Rgb888, 10, 10, 1, 8, 5, 10, 5, 4>::new(MatrixConfig::default());
+        let mut fb = declare_frame_buffer!(10, 10, 8, 1, 5);
+        matrix.configure_frame_buffer(&mut fb);
+        matrix.set_pixel(9, 9, Rgb888::WHITE).unwrap();
+        assert_eq!(matrix.dirty_bitmap, [0, 0, 0, 1 << 3]);
+        matrix.update_dirty(&mut fb);
+        let location = fb.locate(9, 9);
+        assert!(fb.plane(location.scanline, 7).unwrap()[location.word_index].red(location.lane));
+        assert_eq!(matrix.dirty_bitmap, [0; 4]);
+
+        // Marking everything dirty doesn't mark pixels that don't exist.
+        matrix.mark_all_dirty();
+        assert_eq!(matrix.dirty_bitmap[3], 0xF);
+        assert!(matrix.all_dirty());
+        matrix.update_dirty(&mut fb);
+        assert_eq!(matrix.dirty_bitmap, [0; 4]);
+    }
+
+    #[test]
+    fn chained_dirty_bits_cover_every_pixel() {
+        // Three 10x10 panels have 300 pixels, which needs 10 bitmap elements.
+        let mut matrix =
+            RgbMatrix::<Rgb888, 10, 10, 3, 8, 5, 30, 5, 10>::new(MatrixConfig::default());
+        let mut seen = [false; 10 * 32];
+        for y in 0..10 {
+            for x in 0..30 {
+                let before = matrix.dirty_bitmap;
+                matrix.set_pixel(x, y, Rgb888::WHITE).unwrap();
+                let mut new_bits = (0..10 * 32).filter(|bit| {
+                    let mask = 1 << (bit % 32);
+                    matrix.dirty_bitmap[bit / 32] & mask != 0 && before[bit / 32] & mask == 0
+                });
+                let bit = new_bits.next().unwrap();
+                assert_eq!(new_bits.next(), None, "({x}, {y}) set more than one bit");
+                assert!(bit < 300 && !seen[bit], "({x}, {y}) has bit {bit}");
+                seen[bit] = true;
+            }
+        }
+        assert!(matrix.all_dirty());
+
+        // The last pixel of the last panel is flushed.
+        let mut fb = declare_frame_buffer!(10, 10, 8, 3, 5);
+        matrix.configure_frame_buffer(&mut fb);
+        assert_eq!(matrix.update_dirty(&mut fb), 300);
+        let location = fb.locate(29, 9);
+        assert!(fb.plane(location.scanline, 0).unwrap()[location.word_index].blue(location.lane));
+    }
+
+    #[test]
+    fn refresh_region() {
+        let mut matrix = TestMatrix::new(MatrixConfig::default());
+        let mut fb = declare_frame_buffer!(64, 32, 8, 1, 16);
+        matrix.configure_frame_buffer(&mut fb);
+        matrix.assume_synced();
+        // The rectangle hangs off the right edge, so only 4 columns are inside the matrix.
+        matrix.refresh_region(Rectangle::new(Point::new(60, 2), Size::new(8, 3)));
+        assert_eq!(
+            matrix.dirty_bounds(),
+            Some(Rectangle::with_corners(
+                Point::new(60, 2),
+                Point::new(63, 4)
+            ))
+        );
+        for y in 0..32 {
+            for x in 0..64 {
+                let overall_bit_index = y * 64 + x;
+                let is_dirty = matrix.dirty_bitmap[overall_bit_index / 32]
+                    & (1 << (overall_bit_index % 32))
+                    != 0;
+                assert_eq!(is_dirty, (60..64).contains(&x) && (2..5).contains(&y));
+            }
+        }
+        assert_eq!(matrix.update_dirty(&mut fb), 4 * 3);
+
+        // Rectangles entirely outside the matrix are ignored.
+        matrix.refresh_region(Rectangle::new(Point::new(64, 0), Size::new(8, 8)));
+        assert_eq!(matrix.dirty_bounds(), None);
+    }
+
+    #[test]
+    fn flush_budgeted_resumes() {
+        let mut matrix = TestMatrix::new(MatrixConfig::default());
+        let mut fb = declare_frame_buffer!(64, 32, 8, 1, 16);
+        matrix.configure_frame_buffer(&mut fb);
+        let mut expected = fb.clone();
+        for index in 0..100 {
+            let (x, y) = (index % 64, index / 64 * 7);
+            matrix.set_pixel(x, y, test_color(x, y)).unwrap();
+            let color = test_color(x, y);
+            expected.set_pixel(x, y, color.red(), color.green(), color.blue());
+        }
+        assert!(matrix.flush_budgeted(&mut fb, 30));
+        assert!(matrix.flush_budgeted(&mut fb, 30));
+        assert!(matrix.flush_budgeted(&mut fb, 30));
+        assert_ne!(fb, expected);
+        assert!(!matrix.flush_budgeted(&mut fb, 30));
+        assert_eq!(fb, expected);
+        assert_eq!(matrix.dirty_bounds(), None);
+        // There's nothing left for the next update.
+        assert_eq!(matrix.update_dirty(&mut fb), 0);
+    }
+
+    #[test]
+    fn update_dirty_partial_brightness_pass() {
+        let mut matrix = TestMatrix::new(MatrixConfig::default());
+        matrix.set_pixel(3, 4, test_color(3, 4)).unwrap();
+        matrix.set_brightness(200);
+        let mut fb = declare_frame_buffer!(64, 32, 8, 1, 16);
+        matrix.configure_frame_buffer(&mut fb);
+        // Only one pixel is dirty, so the brightness needs its own pass over every scanline.
+        assert_eq!(matrix.update_dirty(&mut fb), 1);
+        for scanline in 0..16 {
+            assert_eq!(
+                lit_words(&fb, scanline),
+                59 * 200 / 255,
+                "scanline {scanline}"
+            );
+        }
+    }
+
+    #[test]
+    fn render_to_reproducible() {
+        fn checksum(fb: &FrameBuffer<64, 32, 1, 8, 16, 64, 16>) -> u32 {
+            // FNV-1a over every word, in the order they're sent.
+            fb.buffer_iter().flatten().fold(0x811c_9dc5, |hash, word| {
+                (hash ^ *word as u32).wrapping_mul(0x0100_0193)
+            })
+        }
+        let mut matrix = TestMatrix::new(MatrixConfig::default());
+        for y in 0..32 {
+            for x in 0..64 {
+                matrix.set_pixel(x, y, test_color(x, y)).unwrap();
+            }
+        }
+        matrix.set_brightness(200);
+        let mut fb = declare_frame_buffer!(64, 32, 8, 1, 16);
+        matrix.render_to(&mut fb);
+        // Start the second buffer with unrelated contents.
+        let mut other_fb = declare_frame_buffer!(64, 32, 8, 1, 16);
+        other_fb.set_pixel(5, 5, 0xFFu8, 0xFF, 0xFF);
+        matrix.render_to(&mut other_fb);
+        assert_eq!(checksum(&fb), checksum(&other_fb));
+        // Rendering doesn't touch the dirty tracking.
+        assert!(matrix.all_dirty());
+    }
+
+    #[test]
+    fn reset() {
+        let mut fb = declare_frame_buffer!(64, 32, 8, 1, 16);
+        let mut next_fb = declare_frame_buffer!(64, 32, 8, 1, 16);
+        let mut matrix = TestMatrix::new(MatrixConfig::default());
+        matrix.configure_frame_buffer(&mut fb);
+        matrix.configure_frame_buffer(&mut next_fb);
+        matrix.set_pending(&mut fb);
+        for y in 0..32 {
+            for x in 0..64 {
+                matrix.set_pixel(x, y, test_color(x, y)).unwrap();
+                next_fb.set_pixel(x, y, 0xFFu8, 0xFF, 0xFF);
+            }
+        }
+        matrix.set_brightness(255);
+
+        matrix.reset();
+
+        assert_eq!(matrix.brightness(), TestMatrix::DEFAULT_BRIGHTNESS);
+        for row in matrix.pixel_buffer.iter() {
+            for color in row.iter().flat_map(|panel_row| panel_row.iter()) {
+                assert_eq!(*color, Rgb888::default());
+            }
+        }
+        let pending = matrix.pending_frame_buffer.as_ref().unwrap();
+        for segment in pending.buffer_iter() {
+            assert!(segment.iter().all(|word| word & 0x3F == 0));
+        }
+        // The next buffer should be redrawn entirely black as well.
+        matrix.set_pending(&mut next_fb);
+        let pending = matrix.pending_frame_buffer.as_ref().unwrap();
+        for segment in pending.buffer_iter() {
+            assert!(segment.iter().all(|word| word & 0x3F == 0));
+        }
+    }
+
+    #[test]
+    fn get_pixel() {
+        let mut matrix = TestMatrix::new(MatrixConfig::default());
+        matrix.set_pixel(63, 31, test_color(63, 31)).unwrap();
+        assert_eq!(matrix.get_pixel(63, 31), Ok(test_color(63, 31)));
+        assert_eq!(matrix.get_pixel(0, 0), Ok(Rgb888::default()));
+        assert_eq!(matrix.get_pixel(64, 0), Err(MatrixError::OutOfBounds));
+        assert_eq!(matrix.get_pixel(0, 32), Err(MatrixError::OutOfBounds));
+    }
+
+    #[test]
+    fn draw_glyph_coverage_blends_with_existing() {
+        let mut matrix = TestMatrix::new(MatrixConfig::default());
+        let red = Rgb888::new(255, 0, 0);
+        let gray = Rgb888::new(128, 128, 128);
+        for x in 0..3 {
+            matrix.set_pixel(x, 0, red).unwrap();
+        }
+        // The edge of a glyph: fully covered, partially covered, then not covered.
+        let glyph = [
+            (Point::new(0, 0), 255),
+            (Point::new(1, 0), 128),
+            (Point::new(2, 0), 0),
+            (Point::new(-1, 0), 255),
+        ];
+        matrix.draw_glyph_coverage(glyph, gray);
+        assert_eq!(matrix.get_pixel(0, 0), Ok(gray));
+        assert_eq!(matrix.get_pixel(1, 0), Ok(Rgb888::new(201, 90, 90)));
+        assert_eq!(matrix.get_pixel(2, 0), Ok(red));
+    }
+
+    #[test]
+    fn add_pixel_saturates() {
+        let mut matrix = TestMatrix::new(MatrixConfig::default());
+        matrix.add_pixel(1, 1, Rgb888::RED).unwrap();
+        matrix.add_pixel(1, 1, Rgb888::GREEN).unwrap();
+        assert_eq!(matrix.get_pixel(1, 1), Ok(Rgb888::YELLOW));
+        matrix.set_pixel(2, 1, Rgb888::new(200, 10, 0)).unwrap();
+        matrix.add_pixel(2, 1, Rgb888::new(100, 10, 0)).unwrap();
+        assert_eq!(matrix.get_pixel(2, 1), Ok(Rgb888::new(255, 20, 0)));
+        assert_eq!(
+            matrix.add_pixel(64, 0, Rgb888::RED),
+            Err(MatrixError::OutOfBounds)
+        );
+    }
+
+    #[test]
+    fn rotation_size_matches_bounds() {
+        let mut config = MatrixConfig::default();
+        config.set_rotation(Rotation::Rot90);
+        let mut matrix = TestMatrix::new(config);
+        assert_eq!(matrix.size(), Size::new(32, 64));
+        // The rotated bottom right corner is the physical bottom left corner.
+        assert_eq!(matrix.set_pixel(31, 63, Rgb888::RED), Ok(()));
+        assert_eq!(matrix.get_pixel(31, 63), Ok(Rgb888::RED));
+        assert_eq!(matrix.pixel_buffer[31][0][0], Rgb888::RED);
+        assert_eq!(
+            matrix.set_pixel(32, 63, Rgb888::RED),
+            Err(MatrixError::OutOfBounds)
+        );
+        assert_eq!(
+            matrix.set_pixel(31, 64, Rgb888::RED),
+            Err(MatrixError::OutOfBounds)
+        );
+
+        // Drawing follows the same bounds, ignoring the pixels past the corner.
+        matrix
+            .draw_iter([
+                Pixel(Point::new(0, 0), Rgb888::GREEN),
+                Pixel(Point::new(32, 0), Rgb888::BLUE),
+            ])
+            .unwrap();
+        assert_eq!(matrix.pixel_buffer[0][0][63], Rgb888::GREEN);
+        assert!(matrix
+            .pixel_buffer
+            .iter()
+            .flatten()
+            .flatten()
+            .all(|color| *color != Rgb888::BLUE));
+    }
+
+    #[test]
+    fn rotation_mapping() {
+        for (rotation, expected) in [
+            (Rotation::Rot0, (1, 2)),
+            (Rotation::Rot90, (61, 1)),
+            (Rotation::Rot180, (62, 29)),
+            (Rotation::Rot270, (2, 30)),
+        ] {
+            let mut config = MatrixConfig::default();
+            config.set_rotation(rotation);
+            let matrix = TestMatrix::new(config);
+            assert_eq!(matrix.to_physical(1, 2), Ok(expected), "{rotation:?}");
+        }
+    }
+
+    #[test]
+    fn map_round_trip() {
+        fn check<
+            const W: usize,
+            const H: usize,
+            const C: usize,
+            const WPP: usize,
+            const B: usize,
+        >(
+            matrix: &RgbMatrix<Rgb888, W, H, C, 8, 5, WPP, 5, B>,
+        ) {
+            let (width, height) = matrix.logical_size();
+            for y in 0..height {
+                for x in 0..width {
+                    let (panel_index, panel_x, panel_y) = matrix.map_logical(x, y).unwrap();
+                    assert!(panel_index < C && panel_x < W && panel_y < H);
+                    assert_eq!(
+                        matrix.map_physical(panel_index, panel_x, panel_y),
+                        Ok((x, y))
+                    );
+                }
+            }
+            assert_eq!(matrix.map_logical(width, 0), Err(MatrixError::OutOfBounds));
+            assert_eq!(matrix.map_physical(C, 0, 0), Err(MatrixError::OutOfBounds));
+            assert_eq!(matrix.map_physical(0, W, 0), Err(MatrixError::OutOfBounds));
+            assert_eq!(matrix.map_physical(0, 0, H), Err(MatrixError::OutOfBounds));
+        }
+        for rotation in [
+            Rotation::Rot0,
+            Rotation::Rot90,
+            Rotation::Rot180,
+            Rotation::Rot270,
+        ] {
+            check(&RgbMatrix::<Rgb888, 10, 10, 1, 8, 5, 10, 5, 4>::new(
+                MatrixConfig::default().with_rotation(rotation),
+            ));
+            check(&RgbMatrix::<Rgb888, 10, 10, 3, 8, 5, 30, 5, 10>::new(
+                MatrixConfig::default().with_rotation(rotation),
+            ));
+        }
+        // The second panel of a chain starts right after the first.
+        let matrix = RgbMatrix::<Rgb888, 10, 10, 3, 8, 5, 30, 5, 10>::new(MatrixConfig::default());
+        assert_eq!(matrix.map_logical(13, 4), Ok((1, 3, 4)));
+        assert_eq!(matrix.map_physical(2, 0, 9), Ok((20, 9)));
+    }
+
+    #[test]
+    fn set_gamma_deferred_to_end_frame() {
+        let mut fb = declare_frame_buffer!(64, 32, 8, 1, 16);
+        let mut matrix = TestMatrix::new(MatrixConfig::default());
+        matrix.configure_frame_buffer(&mut fb);
+        matrix.set_pending(&mut fb);
+        matrix.set_pixel(2, 2, test_color(2, 2)).unwrap();
+        let before = matrix.pending_buffer().unwrap().clone();
+
+        matrix.begin_frame();
+        matrix.set_pixel(3, 3, test_color(3, 3)).unwrap();
+        matrix.set_gamma(Some(GammaTable::squared()));
+        // The pending frame buffer still shows the last finished frame, without the correction.
+        assert_eq!(matrix.pending_buffer(), Some(&before));
+
+        assert!(matrix.end_frame().is_none());
+        let mut expected = declare_frame_buffer!(64, 32, 8, 1, 16);
+        matrix.render_to(&mut expected);
+        assert_eq!(matrix.pending_buffer(), Some(&expected));
+    }
+
+    #[test]
+    fn frame_defers_pending_writes() {
+        let mut fb = declare_frame_buffer!(64, 32, 8, 1, 16);
+        let mut matrix = TestMatrix::new(MatrixConfig::default());
+        matrix.configure_frame_buffer(&mut fb);
+        let untouched = fb.clone();
+        matrix.set_pending(&mut fb);
+        matrix.begin_frame();
+        matrix.set_pixel(1, 2, Rgb888::RED).unwrap();
+        matrix
+            .draw_iter((10..20).map(|x| Pixel(Point::new(x, 5), Rgb888::BLUE)))
+            .unwrap();
+        assert_eq!(matrix.pending_frame_buffer.as_deref(), Some(&untouched));
+        // The changes are still tracked for the next frame buffer.
+        assert_eq!(
+            matrix.dirty_bounds(),
+            Some(Rectangle::with_corners(Point::new(1, 2), Point::new(19, 5)))
+        );
+
+        matrix.end_frame();
+        let mut expected = untouched.clone();
+        expected.set_pixel(1, 2, 255u8, 0, 0);
+        for x in 10..20 {
+            expected.set_pixel(x, 5, 0u8, 0, 255);
+        }
+        assert_eq!(matrix.pending_frame_buffer.as_deref(), Some(&expected));
+
+        // Outside of a frame, pixels are written immediately again.
+        matrix.set_pixel(3, 3, Rgb888::GREEN).unwrap();
+        expected.set_pixel(3, 3, 0u8, 255, 0);
+        assert_eq!(matrix.pending_frame_buffer.as_deref(), Some(&expected));
+    }
+
+    #[test]
+    fn frame_survives_swap() {
+        let mut fb = declare_frame_buffer!(64, 32, 8, 1, 16);
+        let mut next_fb = declare_frame_buffer!(64, 32, 8, 1, 16);
+        let mut matrix = TestMatrix::new(MatrixConfig::default());
+        matrix.configure_frame_buffer(&mut fb);
+        matrix.configure_frame_buffer(&mut next_fb);
+        let untouched = fb.clone();
+        matrix.set_pending(&mut fb);
+        matrix.begin_frame();
+        matrix.set_pixel(1, 2, Rgb888::RED).unwrap();
+        matrix.set_brightness(40);
+        // Swapping in the middle of a frame waits for the frame to be finished, so neither buffer
+        // shows the partial frame.
+        assert!(matrix.set_pending(&mut next_fb).is_none());
+        assert_eq!(matrix.pending_frame_buffer.as_deref(), Some(&untouched));
+        assert_eq!(matrix.deferred_pending.as_deref(), Some(&untouched));
+        let shown = matrix.end_frame().unwrap();
+        let mut expected = declare_frame_buffer!(64, 32, 8, 1, 16);
+        matrix.render_to(&mut expected);
+        assert_eq!(*shown, expected);
+        assert_eq!(matrix.pending_frame_buffer.as_deref(), Some(&expected));
+        // The buffer that was shown still gets the changes when it comes back.
+        matrix.set_pending(shown);
+        assert_eq!(matrix.pending_frame_buffer.as_deref(), Some(&expected));
+    }
+
+    #[test]
+    fn alternating_swaps_stay_in_sync() {
+        let mut first = declare_frame_buffer!(64, 32, 8, 1, 16);
+        let mut second = declare_frame_buffer!(64, 32, 8, 1, 16);
+        let mut expected = declare_frame_buffer!(64, 32, 8, 1, 16);
+        let mut matrix = TestMatrix::new(MatrixConfig::default());
+        matrix.configure_frame_buffer(&mut first);
+        matrix.configure_frame_buffer(&mut second);
+        matrix.set_pending(&mut first);
+        let mut shown = matrix.set_pending(&mut second).unwrap();
+        // Each edit lands in a different swap interval, and some pixels are changed again two
+        // swaps later, after the other frame buffer has been shown.
+        let edits = [
+            (3, 4, Rgb888::RED),
+            (40, 20, Rgb888::GREEN),
+            (3, 4, Rgb888::BLUE),
+            (63, 31, Rgb888::WHITE),
+            (40, 20, Rgb888::BLACK),
+            (0, 0, Rgb888::CYAN),
+        ];
+        for (x, y, color) in edits {
+            matrix.set_pixel(x, y, color).unwrap();
+            if x == 63 {
+                matrix.set_brightness(90);
+            }
+            shown = matrix.set_pending(shown).unwrap();
+            matrix.render_to(&mut expected);
+            assert_eq!(*shown, expected, "shown after setting ({x}, {y})");
+            assert_eq!(
+                matrix.pending_buffer(),
+                Some(&expected),
+                "pending after setting ({x}, {y})"
+            );
+        }
+    }
+
+    #[test]
+    fn maybe_present_skips_unchanged() {
+        let mut first = declare_frame_buffer!(64, 32, 8, 1, 16);
+        let mut second = declare_frame_buffer!(64, 32, 8, 1, 16);
+        let mut matrix = TestMatrix::new(MatrixConfig::default());
+        matrix.configure_frame_buffer(&mut first);
+        matrix.configure_frame_buffer(&mut second);
+        let first_address: *const _ = &first;
+        let second_address: *const _ = &second;
+        matrix.set_pending(&mut first);
+        let mut back = &mut second;
+
+        matrix.set_pixel(7, 9, Rgb888::RED).unwrap();
+        assert!(matrix.maybe_present(&mut back));
+        assert!(core::ptr::eq(&*back, first_address));
+        let mut expected = declare_frame_buffer!(64, 32, 8, 1, 16);
+        matrix.render_to(&mut expected);
+        assert_eq!(*back, expected);
+
+        // Setting a pixel to the color it already has isn't a change.
+        matrix.set_pixel(7, 9, Rgb888::RED).unwrap();
+        assert!(!matrix.maybe_present(&mut back));
+        assert!(core::ptr::eq(&*back, first_address));
+        assert!(core::ptr::eq(
+            matrix.pending_buffer().unwrap(),
+            second_address
+        ));
+    }
+
+    #[test]
+    fn dropped_pixels() {
+        let mut matrix = TestMatrix::new(MatrixConfig::default());
+        matrix.set_pixel(63, 31, Rgb888::RED).unwrap();
+        assert_eq!(matrix.dropped_pixels(), 0);
+        assert_eq!(
+            matrix.set_pixel(64, 0, Rgb888::RED),
+            Err(MatrixError::OutOfBounds)
+        );
+        assert_eq!(matrix.dropped_pixels(), 1);
+        matrix
+            .draw_iter([
+                Pixel(Point::new(-1, 5), Rgb888::RED),
+                Pixel(Point::new(10, 5), Rgb888::RED),
+                Pixel(Point::new(10, 32), Rgb888::RED),
+            ])
+            .unwrap();
+        assert_eq!(matrix.dropped_pixels(), 3);
+        assert_eq!(matrix.get_pixel(10, 5), Ok(Rgb888::RED));
+        matrix.reset_dropped_pixels();
+        assert_eq!(matrix.dropped_pixels(), 0);
+    }
+
+    #[test]
+    fn flush_into_matches_set_pending() {
+        let draw = |matrix: &mut TestMatrix| {
+            matrix.set_pixel(4, 9, Rgb888::RED).unwrap();
+            matrix.set_pixel(60, 30, test_color(60, 30)).unwrap();
+            matrix.set_brightness(70);
+        };
+        let mut pending = declare_frame_buffer!(64, 32, 8, 1, 16);
+        let mut flushed = declare_frame_buffer!(64, 32, 8, 1, 16);
+        let mut matrix = TestMatrix::new(MatrixConfig::default());
+        matrix.configure_frame_buffer(&mut pending);
+        matrix.configure_frame_buffer(&mut flushed);
+        let mut swapped = flushed.clone();
+        matrix.set_pending(&mut pending);
+        draw(&mut matrix);
+        matrix.flush_into(&mut flushed);
+        // The changes are left for the next frame buffer passed to `set_pending`.
+        assert!(matrix.pending_differs());
+
+        let mut other_pending = declare_frame_buffer!(64, 32, 8, 1, 16);
+        let mut other = TestMatrix::new(MatrixConfig::default());
+        other.configure_frame_buffer(&mut other_pending);
+        other.set_pending(&mut other_pending);
+        draw(&mut other);
+        other.set_pending(&mut swapped);
+        assert_eq!(flushed, swapped);
+    }
+
+    #[test]
+    fn full_rewrite_hook() {
+        let rewrites = core::cell::RefCell::new([0usize; 2]);
+        let hook = |rewrite| rewrites.borrow_mut()[rewrite as usize] += 1;
+        let mut fb = declare_frame_buffer!(64, 32, 8, 1, 16);
+        let mut next_fb = declare_frame_buffer!(64, 32, 8, 1, 16);
+        let mut matrix = TestMatrix::new(MatrixConfig::default());
+        matrix.set_full_rewrite_hook(&hook);
+        matrix.configure_frame_buffer(&mut fb);
+        matrix.configure_frame_buffer(&mut next_fb);
+        assert_eq!(*rewrites.borrow(), [2, 2]);
+        matrix.set_pending(&mut fb);
+        matrix.set_pixel(1, 1, Rgb888::RED).unwrap();
+        assert_eq!(*rewrites.borrow(), [2, 2]);
+
+        // Changing the brightness rewrites the brightness bits of the pending frame buffer, then
+        // the next frame buffer once.
+        matrix.set_brightness(200);
+        assert_eq!(*rewrites.borrow(), [2, 3]);
+        let fb = matrix.set_pending(&mut next_fb).unwrap();
+        assert_eq!(*rewrites.borrow(), [2, 4]);
+
+        // Configuring with scanline brightness set still only rewrites the brightness bits once.
+        let mut brightness = [u8::MAX; 16];
+        brightness[3] = 100;
+        matrix.set_scanline_brightness(&brightness).unwrap();
+        assert_eq!(*rewrites.borrow(), [2, 5]);
+        let mut other_fb = declare_frame_buffer!(64, 32, 8, 1, 16);
+        matrix.configure_frame_buffer(&mut other_fb);
+        assert_eq!(*rewrites.borrow(), [3, 6]);
+
+        // Writing the brightness along with every pixel counts as rewriting it.
+        matrix.set_brightness(100);
+        matrix.assume_desynced();
+        assert_eq!(*rewrites.borrow(), [3, 7]);
+        matrix.set_pending(fb);
+        assert_eq!(*rewrites.borrow(), [3, 8]);
+        matrix.set_gamma(Some(GammaTable::squared()));
+        assert_eq!(*rewrites.borrow(), [3, 9]);
+        matrix.render_to(&mut other_fb);
+        assert_eq!(*rewrites.borrow(), [4, 10]);
+        matrix.clear_full_rewrite_hook();
+    }
+
+    #[test]
+    fn native_depth_scaled_down() {
+        /// 8 bit components shown at a color depth of 5.
+        #[derive(Clone, Copy, PartialEq)]
+        struct Rgb888At5(Rgb888);
+
+        impl Color<5> for Rgb888At5 {
+            type Storage = u8;
+
+            const NATIVE_DEPTH: usize = 8;
+
+            fn new<R: AsRef<u8>, G: AsRef<u8>, B: AsRef<u8>>(red: R, green: G, blue: B) -> Self {
+                Self(Rgb888::new(*red.as_ref(), *green.as_ref(), *blue.as_ref()))
+            }
+
+            fn red(&self) -> u8 {
+                self.0.r()
+            }
+
+            fn green(&self) -> u8 {
+                self.0.g()
+            }
+
+            fn blue(&self) -> u8 {
+                self.0.b()
+            }
+        }
+
+        // Only the low 5 bits of 0xFF would be packed without scaling, but those happen to all be
+        // set. 0xE0 shows the difference, as its low 5 bits are all clear.
+        let bits = |red, green, blue| {
+            corrected_plane_bits(&Rgb888At5(Rgb888::new(red, green, blue)), None, u8::MAX)
+        };
+        assert_eq!(bits(0xFF, 0xFF, 0xFF), [0b111; 5]);
+        assert_eq!(bits(0xE0, 0, 0), plane_bits(28u8, 0, 0));
+        // Rounded to the nearest value, without overflowing.
+        assert_eq!(bits(0x80, 0x04, 0x03), plane_bits(16u8, 1, 0));
+        assert_eq!(bits(0xFC, 0, 0), plane_bits(31u8, 0, 0));
+    }
+
+    #[test]
+    fn color_cache_solid_run() {
+        let mut cache = ColorCache::<Rgb888, 8>::new();
+        let color = Rgb888::new(0x12, 0x34, 0x56);
+        let expected = plane_bits(color.red(), color.green(), color.blue());
+        for _ in 0..100 {
+            assert_eq!(cache.plane_bits(color, None, u8::MAX), &expected);
+        }
+        assert_eq!(cache.decompositions, 1);
+    }
+
+    #[test]
+    fn color_cache_alternating() {
+        let mut cache = ColorCache::<Rgb888, 8>::new();
+        for index in 0..100 {
+            let color = test_color(index % 2, 0);
+            let expected = plane_bits(color.red(), color.green(), color.blue());
+            assert_eq!(cache.plane_bits(color, None, u8::MAX), &expected);
+        }
+        assert_eq!(cache.decompositions, 100);
+    }
+
+    #[test]
+    fn draw_direct_matches_tracked() {
+        let config = MatrixConfig::default()
+            .with_rotation(Rotation::Rot90)
+            .with_panel_brightness([128]);
+        let mut tracked_fb = declare_frame_buffer!(64, 32, 8, 1, 16);
+        let mut direct_fb = declare_frame_buffer!(64, 32, 8, 1, 16);
+        let mut tracked = TestMatrix::new(config);
+        let mut direct = TestMatrix::new(config);
+        tracked.configure_frame_buffer(&mut tracked_fb);
+        direct.configure_frame_buffer(&mut direct_fb);
+        tracked.set_pending(&mut tracked_fb);
+        direct.set_pending(&mut direct_fb);
+
+        // Rotated, so 32 wide and 64 high. The out of bounds pixels are skipped.
+        let pixels = || {
+            (-1..33).flat_map(|x| {
+                (0..65).map(move |y| {
+                    (
+                        Point::new(x, y),
+                        test_color(x.rem_euclid(64) as usize, y as usize % 32),
+                    )
+                })
+            })
+        };
+        tracked
+            .draw_iter(pixels().map(|(point, color)| Pixel(point, color)))
+            .unwrap();
+        assert_eq!(direct.draw_direct(pixels()), 32 * 64);
+        assert_eq!(direct.pending_buffer(), tracked.pending_buffer());
+
+        // Nothing was remembered.
+        assert_eq!(direct.get_pixel(5, 5).unwrap(), Rgb888::default());
+        assert!(!direct.pending_differs());
+    }
+
+    #[test]
+    fn draw_direct_skipped_in_frame() {
+        let mut fb = declare_frame_buffer!(64, 32, 8, 1, 16);
+        let mut matrix = TestMatrix::new(MatrixConfig::default());
+        matrix.configure_frame_buffer(&mut fb);
+        matrix.set_pending(&mut fb);
+        let before = matrix.pending_buffer().unwrap().clone();
+
+        matrix.begin_frame();
+        let pixels = || (0..4).map(|x| (Point::new(x, 0), Rgb888::RED));
+        assert_eq!(matrix.draw_direct(pixels()), 0);
+        assert_eq!(*matrix.pending_buffer().unwrap(), before);
+        matrix.end_frame();
+        assert_eq!(*matrix.pending_buffer().unwrap(), before);
+        assert_eq!(matrix.draw_direct(pixels()), 4);
+    }
+
+    #[test]
+    fn pending_buffer_reflects_drawing() {
+        let mut fb = declare_frame_buffer!(64, 32, 8, 1, 16);
+        let mut expected = declare_frame_buffer!(64, 32, 8, 1, 16);
+        let mut matrix = TestMatrix::new(MatrixConfig::default());
+        assert!(matrix.pending_buffer().is_none());
+        matrix.configure_frame_buffer(&mut fb);
+        matrix.configure_frame_buffer(&mut expected);
+        matrix.set_pending(&mut fb);
+        matrix.begin_frame();
+        for (x, y) in [(3, 4), (40, 20), (63, 31)] {
+            let color = test_color(x, y);
+            matrix.set_pixel(x, y, color).unwrap();
+            expected.set_pixel(x, y, color.red(), color.green(), color.blue());
+        }
+        matrix.end_frame();
+        assert_eq!(matrix.pending_buffer(), Some(&expected));
+
+        matrix
+            .pending_buffer_mut()
+            .unwrap()
+            .set_pixel(0, 0, 255u8, 0, 0);
+        expected.set_pixel(0, 0, 255u8, 0, 0);
+        assert_eq!(matrix.pending_buffer(), Some(&expected));
+    }
+
+    #[test]
+    fn flush_into_keeps_changes_for_other_frame_buffers() {
+        let mut buffers = [
+            declare_frame_buffer!(64, 32, 8, 1, 16),
+            declare_frame_buffer!(64, 32, 8, 1, 16),
+            declare_frame_buffer!(64, 32, 8, 1, 16),
+        ];
+        let mut matrix = TestMatrix::new(MatrixConfig::default());
+        for fb in buffers.iter_mut() {
+            matrix.configure_frame_buffer(fb);
+        }
+        let [first, second, third] = &mut buffers;
+        let mut expected = declare_frame_buffer!(64, 32, 8, 1, 16);
+        matrix.set_pending(first);
+        matrix.set_pixel(3, 3, Rgb888::RED).unwrap();
+        matrix.flush_into(third);
+        let first = matrix.set_pending(second).unwrap();
+        matrix.set_pixel(8, 20, Rgb888::GREEN).unwrap();
+        matrix.set_brightness(40);
+        matrix.flush_into(first);
+        let second = matrix.set_pending(third).unwrap();
+        matrix.render_to(&mut expected);
+        assert_eq!(*first, expected);
+        assert_eq!(matrix.pending_buffer(), Some(&expected));
+        // Swapping back to the second frame buffer keeps it in sync as well.
+        matrix.set_pending(second);
+        assert_eq!(matrix.pending_buffer(), Some(&expected));
+    }
+
+    #[test]
+    fn pending_differs_across_commit() {
+        let mut fb = declare_frame_buffer!(64, 32, 8, 1, 16);
+        let mut next_fb = declare_frame_buffer!(64, 32, 8, 1, 16);
+        let mut matrix = TestMatrix::new(MatrixConfig::default());
+        matrix.configure_frame_buffer(&mut fb);
+        matrix.configure_frame_buffer(&mut next_fb);
+        matrix.set_pending(&mut fb);
+        assert!(!matrix.pending_differs());
+        // Setting a pixel to the color it already is isn't a change.
+        matrix.set_pixel(3, 4, Rgb888::default()).unwrap();
+        assert!(!matrix.pending_differs());
+        matrix.set_pixel(3, 4, Rgb888::RED).unwrap();
+        assert!(matrix.pending_differs());
+        let shown = matrix.set_pending(&mut next_fb).unwrap();
+        assert!(!matrix.pending_differs());
+        matrix.set_brightness(matrix.brightness() / 2);
+        assert!(matrix.pending_differs());
+        matrix.set_pending(shown);
+        assert!(!matrix.pending_differs());
+    }
+
+    #[test]
+    fn dirty_bounds_unchanged() {
+        let mut matrix = TestMatrix::new(MatrixConfig::default());
+        assert_eq!(matrix.dirty_bounds(), None);
+        // Setting a pixel to the color it already is doesn't make it dirty.
+        matrix.set_pixel(4, 5, Rgb888::default()).unwrap();
+        assert_eq!(matrix.dirty_bounds(), None);
+    }
+
+    #[test]
+    fn dirty_bounds_single_pixel() {
+        let mut matrix = TestMatrix::new(MatrixConfig::default());
+        matrix.set_pixel(4, 5, test_color(4, 5)).unwrap();
+        assert_eq!(
+            matrix.dirty_bounds(),
+            Some(Rectangle::new(Point::new(4, 5), Size::new(1, 1)))
+        );
+    }
+
+    #[test]
+    fn dirty_bounds_scattered() {
+        let mut matrix = TestMatrix::new(MatrixConfig::default());
+        matrix.set_pixel(10, 20, test_color(10, 20)).unwrap();
+        matrix.set_pixel(3, 25, test_color(3, 25)).unwrap();
+        matrix.set_pixel(40, 2, test_color(40, 2)).unwrap();
+        assert_eq!(
+            matrix.dirty_bounds(),
+            Some(Rectangle::with_corners(
+                Point::new(3, 2),
+                Point::new(40, 25)
+            ))
+        );
+        let mut fb = declare_frame_buffer!(64, 32, 8, 1, 16);
+        matrix.set_pending(&mut fb);
+        assert_eq!(matrix.dirty_bounds(), None);
+    }
+
+    #[test]
+    fn set_brightness_checked_floor() {
+        let mut config = MatrixConfig::default();
+        config.set_min_brightness(16);
+        let mut matrix = TestMatrix::new(config);
+        assert_eq!(matrix.set_brightness_checked(200), Ok(()));
+        assert_eq!(matrix.brightness(), 200);
+        assert_eq!(
+            matrix.set_brightness_checked(3),
+            Err(MatrixError::BrightnessBelowMinimum)
+        );
+        assert_eq!(matrix.brightness(), 16);
+        // The unchecked setter can still go below the minimum.
+        matrix.set_brightness(0);
+        assert_eq!(matrix.brightness(), 0);
+    }
+
+    #[test]
+    fn set_brightness_checked_default_rejects_zero() {
+        let mut matrix = TestMatrix::new(MatrixConfig::default());
+        assert_eq!(
+            matrix.set_brightness_checked(0),
+            Err(MatrixError::BrightnessBelowMinimum)
+        );
+        assert_eq!(matrix.brightness(), 1);
+    }
+
+    #[test]
+    fn idle_pattern_until_first_pending() {
+        let mut fb = declare_frame_buffer!(64, 32, 8, 1, 16);
+        let mut matrix = TestMatrix::new(MatrixConfig::default());
+        matrix.set_idle_pattern(IdlePattern::Solid(Rgb888::RED));
+        matrix.configure_frame_buffer(&mut fb);
+        // Red on both lanes, and nothing else.
+        for segment in fb.buffer_iter() {
+            assert!(segment.iter().all(|word| word & 0x3F == 0b00_1001));
+        }
+        // The pixels are all still black, so the idle pattern is replaced.
+        matrix.set_pending(&mut fb);
+        let pending = matrix.pending_frame_buffer.as_ref().unwrap();
+        for segment in pending.buffer_iter() {
+            assert!(segment.iter().all(|word| word & 0x3F == 0));
+        }
+    }
+
+    #[test]
+    fn idle_pattern_replaced_in_every_frame_buffer() {
+        let mut first = declare_frame_buffer!(64, 32, 8, 1, 16);
+        let mut second = declare_frame_buffer!(64, 32, 8, 1, 16);
+        let mut matrix = TestMatrix::new(MatrixConfig::default());
+        matrix.set_idle_pattern(IdlePattern::Solid(Rgb888::RED));
+        matrix.configure_frame_buffer(&mut first);
+        matrix.configure_frame_buffer(&mut second);
+        matrix.set_pending(&mut first);
+        matrix.set_pixel(4, 9, Rgb888::BLUE).unwrap();
+        // The second frame buffer was showing the idle pattern too, so it is fully redrawn instead
+        // of only getting the pixel changed since the first swap.
+        let first = matrix.set_pending(&mut second).unwrap();
+        let second = matrix.pending_frame_buffer.as_ref().unwrap();
+        assert_eq!(first, *second);
+        for segment in second.buffer_iter() {
+            assert!(segment.iter().all(|word| word & 0b00_1001 == 0));
+        }
+    }
+
+    #[test]
+    fn idle_pattern_swaps_stay_in_sync() {
+        let mut first = declare_frame_buffer!(64, 32, 8, 1, 16);
+        let mut second = declare_frame_buffer!(64, 32, 8, 1, 16);
+        let mut matrix = TestMatrix::new(MatrixConfig::default());
+        matrix.set_idle_pattern(IdlePattern::ColorBars);
+        matrix.configure_frame_buffer(&mut first);
+        matrix.configure_frame_buffer(&mut second);
+        let mut expected = declare_frame_buffer!(64, 32, 8, 1, 16);
+        let mut reference = TestMatrix::new(MatrixConfig::default());
+        reference.configure_frame_buffer(&mut expected);
+
+        let mut back = Some(&mut second);
+        matrix.set_pending(&mut first);
+        for step in 0..4 {
+            matrix.set_pixel(step * 3, step, Rgb888::GREEN).unwrap();
+            reference.set_pixel(step * 3, step, Rgb888::GREEN).unwrap();
+            back = matrix.set_pending(back.take().unwrap());
+        }
+        reference.render_to(&mut expected);
+        assert_eq!(**back.as_ref().unwrap(), expected);
+        assert_eq!(*matrix.pending_frame_buffer.as_deref().unwrap(), expected);
+    }
+
+    #[test]
+    fn double_buffered_swaps() {
+        let mut first = declare_frame_buffer!(64, 32, 8, 1, 16);
+        let mut second = declare_frame_buffer!(64, 32, 8, 1, 16);
+        let mut matrix = TestMatrix::new(MatrixConfig::default());
+        let mut expected = declare_frame_buffer!(64, 32, 8, 1, 16);
+        let mut reference = TestMatrix::new(MatrixConfig::default());
+        matrix.set_pixel(0, 0, Rgb888::RED).unwrap();
+        reference.set_pixel(0, 0, Rgb888::RED).unwrap();
+
+        // With a single frame buffer nothing has been swapped out to be shown.
+        matrix.configure_frame_buffer(&mut first);
+        assert!(matrix.set_pending(&mut first).is_none());
+        assert!(!matrix.is_double_buffered());
+
+        // The first frame buffer swapped in (configured without the idle pattern, as there is
+        // already a pending frame buffer) is missing the pixels drawn before, so it is redrawn.
+        matrix.configure_frame_buffer(&mut second);
+        matrix.set_pixel(1, 0, Rgb888::GREEN).unwrap();
+        reference.set_pixel(1, 0, Rgb888::GREEN).unwrap();
+        let shown = matrix.set_pending(&mut second).unwrap();
+        assert!(matrix.is_double_buffered());
+        reference.render_to(&mut expected);
+        assert_eq!(*shown, expected);
+        assert_eq!(*matrix.pending_frame_buffer.as_deref().unwrap(), expected);
+
+        // From then on only the changes since the last swap are written.
+        shown.set_pixel(5, 5, 0xFFu8, 0, 0);
+        matrix.set_pixel(2, 0, Rgb888::BLUE).unwrap();
+        let shown = matrix.set_pending(shown).unwrap();
+        let red_at = |fb: &FrameBuffer<64, 32, 1, 8, 16, 64, 16>, x: usize, y: usize| {
+            let location = fb.locate(x, y);
+            fb.plane(location.scanline, 7).unwrap()[location.word_index].red(location.lane)
+        };
+        let back = matrix.pending_frame_buffer.as_deref().unwrap();
+        assert!(red_at(back, 5, 5));
+        assert!(!red_at(shown, 5, 5));
+    }
+
+    #[test]
+    fn idle_pattern_color_bars() {
+        let mut fb = declare_frame_buffer!(64, 32, 8, 1, 16);
+        let mut matrix = TestMatrix::new(MatrixConfig::default());
+        matrix.set_idle_pattern(IdlePattern::ColorBars);
+        matrix.configure_frame_buffer(&mut fb);
+        let bar_width = 64 / 8;
+        for bar in 0..8 {
+            let location = fb.locate(bar * bar_width, 5);
+            for plane in 0..8 {
+                let word = fb.plane(location.scanline, plane).unwrap()[location.word_index];
+                assert_eq!(word.red(location.lane), bar & 0b001 != 0);
+                assert_eq!(word.green(location.lane), bar & 0b010 != 0);
+                assert_eq!(word.blue(location.lane), bar & 0b100 != 0);
+            }
+        }
+    }
+
+    #[test]
+    fn effective_brightness_percent() {
+        let config = MatrixConfig::default();
+        let mut matrix = TestMatrix::new(config);
+        matrix.set_brightness(255);
+        // Two blanking columns on each side of the latch column is 5 dark columns out of 64.
+        assert_eq!(matrix.effective_brightness_percent(), 92);
+        matrix.set_brightness(0);
+        assert_eq!(matrix.effective_brightness_percent(), 0);
+    }
+
+    #[test]
+    fn effective_brightness_percent_large_blanking() {
+        let mut config = MatrixConfig::default();
+        config.set_latch_blanking_count(4);
+        let mut matrix = TestMatrix::new(config);
+        matrix.set_brightness(255);
+        let percent = matrix.effective_brightness_percent();
+        assert!(percent < 100);
+        // 9 of the 64 columns are always dark.
+        assert_eq!(percent, 85);
+    }
+
+    #[test]
+    fn assume_synced() {
+        let mut fb = declare_frame_buffer!(64, 32, 8, 1, 16);
+        let mut matrix = TestMatrix::new(MatrixConfig::default());
+        matrix.configure_frame_buffer(&mut fb);
+        matrix.set_pixel(3, 4, Rgb888::RED).unwrap();
+        matrix.set_brightness(10);
+        assert!(matrix.dirty_bounds().is_some());
+
+        matrix.assume_synced();
+        assert_eq!(matrix.dirty_bounds(), None);
+        assert!(!matrix.brightness_dirty);
+        // Nothing is written to the next buffer.
+        assert_eq!(matrix.update_dirty(&mut fb), 0);
+        let location = fb.locate(3, 4);
+        let word = fb.plane(location.scanline, 7).unwrap()[location.word_index];
+        assert!(!word.red(location.lane));
+    }
+
+    #[test]
+    fn assume_desynced() {
+        let mut fb = declare_frame_buffer!(64, 32, 8, 1, 16);
+        let mut matrix = TestMatrix::new(MatrixConfig::default());
+        matrix.configure_frame_buffer(&mut fb);
+        assert_eq!(matrix.dirty_bounds(), None);
+
+        matrix.assume_desynced();
+        assert!(matrix.all_dirty());
+        assert!(matrix.brightness_dirty);
+        // Every pixel is redrawn, with the brightness applied in the same pass.
+        assert_eq!(matrix.update_dirty(&mut fb), 64 * 32);
+        assert_eq!(matrix.dirty_bounds(), None);
+        assert_eq!(matrix.update_dirty(&mut fb), 0);
+    }
+
+    #[test]
+    fn draw_iter_batches_spans() {
+        let mut fb = declare_frame_buffer!(64, 32, 8, 1, 16);
+        let mut matrix = TestMatrix::new(MatrixConfig::default());
+        matrix.configure_frame_buffer(&mut fb);
+        matrix.set_pending(&mut fb);
+        let line = (10..40).map(|x| Pixel(Point::new(x, 7), Rgb888::GREEN));
+        matrix.draw_iter(line).unwrap();
+        // The whole line is written as one span, instead of a pixel at a time.
+        assert_eq!(matrix.span_writes, 1);
+        assert_eq!(
+            matrix.dirty_bounds(),
+            Some(Rectangle::with_corners(
+                Point::new(10, 7),
+                Point::new(39, 7)
+            ))
+        );
+        for x in 0..64 {
+            let expected = if (10..40).contains(&x) {
+                Rgb888::GREEN
+            } else {
+                Rgb888::BLACK
+            };
+            assert_eq!(matrix.get_pixel(x, 7).unwrap(), expected);
+            let pending = matrix.pending_frame_buffer.as_ref().unwrap();
+            let location = pending.locate(x, 7);
+            let word = pending.plane(location.scanline, 7).unwrap()[location.word_index];
+            assert_eq!(word.green(location.lane), expected == Rgb888::GREEN);
+        }
+    }
+
+    #[test]
+    fn draw_iter_splits_spans() {
+        let mut fb = declare_frame_buffer!(64, 32, 8, 1, 16);
+        let mut matrix = TestMatrix::new(MatrixConfig::default());
+        matrix.configure_frame_buffer(&mut fb);
+        let mut expected = fb.clone();
+        matrix.set_pending(&mut fb);
+        let pixels = [
+            Pixel(Point::new(0, 0), Rgb888::RED),
+            Pixel(Point::new(1, 0), Rgb888::RED),
+            // Different color
+            Pixel(Point::new(2, 0), Rgb888::BLUE),
+            // Not adjacent
+            Pixel(Point::new(4, 0), Rgb888::BLUE),
+            // Different row
+            Pixel(Point::new(5, 1), Rgb888::BLUE),
+            // Off the matrix
+            Pixel(Point::new(-1, 1), Rgb888::BLUE),
+            Pixel(Point::new(6, 1), Rgb888::BLUE),
+        ];
+        matrix.draw_iter(pixels).unwrap();
+        // The skipped pixel doesn't split the last span.
+        assert_eq!(matrix.span_writes, 4);
+        assert_eq!(matrix.get_pixel(1, 0).unwrap(), Rgb888::RED);
+        assert_eq!(matrix.get_pixel(3, 0).unwrap(), Rgb888::BLACK);
+        assert_eq!(matrix.get_pixel(6, 1).unwrap(), Rgb888::BLUE);
+        // Each span is written with its own color, and nothing between them.
+        for x in 0..2 {
+            expected.set_pixel(x, 0, 255u8, 0, 0);
+        }
+        for (x, y) in [(2, 0), (4, 0), (5, 1), (6, 1)] {
+            expected.set_pixel(x, y, 0u8, 0, 255);
+        }
+        assert_eq!(matrix.pending_buffer(), Some(&expected));
+        assert_eq!(matrix.dropped_pixels(), 1);
+    }
+
+    #[test]
+    fn gamma_applied_to_matrix_draws() {
+        let mut fb = declare_frame_buffer!(64, 32, 8, 1, 16);
+        let mut matrix = TestMatrix::new(MatrixConfig::default());
+        matrix.set_gamma(Some(GammaTable::squared()));
+        matrix.configure_frame_buffer(&mut fb);
+        matrix.set_pending(&mut fb);
+        matrix.set_pixel(2, 3, Rgb888::new(128, 0, 0)).unwrap();
+        let pending = matrix.pending_frame_buffer.as_ref().unwrap();
+        let location = pending.locate(2, 3);
+        let red_bits: u8 = (0..8)
+            .filter(|plane| {
+                pending.plane(location.scanline, *plane).unwrap()[location.word_index]
+                    .red(location.lane)
+            })
+            .map(|plane| 1 << plane)
+            .sum();
+        assert_eq!(red_bits, 64);
+        // The shadow pixels are not corrected.
+        assert_eq!(matrix.get_pixel(2, 3).unwrap(), Rgb888::new(128, 0, 0));
+    }
+
+    #[test]
+    fn gamma_not_applied_to_raw_writes() {
+        let mut fb = declare_frame_buffer!(64, 32, 8, 1, 16);
+        let mut matrix = TestMatrix::new(MatrixConfig::default());
+        matrix.set_gamma(Some(GammaTable::squared()));
+        matrix.configure_frame_buffer(&mut fb);
+        fb.set_pixel_raw(2, 3, 128u8, 0, 0);
+        let location = fb.locate(2, 3);
+        let red_bits: u8 = (0..8)
+            .filter(|plane| {
+                fb.plane(location.scanline, *plane).unwrap()[location.word_index].red(location.lane)
+            })
+            .map(|plane| 1 << plane)
+            .sum();
+        assert_eq!(red_bits, 128);
+    }
+
+    #[test]
+    fn panel_brightness_trims_colors() {
+        let mut config = MatrixConfig::default();
+        config.set_panel_brightness([255, 128]);
+        let mut fb = declare_frame_buffer!(32, 16, 8, 2, 8);
+        let mut matrix = RgbMatrix::<Rgb888, 32, 16, 2, 8, 8, 64, 8, 32>::new(config);
+        matrix.configure_frame_buffer(&mut fb);
+        matrix.set_pending(&mut fb);
+        let color = Rgb888::new(200, 0, 0);
+        // One pixel on each panel, and a span crossing from the first panel to the second.
+        matrix.set_pixel(3, 5, color).unwrap();
+        matrix.set_pixel(35, 5, color).unwrap();
+        matrix
+            .draw_iter((30..34).map(|x| Pixel(Point::new(x, 9), color)))
+            .unwrap();
+        let pending = matrix.pending_frame_buffer.as_ref().unwrap();
+        let red_bits = |x: usize, y: usize| -> u8 {
+            let location = pending.locate(x, y);
+            (0..8)
+                .filter(|plane| {
+                    pending.plane(location.scanline, *plane).unwrap()[location.word_index]
+                        .red(location.lane)
+                })
+                .map(|plane| 1 << plane)
+                .sum()
+        };
+        assert_eq!(red_bits(3, 5), 200);
+        assert_eq!(red_bits(35, 5), 100);
+        assert_eq!(red_bits(31, 9), 200);
+        assert_eq!(red_bits(32, 9), 100);
+    }
+}
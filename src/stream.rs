@@ -0,0 +1,315 @@
+use core::iter;
+
+use super::buffer::{plane_bits, FrameBuffer, LaneMapping, LaneOrder, PixelLocation, Scanline};
+use super::color::{depth_components, Color};
+use super::config::MatrixConfig;
+use super::matrix_word::BufferWord;
+use crate::{const_check, const_not_zero};
+
+/// Pack and send a frame one scanline at a time, for when there isn't enough memory for an entire
+/// `FrameBuffer`.
+///
+/// Only a single scanline is held in memory, so pixels are pulled from a callback as each scanline
+/// is packed instead of being drawn at arbitrary locations. The color planes of each scanline are
+/// sent before moving on to the next scanline, with each plane repeated to give it its binary
+/// weight, the same as the planes of a `FrameBuffer`.
+pub struct ScanlineStream<
+    const WIDTH: usize,
+    const HEIGHT: usize,
+    const CHAIN_LENGTH: usize,
+    const COLOR_DEPTH: usize,
+    const PER_FRAME_DENOMINATOR: u8,
+    const WORDS_PER_PLANE: usize,
+    const SCANLINES_PER_FRAME: usize,
+    Word = u16,
+> {
+    staging: Scanline<
+        WIDTH,
+        HEIGHT,
+        CHAIN_LENGTH,
+        COLOR_DEPTH,
+        PER_FRAME_DENOMINATOR,
+        WORDS_PER_PLANE,
+        Word,
+    >,
+
+    lane_mapping: LaneMapping,
+
+    lane_order: LaneOrder,
+
+    column_offset: u16,
+}
+
+impl<
+        const WIDTH: usize,
+        const HEIGHT: usize,
+        const CHAIN_LENGTH: usize,
+        const COLOR_DEPTH: usize,
+        const PER_FRAME_DENOMINATOR: u8,
+        const WORDS_PER_PLANE: usize,
+        const SCANLINES_PER_FRAME: usize,
+        Word: BufferWord,
+    >
+    ScanlineStream<
+        WIDTH,
+        HEIGHT,
+        CHAIN_LENGTH,
+        COLOR_DEPTH,
+        PER_FRAME_DENOMINATOR,
+        WORDS_PER_PLANE,
+        SCANLINES_PER_FRAME,
+        Word,
+    >
+{
+    const_not_zero!(WIDTH, usize);
+    const_not_zero!(HEIGHT, usize);
+    const_not_zero!(CHAIN_LENGTH, usize);
+    const_not_zero!(COLOR_DEPTH, usize);
+    const_not_zero!(PER_FRAME_DENOMINATOR, u8);
+
+    pub const SCANLINES_PER_FRAME: usize = const_check!(
+        SCANLINES_PER_FRAME,
+        SCANLINES_PER_FRAME == (HEIGHT / (HEIGHT / PER_FRAME_DENOMINATOR as usize)) && (SCANLINES_PER_FRAME <= 32),
+        "SCANLINES_PER_FRAME must equal HEIGHT / (HEIGHT / PER_FRAME_DENOMINATOR), and be less than or equal to 32"
+    );
+
+    pub const fn new() -> Self {
+        // Force the compiler to evaluate all the const checks
+        let _ = Self::WIDTH;
+        let _ = Self::HEIGHT;
+        let _ = Self::CHAIN_LENGTH;
+        let _ = Self::COLOR_DEPTH;
+        let _ = Self::PER_FRAME_DENOMINATOR;
+        let _ = Self::SCANLINES_PER_FRAME;
+
+        Self {
+            staging: Scanline::new(),
+            lane_mapping: LaneMapping::Halves,
+            lane_order: LaneOrder::Normal,
+            column_offset: 0,
+        }
+    }
+
+    pub fn lane_mapping(&self) -> LaneMapping {
+        self.lane_mapping
+    }
+
+    pub fn set_lane_mapping(&mut self, lane_mapping: LaneMapping) {
+        self.lane_mapping = lane_mapping;
+    }
+
+    pub fn lane_order(&self) -> LaneOrder {
+        self.lane_order
+    }
+
+    pub fn set_lane_order(&mut self, lane_order: LaneOrder) {
+        self.lane_order = lane_order;
+    }
+
+    pub fn column_offset(&self) -> u16 {
+        self.column_offset
+    }
+
+    pub fn set_column_offset(&mut self, column_offset: u16) {
+        self.column_offset = column_offset;
+    }
+
+    /// Pack the pixels of a single scanline into the staging buffer.
+    ///
+    /// `pixel` is called with the `x` and `y` coordinates of every pixel shown on the scanline.
+    pub fn pack_scanline<ColorType, F>(
+        &mut self,
+        config: &MatrixConfig<WIDTH, HEIGHT, CHAIN_LENGTH, COLOR_DEPTH, PER_FRAME_DENOMINATOR>,
+        brightness: u8,
+        scanline: usize,
+        mut pixel: F,
+    ) where
+        ColorType: Color<COLOR_DEPTH>,
+        F: FnMut(usize, usize) -> ColorType,
+    {
+        self.staging.set_control_bits(
+            scanline,
+            Self::SCANLINES_PER_FRAME,
+            config.latch_blanking_count(),
+        );
+        self.staging
+            .set_brightness_bits(&config.lit_columns(brightness));
+        for y in 0..Self::HEIGHT {
+            // Every pixel in a row is on the same scanline.
+            if self.locate(0, y).scanline != scanline {
+                continue;
+            }
+            for x in 0..(Self::WIDTH * Self::CHAIN_LENGTH) {
+                let color = pixel(x, y);
+                let (red, green, blue) = depth_components(&color);
+                let bits = plane_bits(red, green, blue);
+                self.staging
+                    .set_pixel_planes(&self.locate(x, y), &bits, None);
+            }
+        }
+    }
+
+    /// The segments of the packed scanline, in the order they are sent to the panel.
+    ///
+    /// Each segment is a color plane, and plane `n` is repeated `2^n` times.
+    pub fn segments(&self) -> impl Iterator<Item = &[Word]> {
+        (0..Self::COLOR_DEPTH)
+            .flat_map(|plane| iter::repeat_n(plane, 1 << plane))
+            .map(|plane| self.staging.plane(plane))
+    }
+
+    /// Pack and send every scanline of a frame in turn.
+    ///
+    /// Each segment of a scanline is passed to `emit` (which would typically start a DMA transfer
+    /// and wait for it to finish) before the next scanline is packed, as they all share the same
+    /// staging buffer. The first error from `emit` stops the frame and is returned.
+    pub fn stream_frame<ColorType, F, E, G>(
+        &mut self,
+        config: &MatrixConfig<WIDTH, HEIGHT, CHAIN_LENGTH, COLOR_DEPTH, PER_FRAME_DENOMINATOR>,
+        brightness: u8,
+        mut pixel: F,
+        mut emit: G,
+    ) -> Result<(), E>
+    where
+        ColorType: Color<COLOR_DEPTH>,
+        F: FnMut(usize, usize) -> ColorType,
+        G: FnMut(&[Word]) -> Result<(), E>,
+    {
+        for scanline in 0..Self::SCANLINES_PER_FRAME {
+            self.pack_scanline(config, brightness, scanline, &mut pixel);
+            for segment in self.segments() {
+                emit(segment)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn locate(&self, x: usize, y: usize) -> PixelLocation {
+        FrameBuffer::<
+            WIDTH,
+            HEIGHT,
+            CHAIN_LENGTH,
+            COLOR_DEPTH,
+            PER_FRAME_DENOMINATOR,
+            WORDS_PER_PLANE,
+            SCANLINES_PER_FRAME,
+            Word,
+        >::locate_with(self.lane_mapping, self.lane_order, self.column_offset, x, y)
+    }
+}
+
+impl<
+        const WIDTH: usize,
+        const HEIGHT: usize,
+        const CHAIN_LENGTH: usize,
+        const COLOR_DEPTH: usize,
+        const PER_FRAME_DENOMINATOR: u8,
+        const WORDS_PER_PLANE: usize,
+        const SCANLINES_PER_FRAME: usize,
+        Word: BufferWord,
+    > Default
+    for ScanlineStream<
+        WIDTH,
+        HEIGHT,
+        CHAIN_LENGTH,
+        COLOR_DEPTH,
+        PER_FRAME_DENOMINATOR,
+        WORDS_PER_PLANE,
+        SCANLINES_PER_FRAME,
+        Word,
+    >
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use embedded_graphics_core::pixelcolor::{Rgb888, RgbColor};
+
+    use crate::declare_frame_buffer;
+
+    type TestStream = ScanlineStream<64, 32, 1, 8, 16, 64, 16>;
+
+    fn test_color(x: usize, y: usize) -> Rgb888 {
+        Rgb888::new(x as u8 * 4, y as u8 * 8, 0xA5)
+    }
+
+    #[test]
+    fn stream_matches_frame_buffer() {
+        let config = MatrixConfig::<64, 32, 1, 8, 16>::default();
+        let brightness = 100;
+        let mut fb = declare_frame_buffer!(64, 32, 8, 1, 16);
+        fb.configure(
+            config.latch_blanking_count(),
+            &config.lit_columns(brightness),
+        );
+        for y in 0..32 {
+            for x in 0..64 {
+                let color = test_color(x, y);
+                fb.set_pixel(x, y, color.r(), color.g(), color.b());
+            }
+        }
+
+        let mut stream = TestStream::new();
+        let mut emitted: usize = 0;
+        let result: Result<(), ()> =
+            stream.stream_frame(&config, brightness, test_color, |segment| {
+                // Segments are sent scanline by scanline, with each plane repeated by its weight.
+                let scanline = emitted / 255;
+                let index_in_scanline = emitted % 255;
+                let plane = (0..8)
+                    .find(|plane| index_in_scanline < (1 << (plane + 1)) - 1)
+                    .unwrap();
+                assert_eq!(
+                    segment,
+                    fb.plane(scanline, plane).unwrap(),
+                    "scanline {scanline} plane {plane}"
+                );
+                emitted += 1;
+                Ok(())
+            });
+        assert!(result.is_ok());
+        assert_eq!(emitted, 16 * 255);
+    }
+
+    #[test]
+    fn stream_stops_on_error() {
+        let config = MatrixConfig::<64, 32, 1, 8, 16>::default();
+        let mut stream = TestStream::new();
+        let mut emitted = 0;
+        let result = stream.stream_frame(
+            &config,
+            255,
+            |_, _| Rgb888::WHITE,
+            |_| {
+                emitted += 1;
+                if emitted == 3 {
+                    Err("underrun")
+                } else {
+                    Ok(())
+                }
+            },
+        );
+        assert_eq!(result, Err("underrun"));
+        assert_eq!(emitted, 3);
+    }
+
+    #[test]
+    fn pack_scanline_interleaved() {
+        let config = MatrixConfig::<64, 32, 1, 8, 16>::default();
+        let mut stream = TestStream::new();
+        stream.set_lane_mapping(LaneMapping::Interleaved);
+        let mut rows = [false; 32];
+        stream.pack_scanline(&config, 255, 3, |_, y| {
+            rows[y] = true;
+            Rgb888::BLACK
+        });
+        // Rows 6 and 7 share scanline 3, one on each lane.
+        let expected: [bool; 32] = core::array::from_fn(|y| y == 6 || y == 7);
+        assert_eq!(rows, expected);
+    }
+}
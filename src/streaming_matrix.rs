@@ -0,0 +1,319 @@
+use embedded_graphics_core::draw_target::DrawTarget;
+use embedded_graphics_core::geometry::{OriginDimensions, Size};
+use embedded_graphics_core::pixelcolor::PixelColor;
+use embedded_graphics_core::Pixel;
+
+use crate::const_not_zero;
+
+use super::buffer::FrameBuffer;
+use super::color::{Color, GammaTable};
+use super::config::MatrixConfig;
+use super::rgb_matrix::{corrected_plane_bits, MatrixError};
+
+/// A matrix without dirty tracking, for when every frame is redrawn from scratch.
+///
+/// `RgbMatrix` keeps a bit for every pixel so only the changed pixels are packed into the next
+/// frame buffer, but when every pixel changes each frame that is wasted memory and work. This only
+/// keeps the pixels, and `flush` packs all of them every time.
+pub struct StreamingMatrix<
+    ColorType,
+    const WIDTH: usize,
+    const HEIGHT: usize,
+    const CHAIN_LENGTH: usize,
+    const COLOR_DEPTH: usize,
+    const PER_FRAME_DENOMINATOR: u8,
+    const WORDS_PER_PLANE: usize,
+    const SCANLINES_PER_FRAME: usize,
+> {
+    config: MatrixConfig<WIDTH, HEIGHT, CHAIN_LENGTH, COLOR_DEPTH, PER_FRAME_DENOMINATOR>,
+
+    pixel_buffer: [[[ColorType; WIDTH]; CHAIN_LENGTH]; HEIGHT],
+
+    brightness: u8,
+
+    gamma: Option<GammaTable>,
+}
+
+impl<
+        ColorType,
+        const WIDTH: usize,
+        const HEIGHT: usize,
+        const CHAIN_LENGTH: usize,
+        const COLOR_DEPTH: usize,
+        const PER_FRAME_DENOMINATOR: u8,
+        const WORDS_PER_PLANE: usize,
+        const SCANLINES_PER_FRAME: usize,
+    >
+    StreamingMatrix<
+        ColorType,
+        WIDTH,
+        HEIGHT,
+        CHAIN_LENGTH,
+        COLOR_DEPTH,
+        PER_FRAME_DENOMINATOR,
+        WORDS_PER_PLANE,
+        SCANLINES_PER_FRAME,
+    >
+{
+    const_not_zero!(WIDTH, usize);
+    const_not_zero!(HEIGHT, usize);
+    const_not_zero!(CHAIN_LENGTH, usize);
+
+    const CHAIN_WIDTH: usize = Self::WIDTH * Self::CHAIN_LENGTH;
+
+    const DEFAULT_BRIGHTNESS: u8 = 128;
+
+    pub fn brightness(&self) -> u8 {
+        self.brightness
+    }
+
+    pub fn set_brightness(&mut self, brightness: u8) {
+        self.brightness = brightness;
+    }
+
+    pub fn gamma(&self) -> Option<&GammaTable> {
+        self.gamma.as_ref()
+    }
+
+    /// Set the gamma correction applied to colors as they're packed by `flush`.
+    pub fn set_gamma(&mut self, gamma: Option<GammaTable>) {
+        self.gamma = gamma;
+    }
+
+    /// The width and height seen by `set_pixel` and `DrawTarget`, after rotation.
+    fn logical_size(&self) -> (usize, usize) {
+        self.config
+            .rotation()
+            .logical_size(Self::CHAIN_WIDTH, Self::HEIGHT)
+    }
+
+    fn to_physical(&self, x: usize, y: usize) -> Result<(usize, usize), MatrixError> {
+        self.config
+            .rotation()
+            .to_physical(Self::CHAIN_WIDTH, Self::HEIGHT, x, y)
+            .ok_or(MatrixError::OutOfBounds)
+    }
+
+    /// Find which panel of the chain a pixel is on, as `(panel_index, panel_x, panel_y)`, the same
+    /// as `RgbMatrix::map_logical`.
+    pub fn map_logical(&self, x: usize, y: usize) -> Result<(usize, usize, usize), MatrixError> {
+        let (x, y) = self.to_physical(x, y)?;
+        Ok((x / Self::WIDTH, x % Self::WIDTH, y))
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, color: ColorType) -> Result<(), MatrixError> {
+        let (panel_index, panel_x, y) = self.map_logical(x, y)?;
+        self.pixel_buffer[y][panel_index][panel_x] = color;
+        Ok(())
+    }
+}
+
+impl<
+        ColorType,
+        const WIDTH: usize,
+        const HEIGHT: usize,
+        const CHAIN_LENGTH: usize,
+        const COLOR_DEPTH: usize,
+        const PER_FRAME_DENOMINATOR: u8,
+        const WORDS_PER_PLANE: usize,
+        const SCANLINES_PER_FRAME: usize,
+    >
+    StreamingMatrix<
+        ColorType,
+        WIDTH,
+        HEIGHT,
+        CHAIN_LENGTH,
+        COLOR_DEPTH,
+        PER_FRAME_DENOMINATOR,
+        WORDS_PER_PLANE,
+        SCANLINES_PER_FRAME,
+    >
+where
+    ColorType: Default + Copy + Color<COLOR_DEPTH>,
+{
+    pub fn new(
+        config: MatrixConfig<WIDTH, HEIGHT, CHAIN_LENGTH, COLOR_DEPTH, PER_FRAME_DENOMINATOR>,
+    ) -> Self {
+        // Force the compiler to evaluate all the const checks
+        let _ = Self::WIDTH;
+        let _ = Self::HEIGHT;
+        let _ = Self::CHAIN_LENGTH;
+
+        Self {
+            config,
+            pixel_buffer: [[[ColorType::default(); WIDTH]; CHAIN_LENGTH]; HEIGHT],
+            brightness: Self::DEFAULT_BRIGHTNESS,
+            gamma: None,
+        }
+    }
+
+    pub fn get_pixel(&self, x: usize, y: usize) -> Result<ColorType, MatrixError> {
+        let (panel_index, panel_x, y) = self.map_logical(x, y)?;
+        Ok(self.pixel_buffer[y][panel_index][panel_x])
+    }
+
+    /// Configure a frame buffer and pack every pixel and the brightness into it.
+    pub fn flush(
+        &self,
+        frame_buffer: &mut FrameBuffer<
+            WIDTH,
+            HEIGHT,
+            CHAIN_LENGTH,
+            COLOR_DEPTH,
+            PER_FRAME_DENOMINATOR,
+            WORDS_PER_PLANE,
+            SCANLINES_PER_FRAME,
+        >,
+    ) {
+        let lit_columns = self.config.lit_columns(self.brightness);
+        frame_buffer.configure(self.config.latch_blanking_count(), &lit_columns);
+        frame_buffer.apply_config(&self.config);
+        for (y, row) in self.pixel_buffer.iter().enumerate() {
+            for (panel_index, panel_row) in row.iter().enumerate() {
+                let trim = self.config.panel_brightness()[panel_index];
+                for (panel_x, color) in panel_row.iter().enumerate() {
+                    frame_buffer.set_pixel_planes(
+                        panel_index * Self::WIDTH + panel_x,
+                        y,
+                        &corrected_plane_bits(color, self.gamma.as_ref(), trim),
+                        None,
+                    );
+                }
+            }
+        }
+    }
+}
+
+impl<
+        ColorType,
+        const WIDTH: usize,
+        const HEIGHT: usize,
+        const CHAIN_LENGTH: usize,
+        const COLOR_DEPTH: usize,
+        const PER_FRAME_DENOMINATOR: u8,
+        const WORDS_PER_PLANE: usize,
+        const SCANLINES_PER_FRAME: usize,
+    > OriginDimensions
+    for StreamingMatrix<
+        ColorType,
+        WIDTH,
+        HEIGHT,
+        CHAIN_LENGTH,
+        COLOR_DEPTH,
+        PER_FRAME_DENOMINATOR,
+        WORDS_PER_PLANE,
+        SCANLINES_PER_FRAME,
+    >
+{
+    fn size(&self) -> Size {
+        let (width, height) = self.logical_size();
+        Size {
+            width: width as u32,
+            height: height as u32,
+        }
+    }
+}
+
+impl<
+        ColorType,
+        const WIDTH: usize,
+        const HEIGHT: usize,
+        const CHAIN_LENGTH: usize,
+        const COLOR_DEPTH: usize,
+        const PER_FRAME_DENOMINATOR: u8,
+        const WORDS_PER_PLANE: usize,
+        const SCANLINES_PER_FRAME: usize,
+    > DrawTarget
+    for StreamingMatrix<
+        ColorType,
+        WIDTH,
+        HEIGHT,
+        CHAIN_LENGTH,
+        COLOR_DEPTH,
+        PER_FRAME_DENOMINATOR,
+        WORDS_PER_PLANE,
+        SCANLINES_PER_FRAME,
+    >
+where
+    ColorType: PixelColor + Color<COLOR_DEPTH>,
+{
+    type Color = ColorType;
+
+    type Error = core::convert::Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(coord, color) in pixels {
+            if coord.x >= 0 && coord.y >= 0 {
+                // Ignore any errors
+                let _ = self.set_pixel(coord.x as usize, coord.y as usize, color);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use embedded_graphics_core::geometry::Point;
+    use embedded_graphics_core::pixelcolor::{Rgb888, RgbColor};
+
+    use crate::config::Rotation;
+    use crate::declare_frame_buffer;
+    use crate::rgb_matrix::RgbMatrix;
+
+    fn test_color(x: usize, y: usize) -> Rgb888 {
+        Rgb888::new(x as u8 * 4, y as u8 * 8, 0xA5)
+    }
+
+    #[test]
+    fn matches_dirty_flush() {
+        let config = MatrixConfig::default().with_panel_brightness([255, 100]);
+        let mut streaming = StreamingMatrix::<Rgb888, 32, 16, 2, 8, 8, 64, 8>::new(config);
+        let mut tracked = RgbMatrix::<Rgb888, 32, 16, 2, 8, 8, 64, 8, 32>::new(config);
+        let pixels = (0..16).flat_map(|y| {
+            (0..64).map(move |x| Pixel(Point::new(x as i32, y as i32), test_color(x, y)))
+        });
+        streaming.draw_iter(pixels.clone()).unwrap();
+        tracked.draw_iter(pixels).unwrap();
+        streaming.set_brightness(200);
+        tracked.set_brightness(200);
+        streaming.set_gamma(Some(GammaTable::squared()));
+        tracked.set_gamma(Some(GammaTable::squared()));
+
+        let mut streamed_fb = declare_frame_buffer!(32, 16, 8, 2, 8);
+        streaming.flush(&mut streamed_fb);
+        let mut tracked_fb = declare_frame_buffer!(32, 16, 8, 2, 8);
+        tracked.configure_frame_buffer(&mut tracked_fb);
+        tracked.set_pending(&mut tracked_fb);
+        assert_eq!(tracked_fb, streamed_fb);
+        assert_eq!(streaming.get_pixel(63, 15), Ok(test_color(63, 15)));
+    }
+
+    #[test]
+    fn spans_across_panels() {
+        let config = MatrixConfig::default()
+            .with_panel_brightness([255, 100])
+            .with_rotation(Rotation::Rot180);
+        let mut streaming = StreamingMatrix::<Rgb888, 32, 16, 2, 8, 8, 64, 8>::new(config);
+        let mut tracked = RgbMatrix::<Rgb888, 32, 16, 2, 8, 8, 64, 8, 32>::new(config);
+        // A single run of pixels crossing from one panel to the next.
+        let line = (20..44).map(|x| Pixel(Point::new(x, 5), Rgb888::CYAN));
+        streaming.draw_iter(line.clone()).unwrap();
+        tracked.draw_iter(line).unwrap();
+        for x in 0..64 {
+            assert_eq!(streaming.map_logical(x, 5), tracked.map_logical(x, 5));
+            assert_eq!(streaming.get_pixel(x, 5), tracked.get_pixel(x, 5));
+        }
+
+        let mut streamed_fb = declare_frame_buffer!(32, 16, 8, 2, 8);
+        streaming.flush(&mut streamed_fb);
+        let mut tracked_fb = declare_frame_buffer!(32, 16, 8, 2, 8);
+        tracked.render_to(&mut tracked_fb);
+        assert_eq!(tracked_fb, streamed_fb);
+    }
+}
@@ -1 +0,0 @@
-pub(crate) trait Sealed {}
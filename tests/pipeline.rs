@@ -0,0 +1,173 @@
+//! Draw on an `RgbMatrix`, swap frame buffers, and "send" them with `NullDma`, checking the words
+//! that would reach the panel.
+
+use embedded_graphics_core::pixelcolor::{Rgb555, RgbColor};
+use embedded_graphics_core::prelude::*;
+use embedded_graphics_core::primitives::Rectangle;
+
+use hub75_bcm::buffer::FrameBuffer;
+use hub75_bcm::config::MatrixConfig;
+use hub75_bcm::dma::null::NullDma;
+use hub75_bcm::dma::MatrixDma;
+use hub75_bcm::rgb_matrix::RgbMatrix;
+
+// An 8x4 panel at 1/2 scan, so each scanline has one row on each lane.
+const WIDTH: usize = 8;
+const HEIGHT: usize = 4;
+const COLOR_DEPTH: usize = 5;
+const WORDS_PER_PLANE: usize = 8;
+const SCANLINES_PER_FRAME: usize = 2;
+const SEGMENTS: usize = ((1 << COLOR_DEPTH) - 1) * SCANLINES_PER_FRAME;
+
+type TestFrameBuffer =
+    FrameBuffer<WIDTH, HEIGHT, 1, COLOR_DEPTH, 2, WORDS_PER_PLANE, SCANLINES_PER_FRAME>;
+type TestMatrix<'a> = RgbMatrix<
+    'a,
+    Rgb555,
+    WIDTH,
+    HEIGHT,
+    1,
+    COLOR_DEPTH,
+    2,
+    WORDS_PER_PLANE,
+    SCANLINES_PER_FRAME,
+    1,
+>;
+
+const COLOR_BITS: u16 = 0x3F;
+const ADDRESS_SHIFT: u16 = 8;
+
+/// The scene drawn by every test, as the color of each pixel.
+fn scene(x: usize, y: usize) -> Rgb555 {
+    if (2..6).contains(&x) && (1..3).contains(&y) {
+        Rgb555::new(0x1F, 0x0A, 0x15)
+    } else if (x, y) == (7, 3) {
+        Rgb555::new(0x01, 0x10, 0x00)
+    } else {
+        Rgb555::BLACK
+    }
+}
+
+fn draw_scene(matrix: &mut TestMatrix) {
+    matrix
+        .fill_solid(
+            &Rectangle::new(Point::new(2, 1), Size::new(4, 2)),
+            Rgb555::new(0x1F, 0x0A, 0x15),
+        )
+        .unwrap();
+    matrix
+        .draw_iter([Pixel(Point::new(7, 3), Rgb555::new(0x01, 0x10, 0x00))])
+        .unwrap();
+}
+
+/// The `(plane, scanline)` of each segment, in the order they are sent.
+fn segment_order() -> impl Iterator<Item = (usize, usize)> {
+    (0..COLOR_DEPTH)
+        .flat_map(|plane| core::iter::repeat_n(plane, 1 << plane))
+        .flat_map(|plane| (0..SCANLINES_PER_FRAME).map(move |scanline| (plane, scanline)))
+}
+
+/// The color bits of a single word: R1, G1, B1 for the top lane, then R2, G2, B2.
+fn expected_color_bits(plane: usize, scanline: usize, column: usize) -> u16 {
+    let lane_bits = |color: Rgb555| {
+        let bit = |component: u8| ((component >> plane) & 1) as u16;
+        bit(color.r()) | bit(color.g()) << 1 | bit(color.b()) << 2
+    };
+    // Rows in the top half are on the first lane, and the bottom half on the second.
+    lane_bits(scene(column, scanline)) | lane_bits(scene(column, scanline + HEIGHT / 2)) << 3
+}
+
+/// Start and stop a transfer of a frame buffer, returning the recording.
+fn send(frame_buffer: &mut TestFrameBuffer, recording: &mut [u16]) -> (usize, usize) {
+    let dma = NullDma::new(recording);
+    let mut transfer = unsafe { dma.start_reference(frame_buffer) }.unwrap();
+    assert!(transfer.poll());
+    let (dma, _) = transfer.stop().unwrap();
+    assert!(!dma.is_truncated());
+    assert_eq!(dma.frames_sent(), 1);
+    (dma.recorded_segments(), dma.recorded().len())
+}
+
+#[test]
+fn drawn_scene_is_sent() {
+    let config = MatrixConfig::default();
+    let mut matrix = TestMatrix::new(config);
+    let mut drawn = TestFrameBuffer::new();
+    let mut spare = TestFrameBuffer::new();
+    let mut blank = TestFrameBuffer::new();
+    matrix.configure_frame_buffer(&mut drawn);
+    matrix.configure_frame_buffer(&mut spare);
+    matrix.configure_frame_buffer(&mut blank);
+
+    matrix.set_pending(&mut drawn);
+    draw_scene(&mut matrix);
+    let shown = matrix.set_pending(&mut spare).unwrap();
+
+    let mut recording = [0u16; SEGMENTS * WORDS_PER_PLANE];
+    assert_eq!(
+        send(shown, &mut recording),
+        (SEGMENTS, SEGMENTS * WORDS_PER_PLANE)
+    );
+    let mut blank_recording = [0u16; SEGMENTS * WORDS_PER_PLANE];
+    send(&mut blank, &mut blank_recording);
+
+    let segments = recording.chunks(WORDS_PER_PLANE);
+    let blank_segments = blank_recording.chunks(WORDS_PER_PLANE);
+    for ((segment, blank_segment), (plane, scanline)) in
+        segments.zip(blank_segments).zip(segment_order())
+    {
+        // The first plane is sent while the previous scanline is still shown.
+        let address = match plane {
+            0 => (scanline + SCANLINES_PER_FRAME - 1) % SCANLINES_PER_FRAME,
+            _ => scanline,
+        };
+        for (column, (word, blank_word)) in segment.iter().zip(blank_segment).enumerate() {
+            assert_eq!(
+                word & COLOR_BITS,
+                expected_color_bits(plane, scanline, column),
+                "plane {plane} scanline {scanline} column {column}"
+            );
+            assert_eq!(
+                (word >> ADDRESS_SHIFT) as usize & 0x1F,
+                address,
+                "plane {plane} scanline {scanline} column {column}"
+            );
+            // Drawing only changes the color bits.
+            assert_eq!(word & !COLOR_BITS, blank_word & !COLOR_BITS);
+        }
+    }
+}
+
+#[test]
+fn swapped_buffer_catches_up() {
+    let mut matrix = TestMatrix::new(MatrixConfig::default());
+    let mut first = TestFrameBuffer::new();
+    let mut second = TestFrameBuffer::new();
+    matrix.configure_frame_buffer(&mut first);
+    matrix.configure_frame_buffer(&mut second);
+
+    matrix.set_pending(&mut first);
+    draw_scene(&mut matrix);
+    let shown = matrix.set_pending(&mut second).unwrap();
+    let mut shown_recording = [0u16; SEGMENTS * WORDS_PER_PLANE];
+    send(shown, &mut shown_recording);
+
+    // The dirty pixels were copied to the new pending frame buffer when it was swapped in, so it
+    // sends the same words.
+    let next = matrix.set_pending(shown).unwrap();
+    let mut next_recording = [0u16; SEGMENTS * WORDS_PER_PLANE];
+    send(next, &mut next_recording);
+    assert_eq!(shown_recording, next_recording);
+}
+
+#[test]
+fn short_recording_is_truncated() {
+    let mut frame_buffer = TestFrameBuffer::new();
+    let mut recording = [0u16; WORDS_PER_PLANE + 3];
+    let dma = NullDma::new(&mut recording);
+    let transfer = unsafe { dma.start_reference(&mut frame_buffer) }.unwrap();
+    let (dma, _) = transfer.stop().unwrap();
+    assert!(dma.is_truncated());
+    assert_eq!(dma.recorded().len(), WORDS_PER_PLANE + 3);
+    assert_eq!(dma.recorded_segments(), SEGMENTS);
+}